@@ -2,12 +2,14 @@
 /// |     Tipo      |  Representacion Binaria   |
 /// |---------------|------------------------   |
 /// |   Acquire     |            b'ACQUI'       |
-/// |   OkAcquire   |            b'OKACQ'       |
+/// |   OkAcquire   |       b'OKACQ;<term>'     |
 /// |   Release     |            b'RELEA'       |
+///
+/// El OkAcquire lleva el term del coordinador que otorga el lock.
 #[derive(PartialEq, Debug)]
 pub enum AcquireMessage {
     Acquire,
-    OkAcquire,
+    OkAcquire(u64),
     Release,
 }
 
@@ -16,26 +18,28 @@ impl AcquireMessage {
     /// ```rust
     /// AcquireMessage::Acquire.as_bytes() // => b'ACQUI'
     /// ```
-    pub fn as_bytes(&self) -> [u8; 5] {
+    pub fn as_bytes(&self) -> Vec<u8> {
         match self {
-            AcquireMessage::Acquire => *b"ACQUI",
-            AcquireMessage::OkAcquire => *b"OKACQ",
-            AcquireMessage::Release => *b"RELEA",
+            AcquireMessage::Acquire => b"ACQUI".to_vec(),
+            AcquireMessage::OkAcquire(term) => format!("OKACQ;{}", term).into_bytes(),
+            AcquireMessage::Release => b"RELEA".to_vec(),
         }
     }
 
-    /// Recibe un caracter binario. Devuelve el tipo de mensaje que corresponde a esa representacion binaria.
+    /// Recibe un mensaje binario. Devuelve el tipo de mensaje que corresponde a esa representacion binaria.
     ///```rust
     ///AcquireMessage::from_bytes(b'ACQUI'); // => Some(AcquireMessage::Acquire)
     ///AcquireMessage::from_bytes(b"Whatever"); // => None
     ///```
-
     pub fn from_bytes(bytes: &[u8]) -> Option<AcquireMessage> {
         match bytes {
             b"ACQUI" => Some(AcquireMessage::Acquire),
-            b"OKACQ" => Some(AcquireMessage::OkAcquire),
             b"RELEA" => Some(AcquireMessage::Release),
-            _ => None,
+            _ => {
+                let term = bytes.strip_prefix(b"OKACQ;")?;
+                let term = std::str::from_utf8(term).ok()?.parse::<u64>().ok()?;
+                Some(AcquireMessage::OkAcquire(term))
+            }
         }
     }
 }
@@ -46,9 +50,9 @@ mod tests {
 
     #[test]
     fn test_as_bytes() {
-        assert_eq!(*b"ACQUI", AcquireMessage::Acquire.as_bytes());
-        assert_eq!(*b"OKACQ", AcquireMessage::OkAcquire.as_bytes());
-        assert_eq!(*b"RELEA", AcquireMessage::Release.as_bytes());
+        assert_eq!(b"ACQUI".to_vec(), AcquireMessage::Acquire.as_bytes());
+        assert_eq!(b"OKACQ;2".to_vec(), AcquireMessage::OkAcquire(2).as_bytes());
+        assert_eq!(b"RELEA".to_vec(), AcquireMessage::Release.as_bytes());
     }
 
     #[test]
@@ -58,12 +62,13 @@ mod tests {
             AcquireMessage::from_bytes("ACQUI".as_bytes())
         );
         assert_eq!(
-            Some(AcquireMessage::OkAcquire),
-            AcquireMessage::from_bytes("OKACQ".as_bytes())
+            Some(AcquireMessage::OkAcquire(2)),
+            AcquireMessage::from_bytes("OKACQ;2".as_bytes())
         );
         assert_eq!(
             Some(AcquireMessage::Release),
             AcquireMessage::from_bytes("RELEA".as_bytes())
         );
+        assert_eq!(None, AcquireMessage::from_bytes("OKACQ".as_bytes()));
     }
 }
@@ -1,18 +1,27 @@
 use crate::blockchain::BlockchainRecord;
 
+/// Mensajes para agregar una nota a la cadena.
+/// - **FromCoordinator**: El coordinador difunde un registro nuevo junto con su posicion en la
+///   cadena y el hash del registro anterior, para que cada nodo lo verifique antes de agregarlo.
+/// - **ToCoordinator**: Un nodo que tiene el lock le pide al coordinador que agregue una nota.
+///
+/// |     Tipo        |  Representacion                                                     |
+/// |-----------------|---------------------------------------------------------------------|
+/// | FromCoordinator | GRADE_FROM_COORDINATOR;<indice>;<hash anterior>;<nombre>;<nota>;<hash> |
+/// | ToCoordinator   | GRADE_TO_COORDINATOR;<nombre>;<nota>                                |
 #[derive(PartialEq, Debug)]
 pub enum AddGradeMessage {
-    FromCoordinator(BlockchainRecord),
+    FromCoordinator(u64, u64, BlockchainRecord),
     ToCoordinator(String, f64),
 }
 
 impl AddGradeMessage {
     pub fn as_string(&self) -> String {
         match self {
-            AddGradeMessage::FromCoordinator(record) => {
+            AddGradeMessage::FromCoordinator(index, previous_hash, record) => {
                 format!(
-                    "GRADE_FROM_COORDINATOR;{};{};{}",
-                    record.student_name, record.grade, record.hash
+                    "GRADE_FROM_COORDINATOR;{};{};{};{};{}",
+                    index, previous_hash, record.student_name, record.grade, record.hash
                 )
             }
             AddGradeMessage::ToCoordinator(student_name, grade) => {
@@ -23,15 +32,19 @@ impl AddGradeMessage {
 
     pub fn from_string(string: String) -> Option<AddGradeMessage> {
         let tokens = string.split(';').collect::<Vec<&str>>();
-        match tokens[0] {
-            "GRADE_FROM_COORDINATOR" => Some(AddGradeMessage::FromCoordinator(BlockchainRecord {
-                student_name: String::from(tokens[1]),
-                grade: tokens[2].parse::<f64>().unwrap(),
-                hash: tokens[3].parse::<u64>().unwrap(),
-            })),
-            "GRADE_TO_COORDINATOR" => Some(AddGradeMessage::ToCoordinator(
+        match (tokens[0], tokens.len()) {
+            ("GRADE_FROM_COORDINATOR", 6) => Some(AddGradeMessage::FromCoordinator(
+                tokens[1].parse::<u64>().ok()?,
+                tokens[2].parse::<u64>().ok()?,
+                BlockchainRecord {
+                    student_name: String::from(tokens[3]),
+                    grade: tokens[4].parse::<f64>().ok()?,
+                    hash: tokens[5].parse::<u64>().ok()?,
+                },
+            )),
+            ("GRADE_TO_COORDINATOR", 3) => Some(AddGradeMessage::ToCoordinator(
                 String::from(tokens[1]),
-                tokens[2].parse::<f64>().unwrap(),
+                tokens[2].parse::<f64>().ok()?,
             )),
             _ => None,
         }
@@ -45,12 +58,16 @@ mod tests {
     #[test]
     fn test_from_coordinator_as_string() {
         assert_eq!(
-            *b"GRADE_FROM_COORDINATOR;asd;12.4;383838",
-            AddGradeMessage::FromCoordinator(BlockchainRecord {
-                student_name: "asd".to_string(),
-                grade: 12.4,
-                hash: 383838,
-            })
+            *b"GRADE_FROM_COORDINATOR;2;1234;asd;12.4;383838",
+            AddGradeMessage::FromCoordinator(
+                2,
+                1234,
+                BlockchainRecord {
+                    student_name: "asd".to_string(),
+                    grade: 12.4,
+                    hash: 383838,
+                }
+            )
             .as_string()
             .as_bytes()
         );
@@ -78,13 +95,17 @@ mod tests {
     fn test_from_coordinator_from_string() {
         assert_eq!(
             AddGradeMessage::from_string(String::from(
-                "GRADE_FROM_COORDINATOR;aaaa bbbb;123.123;9393939"
+                "GRADE_FROM_COORDINATOR;0;0;aaaa bbbb;123.123;9393939"
             )),
-            Some(AddGradeMessage::FromCoordinator(BlockchainRecord {
-                student_name: "aaaa bbbb".to_string(),
-                grade: 123.123,
-                hash: 9393939,
-            }))
+            Some(AddGradeMessage::FromCoordinator(
+                0,
+                0,
+                BlockchainRecord {
+                    student_name: "aaaa bbbb".to_string(),
+                    grade: 123.123,
+                    hash: 9393939,
+                }
+            ))
         );
     }
 
@@ -95,4 +116,20 @@ mod tests {
             None
         );
     }
+
+    #[test]
+    fn test_malformed_from_string() {
+        assert_eq!(
+            AddGradeMessage::from_string(String::from("GRADE_TO_COORDINATOR;ueu")),
+            None
+        );
+        assert_eq!(
+            AddGradeMessage::from_string(String::from("GRADE_FROM_COORDINATOR;0;0;a;x;1")),
+            None
+        );
+        assert_eq!(
+            AddGradeMessage::from_string(String::from("GRADE_FROM_COORDINATOR;a;1;1")),
+            None
+        );
+    }
 }
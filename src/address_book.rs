@@ -0,0 +1,76 @@
+use std::net::SocketAddr;
+
+use crate::data_file::DataFile;
+use crate::logger::log;
+
+/// Archivo en el que un nodo iniciado con `--data-dir` guarda las direcciones de los vecinos
+/// que conoce, una por linea (`node-<id>.peers`), para no depender solo de las semillas al
+/// reiniciarse.
+pub struct AddressBook {
+    file: DataFile,
+}
+
+impl AddressBook {
+    pub fn open(dir: &str, node_id: u64) -> Result<AddressBook, String> {
+        Ok(AddressBook {
+            file: DataFile::open(dir, node_id, "peers", "address book")?,
+        })
+    }
+
+    /// Direcciones guardadas. Las lineas que no son una direccion se ignoran.
+    pub fn load(&self) -> Result<Vec<SocketAddr>, String> {
+        let content = self.file.read()?.unwrap_or_default();
+        Ok(content
+            .lines()
+            .filter_map(|line| match line.parse() {
+                Ok(address) => Some(address),
+                Err(_error) => {
+                    log(format!(
+                        "Ignoring invalid address {:?} in {:?}",
+                        line,
+                        self.file.path()
+                    ));
+                    None
+                }
+            })
+            .collect())
+    }
+
+    pub fn save(&self, addresses: &[SocketAddr]) -> Result<(), String> {
+        let content = addresses
+            .iter()
+            .map(|address| format!("{}\n", address))
+            .collect::<String>();
+        self.file.replace(&content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_file::temp_path;
+    use std::fs;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_path("address-book-round-trip");
+        let book = AddressBook::open(&dir, 6060).unwrap();
+        assert_eq!(book.load(), Ok(Vec::new()));
+        let addresses = vec![
+            "127.0.0.1:6061".parse().unwrap(),
+            "[::1]:6062".parse().unwrap(),
+        ];
+        book.save(&addresses).unwrap();
+        assert_eq!(book.load(), Ok(addresses));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_ignores_invalid_lines() {
+        let dir = temp_path("address-book-invalid");
+        let book = AddressBook::open(&dir, 6060).unwrap();
+        fs::write(book.file.path(), "127.0.0.1:6061\nnope\n").unwrap();
+        assert_eq!(book.load(), Ok(vec!["127.0.0.1:6061".parse().unwrap()]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -3,6 +3,8 @@ use std::fmt;
 use std::fmt::Formatter;
 use std::hash::{Hash, Hasher};
 
+use crate::chain_head::ChainHead;
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct BlockchainRecord {
     pub student_name: String,
@@ -10,6 +12,25 @@ pub struct BlockchainRecord {
     pub hash: u64,
 }
 
+impl BlockchainRecord {
+    /// Representacion usada en los mensajes: `<nombre>,<nota>,<hash>`.
+    pub fn as_string(&self) -> String {
+        format!("{},{},{}", self.student_name, self.grade, self.hash)
+    }
+
+    pub fn from_string(string: &str) -> Option<BlockchainRecord> {
+        let fields = string.split(',').collect::<Vec<&str>>();
+        if fields.len() != 3 {
+            return None;
+        }
+        Some(BlockchainRecord {
+            student_name: fields[0].to_string(),
+            grade: fields[1].parse().ok()?,
+            hash: fields[2].parse().ok()?,
+        })
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Blockchain {
     records: Vec<BlockchainRecord>,
@@ -54,12 +75,19 @@ impl Blockchain {
             records: Vec::new(),
         }
     }
-    pub fn clone(&self) -> Blockchain {
-        Blockchain {
-            records: self.records.clone(),
+    /// Hash de la cadena formada por los primeros `height` registros (0 si esta vacia), o `None`
+    /// si la cadena es mas corta.
+    pub fn hash_at(&self, height: u64) -> Option<u64> {
+        match height {
+            0 => Some(0),
+            _ => self.record_at(height - 1).map(|record| record.hash),
         }
     }
 
+    pub fn record_at(&self, index: u64) -> Option<&BlockchainRecord> {
+        self.records.get(index as usize)
+    }
+
     pub fn last_record(&self) -> Option<&BlockchainRecord> {
         self.records.last()
     }
@@ -77,48 +105,61 @@ impl Blockchain {
         });
     }
 
+    /// Agrega el registro sin verificarlo. Solo se usa para armar cadenas invalidas en los tests.
+    #[cfg(test)]
     pub fn add_record(&mut self, record: BlockchainRecord) {
         self.records.push(record)
     }
 
-    pub fn is_valid(&self) -> bool {
-        let mut last_hash = 0;
-        for record in &self.records {
-            if !is_valid(record, last_hash) {
-                return false;
-            }
-            last_hash = generate_hash(record.student_name.clone(), record.grade, last_hash);
+    /// Agrega el registro solo si encadena con el ultimo de la cadena.
+    pub fn append_verified(&mut self, record: BlockchainRecord) -> bool {
+        if !is_valid(&record, self.head().hash) {
+            return false;
         }
+        self.records.push(record);
         true
     }
 
-    pub fn from_str(blockchain_as_str: String) -> Blockchain {
-        let splitted_blockhain = blockchain_as_str.split(';');
-        let mut new_blockchain = Blockchain::new();
-        for record in splitted_blockhain {
-            let fields = record.split(',').collect::<Vec<&str>>();
-            if fields.len() > 2 {
-                let record = BlockchainRecord {
-                    student_name: fields[0].to_string(),
-                    grade: fields[1].to_string().parse().unwrap(),
-                    hash: fields[2].to_string().parse().unwrap(),
-                };
-                new_blockchain.add_record(record);
-            }
+    /// Descarta los registros a partir de la posicion `height`.
+    pub fn truncate(&mut self, height: u64) {
+        self.records.truncate(height as usize);
+    }
+
+    pub fn height(&self) -> u64 {
+        self.records.len() as u64
+    }
+
+    pub fn head(&self) -> ChainHead {
+        ChainHead {
+            height: self.height(),
+            hash: self.last_record().map_or(0, |record| record.hash),
         }
-        new_blockchain
     }
 
-    pub fn as_str(&self) -> String {
-        let mut result = String::new();
-        for record in &self.records {
-            result.push_str(&*format!(
-                "{},{},{};",
-                record.student_name, record.grade, record.hash
-            ));
+    /// Hasta `count` registros a partir de la posicion `from` (empezando en 0).
+    pub fn records_from(&self, from: u64, count: u64) -> Vec<BlockchainRecord> {
+        self.records
+            .iter()
+            .skip(from as usize)
+            .take(count as usize)
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.first_invalid_index().is_none()
+    }
+
+    /// Posicion del primer registro que no encadena con el anterior, si hay alguno.
+    pub fn first_invalid_index(&self) -> Option<u64> {
+        let mut last_hash = 0;
+        for (index, record) in self.records.iter().enumerate() {
+            if !is_valid(record, last_hash) {
+                return Some(index as u64);
+            }
+            last_hash = record.hash;
         }
-        result.pop();
-        result
+        None
     }
 }
 
@@ -136,7 +177,7 @@ mod tests {
 
     #[test]
     fn test_empty_blockchain_is_valid() {
-        assert_eq!(Blockchain::new().is_valid(), true)
+        assert!(Blockchain::new().is_valid())
     }
 
     #[test]
@@ -144,7 +185,7 @@ mod tests {
         let mut blockchain = Blockchain::new();
         blockchain.add_grade(String::from("Dylan"), 10.0);
         blockchain.add_grade(String::from("Gustavo"), 7.99);
-        assert_eq!(blockchain.is_valid(), true)
+        assert!(blockchain.is_valid())
     }
 
     #[test]
@@ -155,7 +196,21 @@ mod tests {
             grade: 10.0,
             hash: 0,
         });
-        assert_eq!(blockchain.is_valid(), false)
+        assert!(!blockchain.is_valid())
+    }
+
+    #[test]
+    fn test_first_invalid_index() {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_grade(String::from("Dylan"), 10.0);
+        assert_eq!(blockchain.first_invalid_index(), None);
+        blockchain.add_record(BlockchainRecord {
+            student_name: String::from("Gustavo"),
+            grade: 7.99,
+            hash: 0,
+        });
+        blockchain.add_grade(String::from("Juan"), 8.0);
+        assert_eq!(blockchain.first_invalid_index(), Some(1));
     }
 
     #[test]
@@ -168,7 +223,61 @@ mod tests {
             grade,
             hash: generate_hash(student_name, grade, 0),
         });
-        assert_eq!(blockchain.is_valid(), true)
+        assert!(blockchain.is_valid())
+    }
+
+    fn range_first_hash(blockchain: &Blockchain) -> u64 {
+        blockchain.records_from(0, 1)[0].hash
+    }
+
+    #[test]
+    fn test_head_and_records_from() {
+        let mut blockchain = Blockchain::new();
+        assert_eq!(blockchain.head(), ChainHead { height: 0, hash: 0 });
+        blockchain.add_grade(String::from("Dylan"), 10.0);
+        blockchain.add_grade(String::from("Gustavo"), 7.99);
+        blockchain.add_grade(String::from("Juan"), 8.0);
+        assert_eq!(blockchain.head().height, 3);
+        assert_eq!(
+            blockchain.head().hash,
+            blockchain.last_record().unwrap().hash
+        );
+        let range = blockchain.records_from(1, 5);
+        assert_eq!(range.len(), 2);
+        assert_eq!(range[0].student_name, "Gustavo");
+        assert!(blockchain.records_from(3, 5).is_empty());
+        assert_eq!(blockchain.hash_at(0), Some(0));
+        assert_eq!(blockchain.hash_at(1), Some(range_first_hash(&blockchain)));
+        assert_eq!(blockchain.hash_at(3), Some(blockchain.head().hash));
+        assert_eq!(blockchain.hash_at(4), None);
+        blockchain.truncate(1);
+        assert_eq!(blockchain.head().height, 1);
+    }
+
+    #[test]
+    fn test_append_verified_rejects_records_that_dont_chain() {
+        let mut source = Blockchain::new();
+        source.add_grade(String::from("Dylan"), 10.0);
+        source.add_grade(String::from("Gustavo"), 7.99);
+        let records = source.records_from(0, 2);
+
+        let mut blockchain = Blockchain::new();
+        assert!(!blockchain.append_verified(records[1].clone()));
+        assert!(blockchain.append_verified(records[0].clone()));
+        assert!(blockchain.append_verified(records[1].clone()));
+        assert_eq!(blockchain, source);
+    }
+
+    #[test]
+    fn test_record_round_trip() {
+        let record = BlockchainRecord {
+            student_name: String::from("asd"),
+            grade: 10.5,
+            hash: 123,
+        };
+        assert_eq!(record.as_string(), "asd,10.5,123");
+        assert_eq!(BlockchainRecord::from_string("asd,10.5,123"), Some(record));
+        assert_eq!(BlockchainRecord::from_string("asd,x,123"), None);
     }
 
     #[test]
@@ -188,6 +297,6 @@ mod tests {
                 generate_hash(first_student_name, first_grade, 0),
             ),
         });
-        assert_eq!(blockchain.is_valid(), true)
+        assert!(blockchain.is_valid())
     }
 }
@@ -1,29 +1,89 @@
-use crate::blockchain::Blockchain;
+use crate::blockchain::BlockchainRecord;
+use crate::chain_head::ChainHead;
 
+/// Mensajes de sincronizacion de la cadena.
+/// - **AnnounceHead**: Un nodo anuncia su cabeza. Los vecinos responden con la suya.
+/// - **ChainHead**: Respuesta a un AnnounceHead con la cabeza del nodo.
+/// - **GetRange**: Pide hasta `count` registros a partir de la posicion `from`.
+/// - **Range**: Registros pedidos, a partir de la posicion `from`.
+/// - **GetHashAt**: Pide el hash de la cadena formada por los primeros `altura` registros.
+/// - **HashAt**: Respuesta a un GetHashAt. No se responde si la cadena es mas corta.
+///
+/// |     Tipo      |  Representacion                          |
+/// |---------------|------------------------------------------|
+/// | AnnounceHead  | AnnounceHead:<altura>,<hash>             |
+/// | ChainHead     | ChainHead:<altura>,<hash>                |
+/// | GetRange      | GetRange:<from>,<count>                  |
+/// | Range         | Range:<from>:<nombre>,<nota>,<hash>;...  |
+/// | GetHashAt     | GetHashAt:<altura>                       |
+/// | HashAt        | HashAt:<altura>,<hash>                   |
 #[derive(PartialEq, Debug)]
 pub enum BlockchainMessage {
-    AskForBlockchain,
-    BlockchainResult(Blockchain),
+    AnnounceHead(ChainHead),
+    ChainHead(ChainHead),
+    GetRange(u64, u64),
+    Range(u64, Vec<BlockchainRecord>),
+    GetHashAt(u64),
+    HashAt(u64, u64),
 }
 
 impl BlockchainMessage {
     pub fn as_string(&self) -> String {
         match self {
-            BlockchainMessage::AskForBlockchain => "AskForBlockchain".to_string(),
-            BlockchainMessage::BlockchainResult(blockchain) => {
-                format!("BlockchainResult:{}", blockchain.as_str())
-            }
+            BlockchainMessage::AnnounceHead(head) => format!("AnnounceHead:{}", head.as_string()),
+            BlockchainMessage::ChainHead(head) => format!("ChainHead:{}", head.as_string()),
+            BlockchainMessage::GetRange(from, count) => format!("GetRange:{},{}", from, count),
+            BlockchainMessage::Range(from, records) => format!(
+                "Range:{}:{}",
+                from,
+                records
+                    .iter()
+                    .map(|record| record.as_string())
+                    .collect::<Vec<String>>()
+                    .join(";")
+            ),
+            BlockchainMessage::GetHashAt(height) => format!("GetHashAt:{}", height),
+            BlockchainMessage::HashAt(height, hash) => format!("HashAt:{},{}", height, hash),
         }
     }
 
-    /// Example: BlockchainResult:asd,10.0,1234;qwe,9.0,5125
+    /// Example: Range:2:asd,10.0,1234;qwe,9.0,5125
     pub fn from_string(string: String) -> Option<BlockchainMessage> {
-        let tokens = string.split(':').collect::<Vec<&str>>();
-        match tokens[0] {
-            "AskForBlockchain" => Some(BlockchainMessage::AskForBlockchain),
-            "BlockchainResult" => Some(BlockchainMessage::BlockchainResult(Blockchain::from_str(
-                tokens[1].to_string(),
-            ))),
+        let (kind, content) = string.split_once(':')?;
+        match kind {
+            "AnnounceHead" => Some(BlockchainMessage::AnnounceHead(ChainHead::from_string(
+                content,
+            )?)),
+            "ChainHead" => Some(BlockchainMessage::ChainHead(ChainHead::from_string(
+                content,
+            )?)),
+            "GetRange" => {
+                let (from, count) = content.split_once(',')?;
+                Some(BlockchainMessage::GetRange(
+                    from.parse::<u64>().ok()?,
+                    count.parse::<u64>().ok()?,
+                ))
+            }
+            "Range" => {
+                let (from, records) = content.split_once(':')?;
+                let records = if records.is_empty() {
+                    Vec::new()
+                } else {
+                    records
+                        .split(';')
+                        .map(BlockchainRecord::from_string)
+                        .collect::<Option<Vec<BlockchainRecord>>>()?
+                };
+                Some(BlockchainMessage::Range(from.parse::<u64>().ok()?, records))
+            }
+            "GetHashAt" => Some(BlockchainMessage::GetHashAt(content.parse::<u64>().ok()?)),
+            "HashAt" => {
+                let (height, hash) = content.split_once(',')?;
+                Some(BlockchainMessage::HashAt(
+                    height.parse::<u64>().ok()?,
+                    hash.parse::<u64>().ok()?,
+                ))
+            }
             _ => None,
         }
     }
@@ -32,121 +92,117 @@ impl BlockchainMessage {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::blockchain::BlockchainRecord;
 
-    #[test]
-    fn test_ask_for_blockchain_as_string() {
-        assert_eq!(
-            *b"AskForBlockchain",
-            BlockchainMessage::AskForBlockchain.as_string().as_bytes()
-        );
+    fn records() -> Vec<BlockchainRecord> {
+        vec![
+            BlockchainRecord {
+                student_name: "asd".to_string(),
+                grade: 10.5,
+                hash: 123,
+            },
+            BlockchainRecord {
+                student_name: "qwe".to_string(),
+                grade: 8.5,
+                hash: 678,
+            },
+        ]
     }
 
     #[test]
-    fn test_blockchain_result_as_string() {
-        let mut blockchain = Blockchain::new();
-        let record = BlockchainRecord {
-            student_name: "asd".to_string(),
-            grade: 10.5,
-            hash: 123,
+    fn test_heads_as_string() {
+        let head = ChainHead {
+            height: 2,
+            hash: 678,
         };
-        blockchain.add_record(record);
         assert_eq!(
-            String::from("BlockchainResult:asd,10.5,123"),
-            BlockchainMessage::BlockchainResult(blockchain).as_string()
+            BlockchainMessage::AnnounceHead(head).as_string(),
+            "AnnounceHead:2,678"
         );
-    }
-
-    #[test]
-    fn test_ask_for_blockchain_from_string() {
         assert_eq!(
-            BlockchainMessage::from_string(String::from("AskForBlockchain")),
-            Some(BlockchainMessage::AskForBlockchain)
+            BlockchainMessage::ChainHead(head).as_string(),
+            "ChainHead:2,678"
         );
     }
 
     #[test]
-    fn test_blockchain_result_from_string() {
-        let mut expected = Blockchain::new();
-        let record = BlockchainRecord {
-            student_name: "asd".to_string(),
-            grade: 10.5,
-            hash: 1234,
+    fn test_heads_from_string() {
+        let head = ChainHead {
+            height: 2,
+            hash: 678,
         };
-        expected.add_record(record);
         assert_eq!(
-            BlockchainMessage::from_string(String::from("BlockchainResult:asd,10.5,1234")),
-            Some(BlockchainMessage::BlockchainResult(expected))
+            BlockchainMessage::from_string(String::from("AnnounceHead:2,678")),
+            Some(BlockchainMessage::AnnounceHead(head))
+        );
+        assert_eq!(
+            BlockchainMessage::from_string(String::from("ChainHead:2,678")),
+            Some(BlockchainMessage::ChainHead(head))
         );
     }
 
     #[test]
-    fn test_none_from_string() {
+    fn test_get_range_round_trip() {
         assert_eq!(
-            BlockchainMessage::from_string(String::from("asdadasd")),
-            None
+            BlockchainMessage::GetRange(4, 16).as_string(),
+            "GetRange:4,16"
+        );
+        assert_eq!(
+            BlockchainMessage::from_string(String::from("GetRange:4,16")),
+            Some(BlockchainMessage::GetRange(4, 16))
         );
     }
 
     #[test]
-    fn test_empty_blockchain_result_as_string() {
-        let mut blockchain = Blockchain::new();
+    fn test_hash_at_round_trip() {
+        assert_eq!(BlockchainMessage::GetHashAt(5).as_string(), "GetHashAt:5");
+        assert_eq!(
+            BlockchainMessage::from_string(String::from("GetHashAt:5")),
+            Some(BlockchainMessage::GetHashAt(5))
+        );
+        assert_eq!(BlockchainMessage::HashAt(5, 99).as_string(), "HashAt:5,99");
         assert_eq!(
-            String::from("BlockchainResult:"),
-            BlockchainMessage::BlockchainResult(blockchain).as_string()
+            BlockchainMessage::from_string(String::from("HashAt:5,99")),
+            Some(BlockchainMessage::HashAt(5, 99))
         );
     }
 
     #[test]
-    fn test_empty_blockchain_result_from_string() {
-        let mut expected = Blockchain::new();
+    fn test_range_as_string() {
+        assert_eq!(
+            BlockchainMessage::Range(3, records()).as_string(),
+            "Range:3:asd,10.5,123;qwe,8.5,678"
+        );
         assert_eq!(
-            BlockchainMessage::from_string(String::from("BlockchainResult:")),
-            Some(BlockchainMessage::BlockchainResult(expected))
+            BlockchainMessage::Range(3, Vec::new()).as_string(),
+            "Range:3:"
         );
     }
 
     #[test]
-    fn test_blockchain_result_multiple_records_as_string() {
-        let mut blockchain = Blockchain::new();
-        let a_record = BlockchainRecord {
-            student_name: "asd".to_string(),
-            grade: 10.5,
-            hash: 123,
-        };
-        let another_record = BlockchainRecord {
-            student_name: "qwe".to_string(),
-            grade: 8.5,
-            hash: 678,
-        };
-        blockchain.add_record(a_record);
-        blockchain.add_record(another_record);
+    fn test_range_from_string() {
+        assert_eq!(
+            BlockchainMessage::from_string(String::from("Range:3:asd,10.5,123;qwe,8.5,678")),
+            Some(BlockchainMessage::Range(3, records()))
+        );
         assert_eq!(
-            String::from("BlockchainResult:asd,10.5,123;qwe,8.5,678"),
-            BlockchainMessage::BlockchainResult(blockchain).as_string()
+            BlockchainMessage::from_string(String::from("Range:3:")),
+            Some(BlockchainMessage::Range(3, Vec::new()))
         );
     }
 
     #[test]
-    fn test_blockchain_result_multiple_records_from_string() {
-        let mut expected = Blockchain::new();
-        let record = BlockchainRecord {
-            student_name: "asd".to_string(),
-            grade: 10.5,
-            hash: 123,
-        };
-        let another_record = BlockchainRecord {
-            student_name: "qwe".to_string(),
-            grade: 8.5,
-            hash: 678,
-        };
-        expected.add_record(record);
-        expected.add_record(another_record);
+    fn test_none_from_string() {
+        assert_eq!(
+            BlockchainMessage::from_string(String::from("asdadasd")),
+            None
+        );
         assert_eq!(
-            BlockchainMessage::from_string(String::from(
-                "BlockchainResult:asd,10.5,123;qwe,8.5,678"
-            )),
-            Some(BlockchainMessage::BlockchainResult(expected))
+            BlockchainMessage::from_string(String::from("GetRange:4")),
+            None
+        );
+        assert_eq!(
+            BlockchainMessage::from_string(String::from("Range:3:asd,x,123")),
+            None
         );
     }
 }
@@ -1,97 +1,398 @@
-use std::collections::HashMap;
-use std::net::UdpSocket;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::sync::{Arc, Condvar, Mutex};
-use std::time::Duration;
-use std::{thread, usize};
+use std::time::{Duration, Instant};
 
 use crate::acquire_message::AcquireMessage;
 use crate::add_grade_message::AddGradeMessage;
-use crate::blockchain::Blockchain;
+use crate::address_book::AddressBook;
+use crate::blockchain::{Blockchain, BlockchainRecord};
 use crate::blockchain_message::BlockchainMessage;
+use crate::capture_record::CaptureRecord;
+use crate::chain_head::ChainHead;
+use crate::chain_store::ChainStore;
+use crate::chain_sync::{ChainSync, RANGE_SIZE};
+use crate::clock::Clock;
 use crate::coordinator_state::CoordinatorState;
+use crate::discovery_config::DiscoveryConfig;
 use crate::dist_mutex::DistMutex;
+use crate::election_algorithm::ElectionAlgorithm;
 use crate::election_message::ElectionMessage;
+use crate::envelope::Envelope;
+use crate::failure_detector::FailureDetector;
+use crate::fault_direction::FaultDirection;
+use crate::fault_injector::FaultInjector;
+use crate::fault_rule::FaultRule;
+use crate::heartbeat_message::HeartbeatMessage;
 use crate::ip_parser;
+use crate::keyring::Keyring;
 use crate::logger::log;
-use crate::sender::send;
-use crate::BUFFER_SIZE;
+use crate::membership_message::MembershipMessage;
+use crate::message_capture::MessageCapture;
+use crate::messenger::Messenger;
+use crate::node_config::NodeConfig;
+use crate::node_identity::NodeIdentity;
+use crate::peer_directory::PeerDirectory;
+use crate::peer_status::PeerStatus;
+use crate::pending_records::PendingRecords;
+use crate::reconciliation::Reconciliation;
+use crate::replay_guard::ReplayGuard;
+use crate::retry_policy::RetryPolicy;
+use crate::system_clock::SystemClock;
+use crate::timeouts::Timeouts;
+use crate::transport::Transport;
+use crate::udp_transport::UdpTransport;
+use crate::xorshift::Xorshift;
 
 pub struct BlockchainNode {
-    port: usize,
-    socket: UdpSocket,
-    leader_port: Arc<Mutex<Option<usize>>>,
-    neighbor_addresses: Vec<String>,
+    identity: NodeIdentity,
+    address: SocketAddr,
+    /// Direccion en la que escucha el nodo, distinta de `address` si se uso `--advertise`.
+    listen_address: SocketAddr,
+    transport: Arc<dyn Transport>,
+    clock: Arc<dyn Clock>,
+    messenger: Messenger,
+    leader_id: Arc<Mutex<Option<u64>>>,
+    neighbor_addresses: Vec<SocketAddr>,
+    /// Vecinos que avisaron que se iban (LEAVE). No se los vuelve a sumar por lo que cuenten
+    /// otros nodos, solo si vuelven a contactar a este.
+    departed: HashSet<SocketAddr>,
+    address_book: Option<AddressBook>,
+    discovery: Option<DiscoveryConfig>,
+    peer_directory: PeerDirectory,
     blockchain: Blockchain,
-    got_ok: Arc<(Mutex<bool>, Condvar)>,
+    chain_store: Option<ChainStore>,
+    recovered_chain: bool,
+    election: Arc<dyn ElectionAlgorithm>,
     is_in_election: Arc<(Mutex<bool>, Condvar)>,
+    /// Term de la ultima eleccion conocida. Crece con cada eleccion, y los anuncios de
+    /// coordinador y los OK_ACQUIRE de terms anteriores se ignoran.
+    term: u64,
+    /// Ultima vez que llego un heartbeat del coordinador (o que se lo eligio).
+    last_leader_heartbeat: Instant,
     synchronization_done: Arc<(Mutex<bool>, Condvar)>,
+    joined: Arc<(Mutex<bool>, Condvar)>,
+    /// Miembro al que se le pidio entrar al cluster, mientras se espera su MEMBERS.
+    join_contact: Option<SocketAddr>,
+    chain_sync: ChainSync,
+    pending_records: PendingRecords,
+    peer_heads: HashMap<SocketAddr, ChainHead>,
+    reconciliation: Reconciliation,
+    rng: Xorshift,
+    auto_repair: bool,
+    leaving: bool,
+    timeouts: Timeouts,
+    retry_policy: RetryPolicy,
+    fault_injector: Arc<Mutex<FaultInjector>>,
+    capture: Option<Arc<MessageCapture>>,
+    failure_detector: FailureDetector,
+    keyring: Arc<Keyring>,
+    replay_guard: ReplayGuard,
+    unauthenticated_messages: u64,
+    replayed_messages: u64,
+    unauthorized_grades: u64,
+    rejected_records: HashMap<u64, u64>,
     pub dist_mutex: DistMutex,
     pub coordinator_state: CoordinatorState,
 }
 
 impl BlockchainNode {
+    pub(crate) fn new(config: NodeConfig) -> BlockchainNode {
+        let transport = match UdpTransport::bind(config.listen_address) {
+            Ok(transport) => transport,
+            Err(_error) => {
+                panic!("Couldn't start to listen on listen port. Port in use?");
+            }
+        };
+        BlockchainNode::with_transport(config, Arc::new(transport), Arc::new(SystemClock))
+    }
+
+    /// Crea un nodo que se comunica por `transport` y mide el tiempo con `clock`.
+    /// Los tests lo usan para correr varios nodos sobre una red en memoria con un reloj virtual.
     #[allow(clippy::mutex_atomic)]
-    pub(crate) fn new(port: usize, neighbor_addresses: Vec<String>) -> BlockchainNode {
-        let self_addr = ip_parser::local_address_with_port(&port.to_string());
-        let cloned_self_addr = self_addr.clone();
+    pub(crate) fn with_transport(
+        config: NodeConfig,
+        transport: Arc<dyn Transport>,
+        clock: Arc<dyn Clock>,
+    ) -> BlockchainNode {
+        let identity = NodeIdentity::new(config.node_id, config.priority);
+        let self_addr = config.advertise_address;
         log(format!(
             "Node address for neighbor messages: {:?}",
             self_addr
         ));
-        let socket = match UdpSocket::bind(self_addr) {
-            Ok(socket) => socket,
-            Err(_error) => {
-                panic!("Couldn't start to listen on listen port. Port in use?");
+
+        log(format!("Election algorithm: {}", config.election.name()));
+        if config.keyring.is_enabled() {
+            log("Message authentication enabled".to_string());
+        }
+        if config.keyring.encrypts() {
+            log("Message encryption enabled".to_string());
+        }
+        let keyring = Arc::new(config.keyring);
+        let fault_injector = Arc::new(Mutex::new(FaultInjector::new(Xorshift::from_time(
+            !identity.id,
+        ))));
+        let capture = config.capture_path.as_ref().map(|path| {
+            log(format!("Capturing messages to {:?}", path));
+            match MessageCapture::create(path) {
+                Ok(capture) => Arc::new(capture),
+                Err(error) => panic!("{}", error),
             }
+        });
+        let messenger = Messenger::new(
+            transport.clone(),
+            clock.clone(),
+            identity,
+            keyring.clone(),
+            fault_injector.clone(),
+            capture.clone(),
+        );
+        let dist_mutex = DistMutex::new(identity.id, self_addr, messenger.try_clone());
+        let coordinator_state = CoordinatorState::new();
+        let chain_store =
+            config
+                .data_dir
+                .as_ref()
+                .map(|dir| match ChainStore::open(dir, identity.id) {
+                    Ok(chain_store) => chain_store,
+                    Err(error) => panic!("{}", error),
+                });
+        let recovered = match &chain_store {
+            Some(chain_store) => match chain_store.load() {
+                Ok(recovered) => recovered,
+                Err(error) => panic!("{}", error),
+            },
+            None => None,
         };
-
-        // try_clone() devuelve referencia independiente apuntando al mismo socket
-        let cloned_socket = match socket.try_clone() {
-            Ok(socket) => socket,
-            Err(error) => {
-                panic!(
-                    "Error trying to clone socket when creating BlockchainNode. Error: {:?}",
-                    error.to_string()
-                )
+        let recovered_chain = recovered.is_some();
+        let blockchain = match recovered {
+            Some(blockchain) => {
+                log(format!(
+                    "Recovered {} records from the data directory",
+                    blockchain.height()
+                ));
+                blockchain
             }
+            None => Blockchain::new(),
         };
+        let address_book =
+            config
+                .data_dir
+                .as_ref()
+                .map(|dir| match AddressBook::open(dir, identity.id) {
+                    Ok(address_book) => address_book,
+                    Err(error) => panic!("{}", error),
+                });
+        let mut neighbor_addresses = config.neighbor_addresses;
+        if let Some(address_book) = &address_book {
+            let known_peers = match address_book.load() {
+                Ok(known_peers) => known_peers,
+                Err(error) => panic!("{}", error),
+            };
+            log(format!(
+                "Loaded {} known peers from the address book",
+                known_peers.len()
+            ));
+            for peer in known_peers {
+                if peer != self_addr && !neighbor_addresses.contains(&peer) {
+                    neighbor_addresses.push(peer);
+                }
+            }
+        }
 
-        let dist_mutex = DistMutex::new(cloned_self_addr, cloned_socket.try_clone().unwrap());
-        let coordinator_state = CoordinatorState::new();
-
-        BlockchainNode {
-            port,
-            socket,
-            leader_port: Arc::new(Mutex::new(Some(port))),
+        let mut node = BlockchainNode {
+            identity,
+            address: self_addr,
+            listen_address: config.listen_address,
+            transport,
+            clock: clock.clone(),
+            messenger,
+            leader_id: Arc::new(Mutex::new(Some(identity.id))),
             neighbor_addresses,
-            blockchain: Blockchain::new(),
-            got_ok: Arc::new((Mutex::new(false), Condvar::new())),
+            departed: HashSet::new(),
+            address_book,
+            discovery: config.discovery,
+            peer_directory: PeerDirectory::default(),
+            blockchain,
+            chain_store,
+            recovered_chain,
+            election: config.election.algorithm(),
             is_in_election: Arc::new((Mutex::new(false), Condvar::new())),
+            term: 0,
+            last_leader_heartbeat: clock.now(),
             synchronization_done: Arc::new((Mutex::new(false), Condvar::new())),
+            joined: Arc::new((Mutex::new(false), Condvar::new())),
+            join_contact: None,
+            chain_sync: ChainSync::default(),
+            pending_records: PendingRecords::default(),
+            peer_heads: HashMap::new(),
+            reconciliation: Reconciliation::new(clock.now()),
+            rng: Xorshift::from_time(identity.id),
+            auto_repair: config.auto_repair,
+            leaving: false,
+            timeouts: config.timeouts,
+            retry_policy: config.retry_policy,
+            fault_injector,
+            capture,
+            failure_detector: FailureDetector::new(
+                config.timeouts.suspect_after,
+                config.timeouts.dead_after,
+                clock.now(),
+            ),
+            keyring,
+            replay_guard: ReplayGuard::default(),
+            unauthenticated_messages: 0,
+            replayed_messages: 0,
+            unauthorized_grades: 0,
+            rejected_records: HashMap::new(),
             dist_mutex,
             coordinator_state,
+        };
+        // Se guarda la cadena desde el inicio para que, si el nodo se reinicia, sepa que vuelve
+        // a un cluster en el que ya estaba aunque no haya llegado a recibir registros.
+        node.persist_chain();
+        node.save_address_book();
+        node
+    }
+
+    /// Autentica un mensaje recibido: que tenga la identidad del emisor, que su MAC sea valido
+    /// y que no sea la repeticion de uno ya aceptado.
+    fn open_message(
+        arc_mutex_self: &Arc<Mutex<BlockchainNode>>,
+        raw_message: &str,
+        sender: &SocketAddr,
+    ) -> Option<Envelope> {
+        let mut _self = arc_mutex_self.lock().unwrap();
+        let envelope =
+            Envelope::from_string(raw_message).and_then(|envelope| _self.keyring.open(envelope));
+        let envelope = match envelope {
+            Some(envelope) => envelope,
+            None => {
+                _self.unauthenticated_messages += 1;
+                log(format!(
+                    "Dropping unauthenticated message #{} from {:?}: {}",
+                    _self.unauthenticated_messages, sender, raw_message
+                ));
+                return None;
+            }
+        };
+        if let Some(nonce) = envelope.nonce {
+            if !_self
+                .replay_guard
+                .check_and_record(envelope.sender.id, nonce)
+            {
+                _self.replayed_messages += 1;
+                log(format!(
+                    "Dropping replayed message #{} from node {}",
+                    _self.replayed_messages, envelope.sender
+                ));
+                return None;
+            }
+        }
+        Some(envelope)
+    }
+
+    /// Admite un mensaje ya autenticado: registra al emisor y, para un GRADE_TO_COORDINATOR,
+    /// verifica que el emisor tenga el lock. Lo usan tanto `listen` como la reproduccion de
+    /// capturas, para que un mensaje rechazado en vivo tambien se rechace al reproducirlo.
+    fn admit_envelope(&mut self, envelope: &Envelope, sender: &SocketAddr) -> bool {
+        let now = self.clock.now();
+        self.failure_detector.record_heard(sender, now);
+        self.peer_directory.record(envelope.sender, *sender);
+
+        if let Some(AddGradeMessage::ToCoordinator(_, _)) =
+            AddGradeMessage::from_string(envelope.payload.clone())
+        {
+            if !self.dist_mutex.is_coordinator(self.identity.id)
+                || self.coordinator_state.lock_owner() != Some(envelope.sender.id)
+            {
+                self.unauthorized_grades += 1;
+                log(format!(
+                    "Dropping GRADE_TO_COORDINATOR from node {} that doesn't hold the lock",
+                    envelope.sender
+                ));
+                return false;
+            }
         }
+        true
+    }
+
+    /// Registra un mensaje autenticado en la captura, lo admite y, si corresponde, se lo pasa
+    /// al handler en un thread propio. La admision corre en el thread que llama, por lo que en
+    /// `listen` respeta el orden de llegada: el GRADE_TO_COORDINATOR se valida antes de
+    /// procesar el RELEASE que le sigue.
+    fn deliver(arc_mutex_self: Arc<Mutex<BlockchainNode>>, envelope: Envelope, sender: SocketAddr) {
+        let clock = {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            if let Some(capture) = &_self.capture {
+                capture.record(
+                    FaultDirection::Receive,
+                    &sender,
+                    envelope.sender,
+                    &envelope.payload,
+                );
+            }
+            if !_self.admit_envelope(&envelope, &sender) {
+                return;
+            }
+            _self.clock()
+        };
+        clock.spawn(Box::new(move || {
+            BlockchainNode::handle_incoming_message(arc_mutex_self, envelope, &sender)
+        }));
     }
 
     pub fn handle_incoming_message(
         arc_mutex_self: Arc<Mutex<BlockchainNode>>,
-        message: &str,
-        sender: &str,
+        envelope: Envelope,
+        sender: &SocketAddr,
     ) {
+        let sender_identity = envelope.sender;
+        let message = envelope.payload.as_str();
         let cloned_arc_mutex_self = arc_mutex_self.clone();
-        if let Some(election_message) = ElectionMessage::from_bytes(message.as_bytes()) {
-            return BlockchainNode::process_election_message(
-                cloned_arc_mutex_self,
-                election_message,
+        if let Some(heartbeat_message) = HeartbeatMessage::from_string(String::from(message)) {
+            return BlockchainNode::process_heartbeat_message(
+                arc_mutex_self,
+                heartbeat_message,
+                sender_identity,
                 sender,
             );
         }
+        if let Some(ElectionMessage::Coordinator(term)) =
+            ElectionMessage::from_bytes(message.as_bytes())
+        {
+            BlockchainNode::receive_coordinator(arc_mutex_self, sender_identity, *sender, term);
+            return;
+        }
+        let election = arc_mutex_self.lock().unwrap().election.clone();
+        if election.process_message(cloned_arc_mutex_self, message, sender, sender_identity) {
+            return;
+        }
         if let Some(acquire_message) = AcquireMessage::from_bytes(message.as_bytes()) {
-            BlockchainNode::process_dist_mutex_message(arc_mutex_self, acquire_message, sender);
+            BlockchainNode::process_dist_mutex_message(
+                arc_mutex_self,
+                acquire_message,
+                sender_identity.id,
+            );
             return;
         }
         if let Some(add_grade_message) = AddGradeMessage::from_string(String::from(message)) {
-            return BlockchainNode::process_add_grade_message(arc_mutex_self, add_grade_message);
+            return BlockchainNode::process_add_grade_message(
+                arc_mutex_self,
+                add_grade_message,
+                sender_identity,
+                sender,
+            );
+        }
+        if let Some(membership_message) = MembershipMessage::from_string(message) {
+            return BlockchainNode::process_membership_message(
+                arc_mutex_self,
+                membership_message,
+                sender_identity,
+                sender,
+            );
         }
         if let Some(blockchain_message) = BlockchainMessage::from_string(String::from(message)) {
             return BlockchainNode::process_blockchain_message(
@@ -100,42 +401,83 @@ impl BlockchainNode {
                 sender,
             );
         }
-        panic!("Unknown message: {}", message)
+        log(format!(
+            "Unknown message from {} at {:?}: {}",
+            sender_identity, sender, message
+        ))
+    }
+
+    fn process_heartbeat_message(
+        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
+        heartbeat_message: HeartbeatMessage,
+        sender_identity: NodeIdentity,
+        sender: &SocketAddr,
+    ) {
+        let mut _self = arc_mutex_self.lock().unwrap();
+        match heartbeat_message {
+            HeartbeatMessage::Ping(seq) => {
+                _self
+                    .messenger
+                    .send(HeartbeatMessage::Pong(seq).as_string().as_bytes(), sender);
+            }
+            HeartbeatMessage::Pong(seq) => {
+                let now = _self.clock.now();
+                _self.failure_detector.record_pong(sender, seq, now);
+            }
+            HeartbeatMessage::Leader(term) => {
+                // Solo cuentan los heartbeats del coordinador actual en un term vigente, o los de
+                // otro nodo con un term posterior: lo eligieron mientras este nodo no se enteraba
+                // (por ejemplo, por una particion), y si este era el coordinador deja de serlo.
+                if sender_identity.id == _self.dist_mutex.coordinator_id && term >= _self.term {
+                    _self.observe_term(term);
+                    _self.last_leader_heartbeat = _self.clock.now();
+                } else if term > _self.term {
+                    log(format!(
+                        "Node {} leads term {}, newer than the current one ({}). Following it",
+                        sender_identity, term, _self.term
+                    ));
+                    _self.set_coordinator(sender_identity, *sender, term);
+                }
+            }
+        }
     }
 
     fn process_add_grade_message(
         arc_mutex_self: Arc<Mutex<BlockchainNode>>,
         add_grade_message: AddGradeMessage,
+        sender_identity: NodeIdentity,
+        sender: &SocketAddr,
     ) {
         match add_grade_message {
-            AddGradeMessage::FromCoordinator(blockchain_record) => {
+            AddGradeMessage::FromCoordinator(index, previous_hash, blockchain_record) => {
                 log(format!(
-                    "Received add grade message from coordinator: {} {} {}",
-                    blockchain_record.student_name, blockchain_record.grade, blockchain_record.hash
+                    "Received add grade message from coordinator: {} {} {} at {}",
+                    blockchain_record.student_name,
+                    blockchain_record.grade,
+                    blockchain_record.hash,
+                    index
                 ));
-                arc_mutex_self
-                    .lock()
-                    .unwrap()
-                    .blockchain
-                    .add_record(blockchain_record.clone());
-                log(format!(
-                    "Processed add grade message from coordinator: {} {} {}",
-                    blockchain_record.student_name, blockchain_record.grade, blockchain_record.hash
-                ))
+                arc_mutex_self.lock().unwrap().append_from_coordinator(
+                    index,
+                    previous_hash,
+                    blockchain_record,
+                    sender_identity,
+                    sender,
+                );
             }
             AddGradeMessage::ToCoordinator(student_name, grade) => {
                 let mut _self = arc_mutex_self.lock().unwrap();
+                let previous_head = _self.blockchain.head();
                 _self.blockchain.add_grade(student_name.clone(), grade);
+                _self.persist_chain();
+                let message = AddGradeMessage::FromCoordinator(
+                    previous_head.height,
+                    previous_head.hash,
+                    _self.blockchain.last_record().unwrap().clone(),
+                )
+                .as_string();
                 for neighbor_addr in _self.neighbor_addresses.iter() {
-                    send(
-                        _self.socket.try_clone().unwrap(),
-                        AddGradeMessage::FromCoordinator(
-                            _self.blockchain.last_record().unwrap().clone(),
-                        )
-                        .as_string()
-                        .as_bytes(),
-                        neighbor_addr,
-                    );
+                    _self.messenger.send(message.as_bytes(), neighbor_addr);
                 }
                 log(format!(
                     "Received add grade message to coordinator: {} {}",
@@ -145,67 +487,63 @@ impl BlockchainNode {
         }
     }
 
-    #[allow(clippy::mutex_atomic)]
-    fn process_election_message(
-        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
-        election_message: ElectionMessage,
-        sender: &str,
+    /// Agrega un registro difundido por el coordinador solo si va en la posicion siguiente a la
+    /// cabeza propia, encadena con ella y su hash es correcto. Si no, lo rechaza, anota al
+    /// coordinador y anuncia la cabeza propia para ponerse al dia con los vecinos.
+    /// Si el registro va mas adelante (se perdieron los anteriores) se lo guarda hasta recibir
+    /// los que faltan, que se piden al coordinador o a cualquier vecino que los tenga.
+    fn append_from_coordinator(
+        &mut self,
+        index: u64,
+        previous_hash: u64,
+        record: BlockchainRecord,
+        coordinator: NodeIdentity,
+        coordinator_addr: &SocketAddr,
     ) {
-        match election_message {
-            ElectionMessage::Election => {
-                let (self_port, socket) = {
-                    let _self = arc_mutex_self.lock().unwrap();
-                    (_self.port, _self.socket.try_clone().unwrap())
-                };
-                log(format!(
-                    "Quieren hacer elecciones desde {:?} y yo soy {:?}!",
-                    sender, self_port
-                ));
-                if let Some(port) = ip_parser::get_port_from_dir(sender) {
-                    if self_port > port {
-                        let message_to_send = ElectionMessage::OkElection.as_bytes();
-                        send(socket, &message_to_send, sender);
-                        thread::spawn(move || {
-                            BlockchainNode::begin_election(arc_mutex_self);
-                        });
-                    }
-                }
-            }
-            ElectionMessage::Coordinator => {
-                let mut _self = arc_mutex_self.lock().unwrap();
-                *_self.leader_port.lock().unwrap() =
-                    Some(ip_parser::get_port_from_dir(sender).unwrap());
-                *_self.is_in_election.0.lock().unwrap() = false;
-                _self.is_in_election.1.notify_all();
-                _self.dist_mutex.coordinator_addr = sender.to_string();
-                log(format!(
-                    "Mi nuevo coordinador es {:?}",
-                    *_self.leader_port.lock().unwrap()
-                ));
-            }
-            ElectionMessage::OkElection => {
-                log("Recibi OkElection. No seré el coordinador.".to_string());
-                let _self = arc_mutex_self.lock().unwrap();
-                *_self.got_ok.0.lock().unwrap() = true;
-                _self.got_ok.1.notify_all();
-            }
+        let head = self.blockchain.head();
+        if index < head.height && self.blockchain.record_at(index) == Some(&record) {
+            log(format!("Already had record at {}. Skipping..", index));
+            return;
         }
+        if index > head.height {
+            self.buffer_out_of_order_record(index, previous_hash, record, coordinator_addr);
+            return;
+        }
+        if index == head.height
+            && previous_hash == head.hash
+            && self.blockchain.append_verified(record.clone())
+        {
+            log(format!(
+                "Processed add grade message from coordinator: {} {} {}",
+                record.student_name, record.grade, record.hash
+            ));
+            self.apply_pending_records();
+            self.persist_chain();
+            return;
+        }
+        let rejected = self.rejected_records.entry(coordinator.id).or_insert(0);
+        *rejected += 1;
+        log(format!(
+            "Rejecting record {:?} at {} (previous hash {}) from coordinator {}: own head is {:?}. Rejected records from it: {}",
+            record, index, previous_hash, coordinator, head, rejected
+        ));
+        self.announce_head();
     }
 
     fn process_dist_mutex_message(
         arc_mutex_self: Arc<Mutex<BlockchainNode>>,
         message: AcquireMessage,
-        sender: &str,
+        sender_id: u64,
     ) {
         match message {
             AcquireMessage::Acquire => {
-                BlockchainNode::process_acquire_message(arc_mutex_self, sender);
+                BlockchainNode::process_acquire_message(arc_mutex_self, sender_id);
             }
-            AcquireMessage::OkAcquire => {
-                BlockchainNode::process_ok_acquire_message(arc_mutex_self);
+            AcquireMessage::OkAcquire(term) => {
+                BlockchainNode::process_ok_acquire_message(arc_mutex_self, sender_id, term);
             }
             AcquireMessage::Release => {
-                BlockchainNode::process_release_message(arc_mutex_self);
+                BlockchainNode::process_release_message(arc_mutex_self, sender_id);
             }
         }
     }
@@ -213,35 +551,407 @@ impl BlockchainNode {
     fn process_blockchain_message(
         arc_mutex_self: Arc<Mutex<BlockchainNode>>,
         blockchain_message: BlockchainMessage,
-        sender: &str,
+        sender: &SocketAddr,
     ) {
+        let mut _self = arc_mutex_self.lock().unwrap();
         match blockchain_message {
-            BlockchainMessage::AskForBlockchain => {
-                BlockchainNode::process_ask_for_blockchain_message(arc_mutex_self, sender);
+            BlockchainMessage::AnnounceHead(head) => {
+                let own_head = BlockchainMessage::ChainHead(_self.blockchain.head()).as_string();
+                _self.messenger.send(own_head.as_bytes(), sender);
+                _self.process_chain_head(head, sender);
             }
-            BlockchainMessage::BlockchainResult(blockchain) => {
-                BlockchainNode::process_blockchain_result_message(
-                    arc_mutex_self,
-                    sender,
-                    blockchain,
-                );
+            BlockchainMessage::ChainHead(head) => _self.process_chain_head(head, sender),
+            BlockchainMessage::GetRange(from, count) => {
+                let records = _self.blockchain.records_from(from, count.min(RANGE_SIZE));
+                log(format!(
+                    "Sending {} records from {} to {:?}",
+                    records.len(),
+                    from,
+                    sender
+                ));
+                let range = BlockchainMessage::Range(from, records).as_string();
+                _self.messenger.send(range.as_bytes(), sender);
+            }
+            BlockchainMessage::Range(from, records) => _self.process_range(from, records, sender),
+            BlockchainMessage::GetHashAt(height) => {
+                if let Some(hash) = _self.blockchain.hash_at(height) {
+                    let reply = BlockchainMessage::HashAt(height, hash).as_string();
+                    _self.messenger.send(reply.as_bytes(), sender);
+                }
             }
+            BlockchainMessage::HashAt(height, hash) => _self.process_hash_at(height, hash, sender),
         }
     }
 
     #[allow(clippy::mutex_atomic)]
-    fn process_acquire_message(arc_mutex_self: Arc<Mutex<BlockchainNode>>, sender: &str) {
-        log("Processing ACQUIRE message".to_string());
-        let (is_coordinator, is_taken, socket) = {
-            let _self = arc_mutex_self.lock().unwrap();
-            (
+    fn process_membership_message(
+        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
+        membership_message: MembershipMessage,
+        sender_identity: NodeIdentity,
+        sender: &SocketAddr,
+    ) {
+        let mut _self = arc_mutex_self.lock().unwrap();
+        match membership_message {
+            MembershipMessage::Join => _self.admit_member(sender_identity, sender),
+            MembershipMessage::Members(term, coordinator, coordinator_addr, members) => {
+                if _self.join_contact != Some(*sender) {
+                    log(format!(
+                        "Ignoring MEMBERS from {:?}: no request to join was sent to it",
+                        sender
+                    ));
+                    return;
+                }
+                _self.join_contact = None;
+                for member in members {
+                    _self.add_neighbor(member);
+                }
+                _self.observe_term(term);
+                _self.last_leader_heartbeat = _self.clock.now();
+                _self.peer_directory.record(coordinator, coordinator_addr);
+                *_self.leader_id.lock().unwrap() = Some(coordinator.id);
                 _self
                     .dist_mutex
-                    .is_coordinator(ip_parser::local_address_with_port(&_self.port.to_string())),
-                _self.coordinator_state.is_taken(),
-                _self.socket.try_clone(),
+                    .set_coordinator(coordinator.id, coordinator_addr);
+                log(format!(
+                    "Joined the cluster. Neighbors: {:?}. Coordinator: {} at {:?} (term {})",
+                    _self.neighbor_addresses, coordinator, coordinator_addr, _self.term
+                ));
+                *_self.joined.0.lock().unwrap() = true;
+                _self.joined.1.notify_all();
+            }
+            MembershipMessage::MemberAdded(member) => {
+                _self.add_neighbor(member);
+            }
+            MembershipMessage::Peers(peers) => _self.merge_peers(peers, sender),
+            MembershipMessage::Announce(cluster_name) => {
+                _self.process_announcement(&cluster_name, sender_identity, sender)
+            }
+            MembershipMessage::Leave => _self.remove_neighbor(sender),
+            MembershipMessage::Handoff(term, queue) => {
+                if !_self.dist_mutex.is_coordinator(sender_identity.id) || term < _self.term {
+                    log(format!(
+                        "Ignoring HANDOFF of term {} from node {} that isn't the coordinator (coordinator is {}, term {})",
+                        term, sender_identity, _self.dist_mutex.coordinator_id, _self.term
+                    ));
+                    return;
+                }
+                log(format!(
+                    "Coordinator {} hands over leadership of term {}. Nodes waiting for the lock: {:?}",
+                    sender_identity, term, queue
+                ));
+                _self.remove_neighbor(sender);
+                // El traspaso cuenta como una eleccion: el sucesor se anuncia con un term nuevo.
+                _self.observe_term(term + 1);
+                _self.make_leader();
+                for (requestor, requestor_addr) in queue {
+                    _self.peer_directory.record(requestor, requestor_addr);
+                    _self.coordinator_state.enqueue_requestor(requestor.id);
+                }
+                drop(_self);
+                BlockchainNode::serve_waiting_requestors(arc_mutex_self);
+            }
+        }
+    }
+
+    /// Suma al cluster a un nodo que pidio entrar: le responde con el coordinador actual y los
+    /// demas miembros, y les avisa a estos la direccion del nodo nuevo.
+    fn admit_member(&mut self, identity: NodeIdentity, address: &SocketAddr) {
+        log(format!(
+            "Node {} at {:?} asks to join the cluster",
+            identity, address
+        ));
+        let mut members = self
+            .neighbor_addresses
+            .iter()
+            .filter(|neighbor| *neighbor != address)
+            .copied()
+            .collect::<Vec<SocketAddr>>();
+        for member in &members {
+            let message = MembershipMessage::MemberAdded(*address).as_string();
+            self.messenger.send(message.as_bytes(), member);
+        }
+        members.push(self.address);
+        let coordinator_id = self.dist_mutex.coordinator_id;
+        let coordinator = self
+            .identity_of(coordinator_id)
+            .unwrap_or(NodeIdentity::new(coordinator_id, 0));
+        let reply = MembershipMessage::Members(
+            self.term,
+            coordinator,
+            self.dist_mutex.coordinator_addr,
+            members,
+        )
+        .as_string();
+        self.messenger.send(reply.as_bytes(), address);
+        self.add_neighbor(*address);
+    }
+
+    /// Agrega un vecino si no se lo conocia y devuelve si era nuevo. Se lo da por escuchado al
+    /// agregarlo, para que el detector de fallas le de el mismo margen que a los vecinos iniciales.
+    fn add_neighbor(&mut self, address: SocketAddr) -> bool {
+        if self.is_own_address(&address) || self.neighbor_addresses.contains(&address) {
+            return false;
+        }
+        log(format!("New neighbor at {:?}", address));
+        self.departed.remove(&address);
+        self.neighbor_addresses.push(address);
+        let now = self.clock.now();
+        self.failure_detector.record_heard(&address, now);
+        self.save_address_book();
+        true
+    }
+
+    /// Si la direccion es de este nodo: la que anuncia, en la que escucha, o una desde la que
+    /// llegaron mensajes con su ID (por ejemplo la que le dio un NAT).
+    fn is_own_address(&self, address: &SocketAddr) -> bool {
+        *address == self.address
+            || *address == self.listen_address
+            || self
+                .peer_directory
+                .identity_at(address)
+                .is_some_and(|identity| identity.id == self.identity.id)
+    }
+
+    /// Si el detector de fallas dio por caido al nodo. A una direccion de la que nunca se supo
+    /// nada no se la juzga.
+    fn is_dead(&self, address: &SocketAddr) -> bool {
+        self.failure_detector.stats(address).is_some()
+            && self.peer_status(address) == PeerStatus::Dead
+    }
+
+    /// Saca del cluster a un vecino que avisa que se va.
+    fn remove_neighbor(&mut self, address: &SocketAddr) {
+        self.departed.insert(*address);
+        if !self.neighbor_addresses.contains(address) {
+            return;
+        }
+        log(format!("Neighbor at {:?} left the cluster", address));
+        self.neighbor_addresses
+            .retain(|neighbor| neighbor != address);
+        self.peer_heads.remove(address);
+        self.save_address_book();
+    }
+
+    /// Guarda los vecinos conocidos en el directorio de datos, si el nodo tiene uno.
+    fn save_address_book(&self) {
+        if let Some(address_book) = &self.address_book {
+            if let Err(error) = address_book.save(&self.neighbor_addresses) {
+                log(error);
+            }
+        }
+    }
+
+    /// Le envia a `address` los vecinos que se conocen, salvo el mismo y los que se dieron por
+    /// caidos.
+    fn send_peers(&self, address: &SocketAddr) {
+        let peers = self
+            .neighbor_addresses
+            .iter()
+            .filter(|neighbor| *neighbor != address && !self.is_dead(neighbor))
+            .copied()
+            .collect();
+        let message = MembershipMessage::Peers(peers).as_string();
+        self.messenger.send(message.as_bytes(), address);
+    }
+
+    /// Suma los vecinos que conoce `sender` (y a el mismo), salvo los que se fueron del cluster o
+    /// se dieron por caidos. A los nodos que no se conocian se les envian los vecinos propios,
+    /// para que tambien sumen a este nodo, y a `sender` se le responde solo si se conoce a alguien
+    /// que no menciono, asi el intercambio termina.
+    fn merge_peers(&mut self, peers: Vec<SocketAddr>, sender: &SocketAddr) {
+        let candidates = peers
+            .iter()
+            .filter(|peer| !self.departed.contains(peer) && !self.is_dead(peer))
+            .chain(std::iter::once(sender))
+            .copied()
+            .collect::<Vec<SocketAddr>>();
+        let mut new_peers = Vec::new();
+        for peer in candidates {
+            if self.add_neighbor(peer) {
+                new_peers.push(peer);
+            }
+        }
+        let sender_is_missing_peers = self.neighbor_addresses.iter().any(|neighbor| {
+            neighbor != sender && !peers.contains(neighbor) && !self.is_dead(neighbor)
+        });
+        if sender_is_missing_peers && !new_peers.contains(sender) {
+            new_peers.push(*sender);
+        }
+        for peer in new_peers {
+            self.send_peers(&peer);
+        }
+    }
+
+    /// Anuncia el nodo en la red local, si se lo inicio con `--discover`.
+    fn announce(&self) {
+        if let Some(discovery) = &self.discovery {
+            log(format!(
+                "Announcing node to cluster {:?} at {} ports {}-{}",
+                discovery.cluster_name, discovery.address, discovery.ports.0, discovery.ports.1
+            ));
+            let message = MembershipMessage::Announce(discovery.cluster_name.clone()).as_string();
+            for target in discovery.targets(&self.address) {
+                self.messenger.send(message.as_bytes(), &target);
+            }
+        }
+    }
+
+    /// Suma al nodo que se anuncio si es del mismo cluster, y le responde con los vecinos propios.
+    /// Un broadcast tambien le llega al que lo envio, con una direccion que puede no ser la propia.
+    fn process_announcement(
+        &mut self,
+        cluster_name: &str,
+        sender_identity: NodeIdentity,
+        sender: &SocketAddr,
+    ) {
+        if sender_identity.id == self.identity.id {
+            return;
+        }
+        match &self.discovery {
+            Some(discovery) if discovery.cluster_name == cluster_name => {
+                self.add_neighbor(*sender);
+                self.send_peers(sender);
+            }
+            _ => log(format!(
+                "Ignoring announcement of node {} at {:?} from cluster {:?}",
+                sender_identity, sender, cluster_name
+            )),
+        }
+    }
+
+    /// Intercambia la lista de vecinos con todos los vecinos conocidos (al principio, las
+    /// semillas) y espera a que lleguen las respuestas, para conocer al resto del cluster antes
+    /// de sincronizar la cadena y elegir coordinador.
+    pub fn discover_peers(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let (clock, discovery_window) = {
+            let _self = arc_mutex_self.lock().unwrap();
+            _self.announce();
+            for neighbor in &_self.neighbor_addresses {
+                _self.send_peers(neighbor);
+            }
+            (_self.clock.clone(), _self.timeouts().peer_discovery_window)
+        };
+        clock.sleep(discovery_window);
+        log(format!(
+            "Known peers after discovery: {:?}",
+            arc_mutex_self.lock().unwrap().neighbor_addresses
+        ));
+    }
+
+    /// Mientras no conozca a nadie, el nodo se vuelve a anunciar en lugar de intercambiar vecinos.
+    fn exchange_peers_with_random_neighbor(&mut self) {
+        if self.neighbor_addresses.is_empty() {
+            self.announce();
+            return;
+        }
+        let index = self.rng.below(self.neighbor_addresses.len() as u64) as usize;
+        let neighbor = self.neighbor_addresses[index];
+        self.send_peers(&neighbor);
+    }
+
+    /// Pide entrar al cluster a traves de `contact`, reintentando si no responde.
+    /// Mientras tanto solo se acepta el MEMBERS de `contact`. Devuelve si se pudo entrar.
+    #[allow(clippy::mutex_atomic)]
+    pub fn join_cluster(arc_mutex_self: Arc<Mutex<BlockchainNode>>, contact: SocketAddr) -> bool {
+        let (joined, clock, join_timeout, join_attempts) = {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            *_self.joined.0.lock().unwrap() = false;
+            _self.join_contact = Some(contact);
+            (
+                _self.joined.clone(),
+                _self.clock.clone(),
+                _self.timeouts().join,
+                _self.retry_policy.join_attempts,
+            )
+        };
+        for attempt in 1..=join_attempts {
+            log(format!(
+                "Asking {:?} to join the cluster (attempt {})",
+                contact, attempt
+            ));
+            arc_mutex_self
+                .lock()
+                .unwrap()
+                .messenger
+                .send(MembershipMessage::Join.as_string().as_bytes(), &contact);
+            if clock.wait_until(&joined, true, join_timeout) {
+                return true;
+            }
+        }
+        log(format!(
+            "{:?} didn't answer. Couldn't join the cluster",
+            contact
+        ));
+        arc_mutex_self.lock().unwrap().join_contact = None;
+        false
+    }
+
+    /// Vuelve a entrar al cluster despues de reiniciarse, pidiendoselo en orden a los vecinos
+    /// hasta que alguno responda. Asi conoce al coordinador actual sin forzar una eleccion.
+    /// Devuelve si alguno respondio.
+    pub fn rejoin_cluster(arc_mutex_self: Arc<Mutex<BlockchainNode>>) -> bool {
+        let neighbors = arc_mutex_self.lock().unwrap().neighbor_addresses.clone();
+        neighbors
+            .into_iter()
+            .any(|neighbor| BlockchainNode::join_cluster(arc_mutex_self.clone(), neighbor))
+    }
+
+    /// Si la cadena se recupero del directorio de datos, es decir, si el nodo ya habia estado
+    /// en el cluster antes de reiniciarse.
+    pub fn recovered_chain(&self) -> bool {
+        self.recovered_chain
+    }
+
+    /// Guarda la cadena en el directorio de datos, si el nodo tiene uno.
+    fn persist_chain(&mut self) {
+        if let Some(chain_store) = &mut self.chain_store {
+            if let Err(error) = chain_store.save(&self.blockchain) {
+                log(error);
+            }
+        }
+    }
+
+    /// Identidad del nodo con el ID indicado, si se lo conoce.
+    fn identity_of(&self, id: u64) -> Option<NodeIdentity> {
+        if id == self.identity.id {
+            return Some(self.identity);
+        }
+        self.peer_directory.identity_of(id)
+    }
+
+    /// Si el nodo le ganaria una eleccion al coordinador actual.
+    pub fn outranks_coordinator(&self) -> bool {
+        match self.identity_of(self.dist_mutex.coordinator_id) {
+            Some(coordinator) => self.identity > coordinator,
+            None => true,
+        }
+    }
+
+    fn process_acquire_message(arc_mutex_self: Arc<Mutex<BlockchainNode>>, requestor_id: u64) {
+        log(format!(
+            "Processing ACQUIRE message from node {}",
+            requestor_id
+        ));
+        let (is_coordinator, is_taken, messenger, requestor_addr, clock, term) = {
+            let _self = arc_mutex_self.lock().unwrap();
+            (
+                _self.dist_mutex.is_coordinator(_self.identity.id),
+                // Un coordinador que esta saliendo del cluster no da mas el lock: los pedidos
+                // quedan en la cola que le pasa a su sucesor.
+                _self.coordinator_state.is_taken() || _self.leaving,
+                _self.messenger.try_clone(),
+                _self.address_of(requestor_id),
+                _self.clock.clone(),
+                _self.term,
             )
         };
+        let requestor_addr = match requestor_addr {
+            Some(requestor_addr) => requestor_addr,
+            None => {
+                log(format!("Unknown address for node {}", requestor_id));
+                return;
+            }
+        };
 
         if is_coordinator {
             if is_taken {
@@ -249,39 +959,49 @@ impl BlockchainNode {
                     .lock()
                     .unwrap()
                     .coordinator_state
-                    .enqueue_requestor(sender.to_string());
+                    .enqueue_requestor(requestor_id);
             } else {
                 {
                     let _self = arc_mutex_self.lock().unwrap();
                     _self.coordinator_state.set_taken(true);
-                    _self
-                        .coordinator_state
-                        .set_lock_owner_addr(sender.to_string());
+                    _self.coordinator_state.set_lock_owner(Some(requestor_id));
                 }
-                let ok_acquire_message = AcquireMessage::OkAcquire.as_bytes();
-                send(socket.unwrap(), &ok_acquire_message, sender);
+                let ok_acquire_message = AcquireMessage::OkAcquire(term).as_bytes();
+                messenger.send(&ok_acquire_message, &requestor_addr);
                 log(String::from("Sent OK_ACQUIRE"));
 
-                const OK_RELEASE_TIMEOUT: Duration = Duration::from_secs(10);
-                let _got_release_confirmation = {
-                    arc_mutex_self
-                        .lock()
-                        .unwrap()
-                        .coordinator_state
-                        .got_release_confirmation
-                        .clone()
+                let (_got_release_confirmation, timeouts) = {
+                    let _self = arc_mutex_self.lock().unwrap();
+                    (
+                        _self.coordinator_state.got_release_confirmation.clone(),
+                        _self.timeouts(),
+                    )
                 };
-                let got_release_confirmation = _got_release_confirmation.1.wait_timeout_while(
-                    _got_release_confirmation.0.lock().unwrap(),
-                    OK_RELEASE_TIMEOUT,
-                    |dont_got_it| !*dont_got_it,
-                );
+                // Si el detector de fallas da por caido al duenio del lock no tiene sentido
+                // seguir esperando su RELEASE.
+                let deadline = clock.now() + timeouts.ok_release;
+                let mut got_release_confirmation = false;
+                while clock.now() < deadline {
+                    got_release_confirmation = clock.wait_until(
+                        &_got_release_confirmation,
+                        true,
+                        timeouts
+                            .heartbeat_interval
+                            .min(deadline.saturating_duration_since(clock.now())),
+                    );
+                    if got_release_confirmation
+                        || arc_mutex_self.lock().unwrap().peer_status(&requestor_addr)
+                            == PeerStatus::Dead
+                    {
+                        break;
+                    }
+                }
                 {
                     let _self = arc_mutex_self.lock().unwrap();
-                    _self.coordinator_state.set_lock_owner_addr(String::new());
+                    _self.coordinator_state.set_lock_owner(None);
                     _self.coordinator_state.set_taken(false);
                 }
-                if !*got_release_confirmation.unwrap().0 {
+                if !got_release_confirmation {
                     log("Timeout waiting for RELEASE message".to_string());
                     let requestor = {
                         arc_mutex_self
@@ -293,10 +1013,7 @@ impl BlockchainNode {
                     match requestor {
                         None => {}
                         Some(requestor) => {
-                            BlockchainNode::process_acquire_message(
-                                arc_mutex_self,
-                                requestor.as_str(),
-                            );
+                            BlockchainNode::process_acquire_message(arc_mutex_self, requestor);
                         }
                     }
                 } else {
@@ -316,23 +1033,53 @@ impl BlockchainNode {
         }
     }
 
-    #[allow(clippy::mutex_atomic)]
-    fn process_ok_acquire_message(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
-        let _self = arc_mutex_self.lock().unwrap();
-        *_self.dist_mutex.got_acquire_confirmation.0.lock().unwrap() = true;
-        _self.dist_mutex.got_acquire_confirmation.1.notify_all();
+    /// Solo se acepta el OK_ACQUIRE del coordinador al que se le envio el ACQUIRE (o del que lo
+    /// reemplazo con un COORDINATOR o HANDOFF ya procesado). Un OK_ACQUIRE nunca cambia el
+    /// coordinador: eso solo lo hacen COORDINATOR, HANDOFF y MEMBERS. Si el de otro nodo trae un
+    /// term posterior puede haberse adelantado al COORDINATOR de ese nodo, y se guarda hasta ver
+    /// si llega.
+    fn process_ok_acquire_message(
+        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
+        sender_id: u64,
+        term: u64,
+    ) {
+        let mut _self = arc_mutex_self.lock().unwrap();
+        if term < _self.term {
+            log(format!(
+                "Ignoring OK_ACQUIRE from node {} for old term {} (current term is {})",
+                sender_id, term, _self.term
+            ));
+            return;
+        }
+        match _self.dist_mutex.acquire_pending {
+            Some(coordinator_id) if coordinator_id == sender_id => {
+                _self.observe_term(term);
+                _self.dist_mutex.grant();
+            }
+            Some(_) if term > _self.term => {
+                log(format!(
+                    "Keeping OK_ACQUIRE of term {} from node {} until it announces itself as coordinator",
+                    term, sender_id
+                ));
+                _self.dist_mutex.early_grant = Some(sender_id);
+            }
+            _ => log(format!(
+                "Ignoring OK_ACQUIRE from node {}: no ACQUIRE sent to it is pending",
+                sender_id
+            )),
+        }
     }
 
     #[allow(clippy::mutex_atomic)]
-    fn process_release_message(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
-        log("Processing RELEASE message".to_string());
+    fn process_release_message(arc_mutex_self: Arc<Mutex<BlockchainNode>>, sender_id: u64) {
+        log(format!(
+            "Processing RELEASE message from node {}",
+            sender_id
+        ));
 
         match arc_mutex_self.lock() {
             Ok(_self) => {
-                if !_self
-                    .dist_mutex
-                    .is_coordinator(ip_parser::local_address_with_port(&_self.port.to_string()))
-                {
+                if !_self.dist_mutex.is_coordinator(_self.identity.id) {
                     return;
                 }
 
@@ -340,8 +1087,16 @@ impl BlockchainNode {
                     return;
                 }
 
+                if _self.coordinator_state.lock_owner() != Some(sender_id) {
+                    log(format!(
+                        "Ignoring RELEASE from node {} that doesn't own the lock",
+                        sender_id
+                    ));
+                    return;
+                }
+
                 _self.coordinator_state.set_taken(false);
-                _self.coordinator_state.set_lock_owner_addr(String::new());
+                _self.coordinator_state.set_lock_owner(None);
                 {
                     *_self
                         .coordinator_state
@@ -361,86 +1116,417 @@ impl BlockchainNode {
             }
         }
 
+        BlockchainNode::serve_waiting_requestors(arc_mutex_self);
+    }
+
+    /// Le da el lock, en orden, a los nodos que lo estan esperando.
+    fn serve_waiting_requestors(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
         loop {
             let enqueded_requestor = {
                 let mut _self = arc_mutex_self.lock().unwrap();
-                if _self.coordinator_state.waiting_nodes_queue.is_empty() {
+                if _self.coordinator_state.waiting_nodes_queue.is_empty() || _self.leaving {
                     break;
                 }
                 _self.coordinator_state.deque_requestor()
             };
             log(format!(
-                "Dequeued pending requestor with addr: {:?}",
+                "Dequeued pending requestor with id: {:?}",
                 enqueded_requestor
             ));
             BlockchainNode::process_acquire_message(
                 arc_mutex_self.clone(),
-                enqueded_requestor.unwrap().as_str(),
+                enqueded_requestor.unwrap(),
             );
         }
     }
 
-    fn process_ask_for_blockchain_message(
-        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
-        sender: &str,
+    fn buffer_out_of_order_record(
+        &mut self,
+        index: u64,
+        previous_hash: u64,
+        record: BlockchainRecord,
+        coordinator_addr: &SocketAddr,
     ) {
-        let _self = arc_mutex_self.lock().unwrap();
-        let blockchain_result_message =
-            BlockchainMessage::BlockchainResult(_self.blockchain.clone()).as_string();
+        let height = self.blockchain.height();
+        if !self.pending_records.insert(index, previous_hash, record) {
+            log(format!(
+                "Too many out of order records. Dropping record at {}",
+                index
+            ));
+            return;
+        }
         log(format!(
-            "Sending BlockchainResult {:?} to : {:?}",
-            _self.blockchain, sender
+            "Gap detected: got record at {} but own height is {}. Requesting missing records",
+            index, height
         ));
-        send(
-            _self.socket.try_clone().unwrap(),
-            blockchain_result_message.as_bytes(),
-            sender,
-        );
+        let missing_head = ChainHead {
+            height: index,
+            hash: previous_hash,
+        };
+        if self
+            .chain_sync
+            .observe(*coordinator_addr, missing_head, height)
+        {
+            self.request_next_range();
+            self.announce_head();
+        }
     }
 
-    #[allow(clippy::mutex_atomic)]
-    fn process_blockchain_result_message(
-        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
-        sender: &str,
-        blockchain: Blockchain,
-    ) {
-        let mut _self = arc_mutex_self.lock().unwrap();
-        if *_self.synchronization_done.0.lock().unwrap() {
-            log("I was already synchronized. Skipping..".to_string());
+    /// Agrega los registros guardados fuera de orden que ya encadenan con la cabeza propia.
+    fn apply_pending_records(&mut self) {
+        while let Some((previous_hash, record)) =
+            self.pending_records.take_next(self.blockchain.height())
+        {
+            if previous_hash != self.blockchain.head().hash
+                || !self.blockchain.append_verified(record.clone())
+            {
+                log(format!(
+                    "Discarding out of order record {:?}: it doesn't chain with own head",
+                    record
+                ));
+                return;
+            }
+            log(format!(
+                "Applied out of order record: {} {} {}",
+                record.student_name, record.grade, record.hash
+            ));
+        }
+    }
+
+    /// Envia la cabeza de la cadena propia a todos los vecinos, que responden con la suya.
+    fn announce_head(&self) {
+        let message_to_send = BlockchainMessage::AnnounceHead(self.blockchain.head()).as_string();
+        for neighbor in &self.neighbor_addresses {
+            log(format!(
+                "\t\tEnviando mensaje AnnounceHead a {:?}",
+                neighbor
+            ));
+            self.messenger.send(message_to_send.as_bytes(), neighbor);
+        }
+    }
+
+    /// Cierra la ventana de recoleccion de cabezas y empieza a sincronizar desde la mejor candidata.
+    fn choose_chain(&mut self) {
+        let groups = self.chain_sync.finish_collecting(self.blockchain.height());
+        for (head, addresses) in &groups {
+            log(format!("Chain head {:?} held by {:?}", head, addresses));
+        }
+        if groups.len() > 1 {
+            log(format!(
+                "Peers disagree on the chain: {} different heads received",
+                groups.len()
+            ));
+        }
+        if self.chain_sync.next_candidate(self.blockchain.height()) {
+            log(format!(
+                "Synchronizing chain from {:?}",
+                self.chain_sync.source()
+            ));
+            self.request_next_range();
+        }
+    }
+
+    /// Descarta lo recibido de la fuente actual y sigue con la siguiente candidata, si hay.
+    fn fall_back_to_next_candidate(&mut self) {
+        self.blockchain.truncate(self.chain_sync.start_height());
+        self.persist_chain();
+        if self.chain_sync.next_candidate(self.blockchain.height()) {
+            log(format!(
+                "Falling back to synchronize from {:?}",
+                self.chain_sync.source()
+            ));
+            self.request_next_range();
+        } else {
+            log("No more candidates to synchronize from".to_string());
+            self.chain_sync.finish();
+        }
+    }
+
+    fn process_chain_head(&mut self, head: ChainHead, sender: &SocketAddr) {
+        log(format!("Node {:?} has chain head {:?}", sender, head));
+        self.peer_heads.insert(*sender, head);
+        self.check_divergence(head, sender);
+        if self
+            .chain_sync
+            .observe(*sender, head, self.blockchain.height())
+        {
+            log(format!(
+                "Synchronizing chain from {:?} up to height {}",
+                sender, head.height
+            ));
+            self.request_next_range();
+        }
+    }
+
+    /// Compara la cabeza de un vecino con la cadena propia a la altura mas alta que tienen ambas.
+    /// Si el vecino esta mas adelante hay que pedirle su hash a la altura propia para compararlos.
+    fn check_divergence(&mut self, head: ChainHead, sender: &SocketAddr) {
+        let own_height = self.blockchain.height();
+        if !self.reconciliation.is_idle() || own_height == 0 || head.height == 0 {
             return;
         }
+        if head.height <= own_height {
+            if self.blockchain.hash_at(head.height) != Some(head.hash) {
+                self.begin_reconciliation(*sender, head.height);
+            }
+        } else {
+            let message = BlockchainMessage::GetHashAt(own_height).as_string();
+            self.messenger.send(message.as_bytes(), sender);
+        }
+    }
+
+    fn process_hash_at(&mut self, height: u64, hash: u64, sender: &SocketAddr) {
+        let now = self.clock.now();
+        let agrees = self.blockchain.hash_at(height) == Some(hash);
+        if self.reconciliation.is_voting() {
+            self.reconciliation.record_vote(*sender, height, hash, now);
+            if self.reconciliation.vote_count() >= self.neighbor_addresses.len() {
+                self.resolve_divergence();
+            }
+        } else if !self.reconciliation.is_idle() {
+            if self
+                .reconciliation
+                .record_probe(*sender, height, agrees, now)
+            {
+                self.continue_reconciliation();
+            }
+        } else if !agrees && height > 0 && height <= self.blockchain.height() {
+            self.begin_reconciliation(*sender, height);
+        }
+    }
+
+    fn begin_reconciliation(&mut self, peer: SocketAddr, divergent_height: u64) {
         log(format!(
-            "Processing BlockchainResult message from : {:?} content: {:?}",
-            sender, blockchain
+            "Chain diverges from {:?} at or before height {}. Looking for the divergence point",
+            peer, divergent_height
         ));
-        _self.blockchain = blockchain;
-        log(format!("Current blockchain is: {:?}", _self.blockchain));
-        *_self.synchronization_done.0.lock().unwrap() = true;
-        _self.synchronization_done.1.notify_all();
-        log("Notifying synchronization_done condvar".to_string());
+        self.reconciliation
+            .start(peer, divergent_height, self.clock.now());
+        self.continue_reconciliation();
+    }
+
+    /// Consulta al vecino el hash a la siguiente altura de la busqueda binaria. Al encontrar el
+    /// punto de divergencia le pregunta a todos los vecinos su version para decidir por mayoria.
+    fn continue_reconciliation(&mut self) {
+        let peer = match self.reconciliation.peer() {
+            Some(peer) => peer,
+            None => return,
+        };
+        match self.reconciliation.next_probe() {
+            Some(height) => {
+                let message = BlockchainMessage::GetHashAt(height).as_string();
+                self.messenger.send(message.as_bytes(), &peer);
+            }
+            None => {
+                let height = self.reconciliation.divergent_height();
+                log(format!(
+                    "Chains agree up to height {} and diverge at {}. Asking neighbors",
+                    self.reconciliation.common_height(),
+                    height
+                ));
+                self.reconciliation.start_voting(self.clock.now());
+                let message = BlockchainMessage::GetHashAt(height).as_string();
+                for neighbor in &self.neighbor_addresses {
+                    self.messenger.send(message.as_bytes(), neighbor);
+                }
+            }
+        }
+    }
+
+    /// Decide por mayoria que version conservar a partir del punto de divergencia. Si la propia
+    /// queda en minoria se descartan los registros desde ese punto y se piden a un vecino que
+    /// tenga la version de la mayoria.
+    fn resolve_divergence(&mut self) {
+        let common_height = self.reconciliation.common_height();
+        let divergent_height = self.reconciliation.divergent_height();
+        let own_hash = self.blockchain.hash_at(divergent_height);
+        let majority = self.reconciliation.majority(own_hash);
+        let source =
+            majority.and_then(|hash| self.reconciliation.voters_for(hash).first().copied());
+        self.reconciliation.reset();
+        match (majority, source) {
+            (Some(hash), Some(source)) if Some(hash) != own_hash => {
+                let discarded = self.blockchain.height() - common_height;
+                log(format!(
+                    "Own chain is in the minority at height {}. Discarding {} records and repairing from {:?}",
+                    divergent_height, discarded, source
+                ));
+                self.blockchain.truncate(common_height);
+                self.persist_chain();
+                self.reconciliation.repairs += 1;
+                self.reconciliation.discarded_records += discarded;
+                let target = self
+                    .peer_heads
+                    .get(&source)
+                    .copied()
+                    .filter(|head| head.height >= divergent_height)
+                    .unwrap_or(ChainHead {
+                        height: divergent_height,
+                        hash,
+                    });
+                self.chain_sync.finish();
+                if self.chain_sync.observe(source, target, common_height) {
+                    self.request_next_range();
+                }
+            }
+            _ => log(format!(
+                "Own chain is in the majority at height {}. Keeping it",
+                divergent_height
+            )),
+        }
+    }
+
+    /// Anti-entropia: cada `anti_entropy_interval` se compara la cabeza propia con la de un vecino
+    /// elegido al azar, para detectar replicas que divergieron. Tambien abandona (o resuelve con
+    /// los votos recibidos) las reconciliaciones que dejaron de avanzar.
+    /// Con `--auto-repair`, ademas, repara la cadena propia si tiene registros invalidos.
+    /// Cada `peer_exchange_interval` tambien intercambia la lista de vecinos con uno al azar.
+    pub fn start_anti_entropy(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let (clock, timeouts) = {
+            let _self = arc_mutex_self.lock().unwrap();
+            (_self.clock.clone(), _self.timeouts())
+        };
+        let mut next_exchange = clock.now() + timeouts.anti_entropy_interval;
+        let mut next_peer_exchange = clock.now() + timeouts.peer_exchange_interval;
+        loop {
+            clock.sleep(timeouts.heartbeat_interval);
+            let needs_repair = {
+                let _self = arc_mutex_self.lock().unwrap();
+                _self.auto_repair && !_self.blockchain.is_valid()
+            };
+            if needs_repair {
+                BlockchainNode::repair_chain(arc_mutex_self.clone());
+            }
+            let mut _self = arc_mutex_self.lock().unwrap();
+            let now = clock.now();
+            if _self
+                .reconciliation
+                .is_timed_out(now, timeouts.reconciliation)
+            {
+                if _self.reconciliation.is_voting() {
+                    _self.resolve_divergence();
+                } else {
+                    log("Reconciliation timed out. Abandoning it".to_string());
+                    _self.reconciliation.reset();
+                }
+            }
+            if now >= next_exchange {
+                next_exchange = now + timeouts.anti_entropy_interval;
+                _self.exchange_head_with_random_neighbor();
+            }
+            if now >= next_peer_exchange {
+                next_peer_exchange = now + timeouts.peer_exchange_interval;
+                _self.exchange_peers_with_random_neighbor();
+            }
+        }
+    }
+
+    fn exchange_head_with_random_neighbor(&mut self) {
+        if self.neighbor_addresses.is_empty() {
+            return;
+        }
+        let index = self.rng.below(self.neighbor_addresses.len() as u64) as usize;
+        let neighbor = self.neighbor_addresses[index];
+        let message = BlockchainMessage::AnnounceHead(self.blockchain.head()).as_string();
+        self.messenger.send(message.as_bytes(), &neighbor);
+        self.reconciliation.exchanges += 1;
+    }
+
+    /// Pide a la fuente de la sincronizacion el siguiente rango a partir de la altura propia.
+    fn request_next_range(&mut self) {
+        if let Some((message, source)) = self.next_range_request() {
+            self.messenger.send(message.as_bytes(), &source);
+        }
+    }
+
+    /// Arma el pedido del proximo rango a la fuente de la sincronizacion, si hay una, y lo
+    /// registra como enviado.
+    fn next_range_request(&mut self) -> Option<(String, SocketAddr)> {
+        let source = self.chain_sync.source()?;
+        let from = self.blockchain.height();
+        self.chain_sync.record_request(self.clock.now());
+        Some((
+            BlockchainMessage::GetRange(from, RANGE_SIZE).as_string(),
+            source,
+        ))
+    }
+
+    #[allow(clippy::mutex_atomic)]
+    fn process_range(&mut self, from: u64, records: Vec<BlockchainRecord>, sender: &SocketAddr) {
+        if self.chain_sync.source() != Some(*sender) || from != self.blockchain.height() {
+            log(format!(
+                "Ignoring range from {} sent by {:?}: not expected",
+                from, sender
+            ));
+            return;
+        }
+        if records.is_empty() {
+            log(format!(
+                "{:?} has no records from {}. Discarding its chain",
+                sender, from
+            ));
+            self.fall_back_to_next_candidate();
+            return;
+        }
+        for record in records {
+            if !self.blockchain.append_verified(record) {
+                log(format!(
+                    "Invalid record received from {:?} at height {}. Discarding its chain",
+                    sender,
+                    self.blockchain.height()
+                ));
+                self.fall_back_to_next_candidate();
+                return;
+            }
+        }
+        if !self.chain_sync.matches_target(self.blockchain.head()) {
+            log(format!(
+                "Chain received from {:?} doesn't match the head it announced. Discarding its chain",
+                sender
+            ));
+            self.fall_back_to_next_candidate();
+            return;
+        }
+        self.apply_pending_records();
+        self.persist_chain();
+        if self.chain_sync.is_caught_up(self.blockchain.height()) {
+            log(format!(
+                "Chain synchronized up to height {}",
+                self.blockchain.height()
+            ));
+            self.chain_sync.finish();
+            *self.synchronization_done.0.lock().unwrap() = true;
+            self.synchronization_done.1.notify_all();
+            log("Notifying synchronization_done condvar".to_string());
+        } else {
+            self.request_next_range();
+        }
     }
 
     pub fn listen(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
-        let port = { arc_mutex_self.lock().unwrap().port };
-        log(format!("Starting to listen on port: {:?}", port));
-        let socket = {
+        let address = { arc_mutex_self.lock().unwrap().address };
+        log(format!("Starting to listen on: {:?}", address));
+        let (transport, clock, fault_injector) = {
             let _self = arc_mutex_self.lock().unwrap();
-            _self.socket.try_clone().unwrap()
+            (
+                _self.transport.clone(),
+                _self.clock.clone(),
+                _self.fault_injector.clone(),
+            )
         };
 
-        let mut incoming_messages: HashMap<String, String> = HashMap::new();
+        let mut incoming_messages: HashMap<SocketAddr, String> = HashMap::new();
 
         loop {
-            let mut buf = [0; BUFFER_SIZE];
-            match socket.recv_from(&mut buf) {
-                Ok((size, from)) => {
-                    let received = Vec::from(&buf[0..size]);
+            match transport.recv_from() {
+                Ok((received, from)) => {
+                    let size = received.len();
                     let str_received = String::from_utf8(received).unwrap();
                     log(format!(
                         "Received bytes {:?} from neighbor: {:?}: {}",
                         size, from, str_received
                     ));
-                    let neighbor = from.to_string();
+                    let neighbor = ip_parser::canonical(from);
                     let clone = arc_mutex_self.clone();
 
                     let mut message = match incoming_messages.get_mut(&neighbor) {
@@ -450,216 +1536,1587 @@ impl BlockchainNode {
                         }
 
                         None => {
-                            incoming_messages.insert(neighbor.clone(), String::from(&str_received));
+                            incoming_messages.insert(neighbor, String::from(&str_received));
                             String::from(&str_received)
                         }
                     };
                     if message.ends_with('\n') {
                         message.pop();
-                        incoming_messages.insert(neighbor.clone(), String::new());
-                        thread::spawn(move || {
-                            BlockchainNode::handle_incoming_message(clone, &message, &neighbor);
-                        });
+                        incoming_messages.insert(neighbor, String::new());
+                        if fault_injector.lock().unwrap().blocks(&neighbor) {
+                            continue;
+                        }
+                        if let Some(envelope) =
+                            BlockchainNode::open_message(&clone, &message, &neighbor)
+                        {
+                            let deliveries = fault_injector.lock().unwrap().apply(
+                                FaultDirection::Receive,
+                                &neighbor,
+                                envelope.payload.clone(),
+                            );
+                            for (delay, payload) in deliveries {
+                                let clone = clone.clone();
+                                let mut envelope = envelope.clone();
+                                envelope.payload = payload;
+                                if delay.is_zero() {
+                                    BlockchainNode::deliver(clone, envelope, neighbor);
+                                    continue;
+                                }
+                                let sleeper = clock.clone();
+                                clock.spawn(Box::new(move || {
+                                    sleeper.sleep(delay);
+                                    BlockchainNode::deliver(clone, envelope, neighbor);
+                                }));
+                            }
+                        }
                     }
                 }
+                // El transporte se cerro (en los tests, al simular la caida del nodo).
+                Err(error) if error.kind() == ErrorKind::NotConnected => {
+                    log(format!("Stopped listening on {:?}: {}", address, error));
+                    return;
+                }
                 Err(error) => print!("Error while listening on port: {:?}", error),
             }
         }
     }
 
-    pub fn ping_neighbors(&self) {
-        for neighbor_addr in self.neighbor_addresses.iter() {
-            self.ping_neighbor(neighbor_addr.clone());
+    /// Procesa un mensaje leido de un archivo de captura como si acabara de llegar: pasa por la
+    /// misma admision que en `listen` (sin MAC ni nonce, porque la captura guarda los mensajes
+    /// ya abiertos) y, si se lo admite, se le pasa al handler en un thread propio.
+    pub fn replay_message(arc_mutex_self: Arc<Mutex<BlockchainNode>>, record: &CaptureRecord) {
+        let envelope = Envelope::new(record.sender, record.payload.clone());
+        BlockchainNode::deliver(arc_mutex_self, envelope, record.peer);
+    }
+
+    /// Envia periodicamente un Ping a todos los vecinos para alimentar al detector de fallas.
+    /// Tambien retoma la sincronizacion de la cadena si el ultimo rango pedido no llego.
+    /// Los mensajes se arman con el nodo tomado y se envian despues de soltarlo, para no
+    /// demorar el procesamiento de los mensajes que llegan mientras tanto.
+    pub fn start_heartbeat(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let (clock, timeouts) = {
+            let _self = arc_mutex_self.lock().unwrap();
+            (_self.clock.clone(), _self.timeouts())
+        };
+        loop {
+            clock.sleep(timeouts.heartbeat_interval);
+            let (messenger, outgoing) = {
+                let mut _self = arc_mutex_self.lock().unwrap();
+                let mut outgoing = _self.pings();
+                if _self.dist_mutex.is_coordinator(_self.identity.id) {
+                    outgoing.extend(_self.leader_heartbeats());
+                } else if let Some(silence) = _self.leader_silence() {
+                    log(format!(
+                        "No heartbeat from coordinator {} for {:?}. Starting an election",
+                        _self.dist_mutex.coordinator_id, silence
+                    ));
+                    _self.last_leader_heartbeat = clock.now();
+                    let node = arc_mutex_self.clone();
+                    clock.spawn(Box::new(move || BlockchainNode::begin_election(node)));
+                }
+                if _self
+                    .chain_sync
+                    .is_stalled(clock.now(), timeouts.range_retry)
+                {
+                    log(format!(
+                        "Range request timed out. Resuming synchronization from height {}",
+                        _self.blockchain.height()
+                    ));
+                    outgoing.extend(_self.next_range_request());
+                }
+                (_self.messenger.try_clone(), outgoing)
+            };
+            for (message, address) in outgoing {
+                messenger.send(message.as_bytes(), &address);
+            }
         }
     }
 
-    pub fn ping_neighbor(&self, dest_addr: String) {
-        log(format!(
-            "Sending ping to neighbor with addr: {:?}",
-            dest_addr
-        ));
-        send(
-            self.socket.try_clone().unwrap(),
-            "PING".as_bytes(),
-            dest_addr.as_str(),
+    /// Heartbeats con los que el coordinador les avisa a los vecinos que sigue vivo. Deja de
+    /// enviarlos al salir del cluster, ya que le pasa el rol a su sucesor.
+    fn leader_heartbeats(&self) -> Vec<(String, SocketAddr)> {
+        if self.leaving {
+            return Vec::new();
+        }
+        let message = HeartbeatMessage::Leader(self.term).as_string();
+        self.neighbor_addresses
+            .iter()
+            .map(|neighbor_addr| (message.clone(), *neighbor_addr))
+            .collect()
+    }
+
+    /// Tiempo sin heartbeats del coordinador, si supera el timeout `leader_silence` y no hay
+    /// una eleccion en curso.
+    fn leader_silence(&self) -> Option<Duration> {
+        if *self.is_in_election.0.lock().unwrap() {
+            return None;
+        }
+        let silence = self
+            .clock
+            .now()
+            .saturating_duration_since(self.last_leader_heartbeat);
+        if silence > self.timeouts.leader_silence {
+            Some(silence)
+        } else {
+            None
+        }
+    }
+
+    pub fn ping_neighbors(&mut self) {
+        for (message, neighbor_addr) in self.pings() {
+            self.messenger.send(message.as_bytes(), &neighbor_addr);
+        }
+    }
+
+    /// Arma un Ping para cada vecino y lo registra en el detector de fallas.
+    fn pings(&mut self) -> Vec<(String, SocketAddr)> {
+        let now = self.clock.now();
+        let mut pings = Vec::new();
+        for neighbor_addr in &self.neighbor_addresses {
+            let seq = self.failure_detector.register_ping(neighbor_addr, now);
+            pings.push((HeartbeatMessage::Ping(seq).as_string(), *neighbor_addr));
+        }
+        pings
+    }
+
+    /// Direccion en la que se puede contactar al nodo con el ID indicado.
+    fn address_of(&self, id: u64) -> Option<SocketAddr> {
+        if id == self.identity.id {
+            return Some(self.address);
+        }
+        self.peer_directory.address_of(id)
+    }
+
+    pub fn identity(&self) -> NodeIdentity {
+        self.identity
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn neighbor_addresses(&self) -> &[SocketAddr] {
+        &self.neighbor_addresses
+    }
+
+    /// Identidad del vecino en `address`, si ya se la conoce.
+    pub fn identity_at(&self, address: &SocketAddr) -> Option<NodeIdentity> {
+        self.peer_directory.identity_at(address)
+    }
+
+    pub fn send(&self, message: &[u8], to: &SocketAddr) {
+        self.messenger.send(message, to);
+    }
+
+    pub fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
+
+    /// Estado de un nodo segun el detector de fallas. El propio nodo siempre esta vivo.
+    pub fn peer_status(&self, addr: &SocketAddr) -> PeerStatus {
+        if *addr == self.address {
+            return PeerStatus::Alive;
+        }
+        self.failure_detector.status(addr, self.clock.now())
+    }
+
+    pub fn print_peers(&self) {
+        log("Print peers".to_string());
+        println!(
+            "term={} coordinator={} rejected_from_coordinator={}",
+            self.term,
+            self.dist_mutex.coordinator_id,
+            self.rejected_records
+                .get(&self.dist_mutex.coordinator_id)
+                .unwrap_or(&0)
+        );
+        println!(
+            "dropped unauthenticated={} replayed={} unauthorized_grades={}",
+            self.unauthenticated_messages, self.replayed_messages, self.unauthorized_grades
+        );
+        println!(
+            "chain height={} out of order records waiting={}",
+            self.blockchain.height(),
+            self.pending_records.len()
+        );
+        println!(
+            "anti-entropy exchanges={} divergences={} repairs={} discarded_records={}",
+            self.reconciliation.exchanges,
+            self.reconciliation.divergences,
+            self.reconciliation.repairs,
+            self.reconciliation.discarded_records
+        );
+        for (coordinator_id, rejected) in &self.rejected_records {
+            println!(
+                "rejected {} records from coordinator {}",
+                rejected, coordinator_id
+            );
+        }
+        let now = self.clock.now();
+        for neighbor_addr in &self.neighbor_addresses {
+            let status = self.peer_status(neighbor_addr);
+            let id = match self.peer_directory.identity_at(neighbor_addr) {
+                Some(identity) => identity.to_string(),
+                None => String::from("?"),
+            };
+            match self.failure_detector.stats(neighbor_addr) {
+                Some(stats) => println!(
+                    "{} {} {} last_heard={:?} rtt_last={:?} rtt_avg={:?} rtt_min={:?} rtt_max={:?} pings={} pongs={}",
+                    id,
+                    neighbor_addr,
+                    status,
+                    stats.last_heard.map(|heard| now.duration_since(heard)),
+                    stats.last_rtt,
+                    stats.avg_rtt(),
+                    stats.min_rtt,
+                    stats.max_rtt,
+                    stats.pings_sent,
+                    stats.pongs_received
+                ),
+                None => println!("{} {} {} never heard", id, neighbor_addr, status),
+            }
+        }
+    }
+
+    pub fn add_fault(&self, rule: FaultRule) {
+        log(format!("Adding fault rule: {}", rule));
+        self.fault_injector.lock().unwrap().add_rule(rule);
+    }
+
+    pub fn clear_faults(&self) {
+        log("Clearing fault rules".to_string());
+        self.fault_injector.lock().unwrap().clear();
+    }
+
+    pub fn print_faults(&self) {
+        let fault_injector = self.fault_injector.lock().unwrap();
+        for rule in fault_injector.rules() {
+            println!("{}", rule);
+        }
+        println!("partitioned from {:?}", fault_injector.partitioned_peers());
+        println!(
+            "dropped={} duplicated={} delayed={} reordered={} partition_drops={}",
+            fault_injector.dropped,
+            fault_injector.duplicated,
+            fault_injector.delayed,
+            fault_injector.reordered,
+            fault_injector.partition_drops
         );
     }
 
+    /// Descarta todo el trafico, en ambos sentidos, con los vecinos indicados.
+    pub fn partition(&self, peers: &[SocketAddr]) {
+        log(format!("Partitioning from {:?}", peers));
+        self.fault_injector.lock().unwrap().partition(peers);
+    }
+
+    /// Levanta la particion con el vecino indicado, o con todos si no se indica ninguno.
+    pub fn heal(&self, peer: Option<&SocketAddr>) {
+        log(format!("Healing partition with {:?}", peer));
+        self.fault_injector.lock().unwrap().heal(peer);
+    }
+
     pub fn make_coordinator(&self) {
         log("Node received make_coordinator".to_string());
-        match (*self).leader_port.lock() {
-            Ok(mut leader_port) => {
-                *leader_port = Option::from((*self).port);
+        match self.leader_id.lock() {
+            Ok(mut leader_id) => {
+                *leader_id = Option::from(self.identity.id);
             }
             Err(error) => {
                 panic!("{}", error.to_string())
             }
         }
-        log(format!("New coordinator: {:?}", self.leader_port));
+        log(format!("New coordinator: {:?}", self.leader_id));
     }
 
+    /// Agrega una nota a traves del coordinador. Si el coordinador no da el lock se inicia una
+    /// eleccion y se reintenta segun la politica de reintentos. Devuelve un error si se agotan
+    /// los intentos o se vence el plazo.
     pub fn add_grade(
         arc_mutex_self: Arc<Mutex<BlockchainNode>>,
         _name: String,
         _note: f64,
-    ) -> Result<(), ()> {
+    ) -> Result<(), String> {
         log("Node received add_grade".to_string());
-        let result = DistMutex::acquire(arc_mutex_self.clone());
-        match result {
-            Ok(()) => {
+        let (retry_policy, ok_acquire, clock) = {
+            let _self = arc_mutex_self.lock().unwrap();
+            (
+                _self.retry_policy,
+                _self.timeouts.ok_acquire,
+                _self.clock.clone(),
+            )
+        };
+        let start = clock.now();
+        // Ninguna espera de un intento se pasa del deadline de la politica de reintentos.
+        let remaining = || retry_policy.remaining(clock.now().saturating_duration_since(start));
+        let mut attempt = 1;
+        loop {
+            let coordinator_is_dead = {
+                let _self = arc_mutex_self.lock().unwrap();
+                _self.peer_status(&_self.dist_mutex.coordinator_addr) == PeerStatus::Dead
+            };
+            if coordinator_is_dead {
+                log(
+                    "El coordinador esta caido. Comenzando proceso de eleccion de lider."
+                        .to_string(),
+                );
+                BlockchainNode::begin_election_within(arc_mutex_self.clone(), remaining());
+            }
+            let acquire_timeout = ok_acquire.min(remaining());
+            if !acquire_timeout.is_zero()
+                && DistMutex::acquire(arc_mutex_self.clone(), acquire_timeout).is_ok()
+            {
                 {
                     log(String::from("antes de enviar el TO COORDINATOR"));
                     let _self = arc_mutex_self.lock().unwrap();
-                    send(
-                        _self.socket.try_clone().unwrap(),
+                    _self.messenger.send(
                         AddGradeMessage::ToCoordinator(_name, _note)
                             .as_string()
                             .as_bytes(),
-                        _self.dist_mutex.coordinator_addr.clone().as_str(),
+                        &_self.dist_mutex.coordinator_addr,
                     );
                     log(String::from("despues de enviar el TO COORDINATOR"));
                 }
-                {
-                    arc_mutex_self.lock().unwrap().dist_mutex.release();
-                    let mut _self = arc_mutex_self.lock().unwrap();
-                    if _self.port != _self.leader_port.lock().unwrap().unwrap() {
-                        *_self.coordinator_state.lock_taken.lock().unwrap() = false;
-                    }
-                    Ok(())
+                arc_mutex_self.lock().unwrap().dist_mutex.release();
+                let mut _self = arc_mutex_self.lock().unwrap();
+                if _self.identity.id != _self.leader_id.lock().unwrap().unwrap() {
+                    *_self.coordinator_state.lock_taken.lock().unwrap() = false;
                 }
+                return Ok(());
             }
-            Err(()) => {
-                log(String::from(
-                    "No hubo respuesta del Coordinador. Comenzando proceso de eleccion de lider.",
-                ));
-                BlockchainNode::begin_election(arc_mutex_self.clone());
-                BlockchainNode::add_grade(arc_mutex_self, _name, _note)
+            let backoff = {
+                let mut _self = arc_mutex_self.lock().unwrap();
+                let random = _self.rng.next_u64();
+                retry_policy.backoff(attempt, random)
+            };
+            let elapsed = clock.now().saturating_duration_since(start);
+            if !retry_policy.allows_retry(attempt, elapsed, backoff) {
+                let error = format!(
+                    "The coordinator didn't grant the lock after {} attempts in {:?}. The grade was not added",
+                    attempt, elapsed
+                );
+                log(error.clone());
+                return Err(error);
             }
+            log(format!(
+                "No hubo respuesta del Coordinador (intento {} de {}). Comenzando proceso de eleccion de lider.",
+                attempt, retry_policy.max_attempts
+            ));
+            BlockchainNode::begin_election_within(arc_mutex_self.clone(), remaining());
+            log(format!("Retrying add_grade in {:?}", backoff));
+            clock.sleep(backoff);
+            attempt += 1;
         }
-        // let result_acquire = DistMutex::acquire(arc_mutex_self.clone());
     }
 
     pub fn print(&self) {
         log("Print current blockchain".to_string());
         println!("{}", self.blockchain);
-        if !self.blockchain.is_valid() {
-            println!("Invalid blockchain!");
+        if let Some(index) = self.blockchain.first_invalid_index() {
+            println!(
+                "Invalid blockchain! First invalid record at {}. Run `repair` to fix it",
+                index
+            );
         }
     }
 
-    #[allow(clippy::mutex_atomic)]
-    /// Comienza el proceso de eleccion de lider.
-    /// Al finalizar, el nodo con número de puerto mas grande es quien queda como coordinador.
-    pub fn begin_election(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
-        match arc_mutex_self.lock() {
-            Ok(_self) => {
-                if *_self.is_in_election.0.lock().unwrap() {
-                    return;
+    /// Busca el primer registro invalido de la cadena, la trunca ahi y vuelve a pedir el resto
+    /// a los vecinos. Solo se aceptan registros que encadenen con el prefijo que quedo, asi que
+    /// los vecinos que no coinciden en ese prefijo se descartan como fuente.
+    /// Devuelve `false` si la cadena era valida.
+    pub fn repair_chain(arc_mutex_self: Arc<Mutex<BlockchainNode>>) -> bool {
+        {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            let index = match _self.blockchain.first_invalid_index() {
+                Some(index) => index,
+                None => {
+                    log("Chain is valid. Nothing to repair".to_string());
+                    return false;
                 }
+            };
+            let discarded = _self.blockchain.height() - index;
+            log(format!(
+                "First invalid record at {}. Discarding {} records and fetching them again",
+                index, discarded
+            ));
+            _self.blockchain.truncate(index);
+            _self.persist_chain();
+            _self.reconciliation.repairs += 1;
+            _self.reconciliation.discarded_records += discarded;
+        }
+        BlockchainNode::ask_for_blockchain(arc_mutex_self);
+        true
+    }
 
-                *_self.got_ok.0.lock().unwrap() = false;
-                *_self.is_in_election.0.lock().unwrap() = true;
+    /// Comienza el proceso de eleccion de lider con el algoritmo configurado.
+    /// Al finalizar, el nodo con mayor prioridad (y a igual prioridad, mayor ID) es quien queda como coordinador.
+    pub fn begin_election(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let election = arc_mutex_self.lock().unwrap().election.clone();
+        election.begin_election(arc_mutex_self);
+    }
 
-                for neighbor in &_self.neighbor_addresses {
-                    match ip_parser::get_port_from_dir(neighbor) {
-                        Some(port) => {
-                            if port < _self.port {
-                                continue;
-                            }
-                            log(format!("\t\tSending ELECTION to {:?}", neighbor));
-                            let message_to_send = ElectionMessage::Election.as_bytes();
-                            send(
-                                _self.socket.try_clone().unwrap(),
-                                &message_to_send,
-                                neighbor,
-                            );
-                        }
+    /// Inicia una eleccion y espera a que termine, como mucho `timeout`. Si no termina a tiempo
+    /// sigue en otro thread.
+    #[allow(clippy::mutex_atomic)]
+    fn begin_election_within(arc_mutex_self: Arc<Mutex<BlockchainNode>>, timeout: Duration) {
+        let clock = arc_mutex_self.lock().unwrap().clock();
+        let finished = Arc::new((Mutex::new(false), Condvar::new()));
+        let election_finished = finished.clone();
+        clock.spawn(Box::new(move || {
+            BlockchainNode::begin_election(arc_mutex_self);
+            *election_finished.0.lock().unwrap() = true;
+            election_finished.1.notify_all();
+        }));
+        if !clock.wait_until(&finished, true, timeout) {
+            log(format!(
+                "The election didn't finish in {:?}. Going on without waiting for it",
+                timeout
+            ));
+        }
+    }
 
-                        None => {
-                            panic!("There is an intruder!")
-                        }
-                    }
-                }
+    /// Marca que hay una eleccion en curso y le asigna un term nuevo, que devuelve. Devuelve
+    /// `None` si ya habia una.
+    pub fn start_election(&mut self) -> Option<u64> {
+        {
+            let mut is_in_election = self.is_in_election.0.lock().unwrap();
+            if *is_in_election {
+                return None;
             }
-            Err(error) => {
-                panic!("{}", error.to_string())
+            *is_in_election = true;
+        }
+        self.term += 1;
+        log(format!("Starting election for term {}", self.term));
+        Some(self.term)
+    }
+
+    pub fn term(&self) -> u64 {
+        self.term
+    }
+
+    /// Adopta `term` si es posterior al conocido, para que la proxima eleccion lo supere.
+    pub fn observe_term(&mut self, term: u64) {
+        if term > self.term {
+            log(format!("Term {} replaces term {}", term, self.term));
+            self.term = term;
+        }
+    }
+
+    pub fn end_election(&self) {
+        *self.is_in_election.0.lock().unwrap() = false;
+        self.is_in_election.1.notify_all();
+    }
+
+    /// Si hay una eleccion en curso, con la `Condvar` que avisa cuando termina.
+    pub fn election_in_progress(&self) -> Arc<(Mutex<bool>, Condvar)> {
+        self.is_in_election.clone()
+    }
+
+    /// Toma a `coordinator` como el nuevo coordinador y da por terminada la eleccion en curso.
+    /// Si el anuncio es de un term anterior al conocido lo ignora y devuelve `false`.
+    pub fn set_coordinator(
+        &mut self,
+        coordinator: NodeIdentity,
+        address: SocketAddr,
+        term: u64,
+    ) -> bool {
+        if term < self.term {
+            log(format!(
+                "Ignoring coordinator {} of old term {} (current term is {})",
+                coordinator, term, self.term
+            ));
+            return false;
+        }
+        self.observe_term(term);
+        self.last_leader_heartbeat = self.clock.now();
+        if coordinator.id != self.identity.id {
+            self.peer_directory.record(coordinator, address);
+        }
+        *self.leader_id.lock().unwrap() = Some(coordinator.id);
+        self.dist_mutex.set_coordinator(coordinator.id, address);
+        self.end_election();
+        log(format!(
+            "Mi nuevo coordinador es {} (term {})",
+            coordinator, term
+        ));
+        true
+    }
+
+    /// Procesa el anuncio de un coordinador y devuelve si se lo tomo. Un anuncio de un term
+    /// anterior no se toma, pero si quien lo hace le ganaria al coordinador actual puede ser un
+    /// nodo que no se entero de las ultimas elecciones: se inicia una nueva para que la gane con
+    /// un term vigente.
+    pub fn receive_coordinator(
+        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
+        coordinator: NodeIdentity,
+        address: SocketAddr,
+        term: u64,
+    ) -> bool {
+        let outranks_coordinator = {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            if _self.set_coordinator(coordinator, address, term) {
+                return true;
             }
+            _self
+                .identity_of(_self.dist_mutex.coordinator_id)
+                .is_none_or(|current| coordinator > current)
+        };
+        if outranks_coordinator {
+            log(format!(
+                "{} would beat the current coordinator. Starting a new election",
+                coordinator
+            ));
+            let clock = arc_mutex_self.lock().unwrap().clock();
+            clock.spawn(Box::new(move || {
+                BlockchainNode::begin_election(arc_mutex_self)
+            }));
         }
+        false
+    }
 
-        log("Enviando mensaje ELECTION a vecinos. Esperando sus respuestas...".to_string());
-        const OK_ELECTION_TIMEOUT: Duration = Duration::from_secs(1);
-        let _got_ok = { arc_mutex_self.lock().unwrap().got_ok.clone() };
-        let got_ok = _got_ok.1.wait_timeout_while(
-            _got_ok.0.lock().unwrap(),
-            OK_ELECTION_TIMEOUT,
-            |got_it| !*got_it,
-        );
-        if !*got_ok.unwrap().0 {
-            match arc_mutex_self.lock() {
-                Ok(mut _self) => {
-                    _self.make_leader();
-                    *_self.is_in_election.0.lock().unwrap() = false;
-                }
-                Err(error) => {
-                    panic!("{}", error.to_string())
+    /// Sale del cluster de forma ordenada. Si es el coordinador deja de dar el lock, espera a que
+    /// lo libere quien lo tiene (o a que venza el timeout) y le pasa el rol y la cola de espera a
+    /// su sucesor. Al final avisa a los vecinos que se va, para que lo saquen del cluster.
+    pub fn leave(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let (is_coordinator, clock, timeouts) = {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            _self.leaving = true;
+            (
+                _self.dist_mutex.is_coordinator(_self.identity.id),
+                _self.clock.clone(),
+                _self.timeouts(),
+            )
+        };
+        if is_coordinator {
+            let deadline = clock.now() + timeouts.lock_release;
+            let lock_is_taken = || arc_mutex_self.lock().unwrap().coordinator_state.is_taken();
+            if lock_is_taken() {
+                log("Waiting for the lock to be released before leaving".to_string());
+            }
+            while lock_is_taken() && clock.now() < deadline {
+                clock.sleep(timeouts.heartbeat_interval / 5);
+            }
+            arc_mutex_self.lock().unwrap().hand_over_leadership();
+        }
+        let _self = arc_mutex_self.lock().unwrap();
+        log("Leaving the cluster".to_string());
+        let message = MembershipMessage::Leave.as_string();
+        for neighbor in &_self.neighbor_addresses {
+            _self.messenger.send(message.as_bytes(), neighbor);
+        }
+    }
+
+    /// Le pasa el rol de coordinador y la cola de espera del lock al vecino vivo que ganaria una
+    /// eleccion entre los restantes.
+    fn hand_over_leadership(&mut self) {
+        let queue = self
+            .coordinator_state
+            .take_waiting_nodes()
+            .into_iter()
+            .filter_map(|id| match (self.identity_of(id), self.address_of(id)) {
+                (Some(identity), Some(address)) => Some((identity, address)),
+                _ => {
+                    log(format!("Unknown address for waiting node {}", id));
+                    None
                 }
+            })
+            .collect::<Vec<(NodeIdentity, SocketAddr)>>();
+        let successor = self
+            .neighbor_addresses
+            .iter()
+            .filter(|neighbor| self.peer_status(neighbor) != PeerStatus::Dead)
+            .filter_map(|neighbor| {
+                self.peer_directory
+                    .identity_at(neighbor)
+                    .map(|identity| (identity, *neighbor))
+            })
+            .max();
+        match successor {
+            Some((identity, address)) => {
+                log(format!(
+                    "Handing over leadership to {} at {:?}. Nodes waiting for the lock: {:?}",
+                    identity, address, queue
+                ));
+                let message = MembershipMessage::Handoff(self.term, queue).as_string();
+                self.messenger.send(message.as_bytes(), &address);
             }
-        } else {
-            let _is_in_election = { arc_mutex_self.lock().unwrap().is_in_election.clone() };
-            let _is_in_election_condvar = _is_in_election
-                .1
-                .wait_while(_is_in_election.0.lock().unwrap(), |is_in_election| {
-                    *is_in_election
-                });
+            None => log(format!(
+                "No alive neighbor to hand over leadership to. Nodes waiting for the lock: {:?}",
+                queue
+            )),
         }
     }
 
-    fn make_leader(&mut self) {
-        *self.leader_port.lock().unwrap() = Some(self.port);
-        self.dist_mutex.coordinator_addr =
-            ip_parser::local_address_with_port(&self.port.to_string());
+    /// Queda como coordinador y se lo anuncia a todos los vecinos.
+    pub fn make_leader(&mut self) {
+        *self.leader_id.lock().unwrap() = Some(self.identity.id);
+        self.dist_mutex
+            .set_coordinator(self.identity.id, self.address);
         log(format!(
-            "Soy el nuevo coordinador! Puerto {:?}",
-            *self.leader_port.lock().unwrap()
+            "Soy el nuevo coordinador! ID {:?} (term {})",
+            *self.leader_id.lock().unwrap(),
+            self.term
         ));
         for neighbor in &self.neighbor_addresses {
             log(format!("\t\tEnviando mensaje COORDINATOR a {:?}", neighbor));
-            let message_to_send = ElectionMessage::Coordinator.as_bytes();
-            send(self.socket.try_clone().unwrap(), &message_to_send, neighbor);
+            let message_to_send = ElectionMessage::Coordinator(self.term).as_bytes();
+            self.messenger.send(&message_to_send, neighbor);
         }
     }
 
+    /// Anuncia la cabeza de la cadena propia a los vecinos, junta sus respuestas durante una
+    /// ventana de tiempo y se sincroniza con la mejor cadena valida entre las recibidas.
+    /// Si ninguno responde con una cadena mas larga se sigue con la propia.
     #[allow(clippy::mutex_atomic)]
     pub fn ask_for_blockchain(arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
-        let (neighbor_addresses, socket, synchronization_done) = {
-            let _self = arc_mutex_self.lock().unwrap();
+        let (synchronization_done, clock, timeouts) = {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            *_self.synchronization_done.0.lock().unwrap() = false;
+            _self.chain_sync.start_collecting();
+            _self.announce_head();
             (
-                _self.neighbor_addresses.clone(),
-                _self.socket.try_clone().unwrap(),
                 _self.synchronization_done.clone(),
+                _self.clock.clone(),
+                _self.timeouts(),
             )
         };
-        for neighbor in &neighbor_addresses {
-            log(format!(
-                "\t\tEnviando mensaje AskForBlockchain a {:?}",
-                neighbor
-            ));
-            let message_to_send = BlockchainMessage::AskForBlockchain.as_string();
-            send(
-                socket.try_clone().unwrap(),
-                message_to_send.as_bytes(),
-                neighbor,
-            );
-        }
 
-        const SYNCHRONIZATION_DONE_TIMEOUT: Duration = Duration::from_secs(1);
-        let _synchronization_done = synchronization_done;
+        clock.sleep(timeouts.heads_window);
+        arc_mutex_self.lock().unwrap().choose_chain();
+
+        let deadline = clock.now() + timeouts.synchronization_done;
         log("Waiting for synchronization_done condvar".to_string());
-        let _synchronization_done_condvar = _synchronization_done.1.wait_timeout_while(
-            _synchronization_done.0.lock().unwrap(),
-            SYNCHRONIZATION_DONE_TIMEOUT,
-            |cannot_begin| !*cannot_begin,
+        let mut done = false;
+        while !done && clock.now() < deadline {
+            if arc_mutex_self.lock().unwrap().chain_sync.source().is_none() {
+                break;
+            }
+            done = clock.wait_until(&synchronization_done, true, timeouts.heartbeat_interval);
+        }
+        log(format!(
+            "Done waiting for synchronization_done condvar. Synchronized: {}",
+            done
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_file::temp_path;
+    use crate::election_kind::ElectionKind;
+    use crate::log_config::LogConfig;
+    use crate::memory_network::MemoryNetwork;
+    use crate::message_nonce::MessageNonce;
+    use crate::task_handle::TaskHandle;
+    use crate::virtual_clock::VirtualClock;
+    use std::thread;
+
+    /// Tiempo virtual que pasa entre cada ronda de entrega de mensajes.
+    const STEP: Duration = Duration::from_millis(100);
+    const MAX_STEPS: usize = 600;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn cluster(
+        ports: &[u16],
+        missing: &[u16],
+    ) -> (
+        Arc<MemoryNetwork>,
+        Arc<VirtualClock>,
+        Vec<Arc<Mutex<BlockchainNode>>>,
+    ) {
+        cluster_with(ports, missing, |_config| {})
+    }
+
+    /// Levanta un nodo por puerto sobre una misma red en memoria, todos vecinos entre si (y de
+    /// los `missing` puertos, en los que no escucha nadie).
+    /// `configure` ajusta la configuracion de cada nodo antes de levantarlo.
+    fn cluster_with(
+        ports: &[u16],
+        missing: &[u16],
+        configure: impl Fn(&mut NodeConfig),
+    ) -> (
+        Arc<MemoryNetwork>,
+        Arc<VirtualClock>,
+        Vec<Arc<Mutex<BlockchainNode>>>,
+    ) {
+        let network = MemoryNetwork::new();
+        let clock = Arc::new(VirtualClock::new());
+        let nodes = ports
+            .iter()
+            .map(|port| {
+                let neighbors = ports
+                    .iter()
+                    .chain(missing)
+                    .filter(|neighbor| *neighbor != port)
+                    .map(|neighbor| address(*neighbor))
+                    .collect();
+                let mut config = node_config(*port, neighbors);
+                configure(&mut config);
+                start_node(&network, &clock, config)
+            })
+            .collect();
+        (network, clock, nodes)
+    }
+
+    /// Configuracion de un nodo que escucha en `port`, con el puerto como ID.
+    fn node_config(port: u16, neighbor_addresses: Vec<SocketAddr>) -> NodeConfig {
+        NodeConfig {
+            node_id: port as u64,
+            derived_id: false,
+            priority: 0,
+            listen_address: address(port),
+            advertise_address: address(port),
+            neighbor_addresses,
+            keyring: Keyring::new(None, Vec::new()),
+            auto_repair: false,
+            capture_path: None,
+            replay_path: None,
+            join_address: None,
+            data_dir: None,
+            discovery: None,
+            logging: LogConfig::default(),
+            election: ElectionKind::Bully,
+            timeouts: Timeouts::default(),
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Levanta un nodo sobre la red en memoria, con el thread que escucha mensajes.
+    fn start_node(
+        network: &Arc<MemoryNetwork>,
+        clock: &Arc<VirtualClock>,
+        config: NodeConfig,
+    ) -> Arc<Mutex<BlockchainNode>> {
+        let transport = Arc::new(MemoryNetwork::bind(network, config.listen_address));
+        let node = Arc::new(Mutex::new(BlockchainNode::with_transport(
+            config,
+            transport,
+            clock.clone(),
+        )));
+        let listener = node.clone();
+        thread::spawn(move || BlockchainNode::listen(listener));
+        node
+    }
+
+    /// Entrega mensajes hasta que no quede ninguno en vuelo. Despues de cada ronda espera a que
+    /// los nodos procesen lo entregado y a que sus tareas terminen o se duerman en el reloj,
+    /// asi que lo que se envie como respuesta ya esta en vuelo al revisar.
+    fn settle(network: &MemoryNetwork, clock: &VirtualClock) {
+        loop {
+            network.deliver_all();
+            network.wait_until_processed();
+            clock.wait_until_idle();
+            if network.in_flight() == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Alterna rondas de entrega con avances del reloj virtual hasta que se cumpla `condition`.
+    fn run_until(network: &MemoryNetwork, clock: &VirtualClock, condition: impl Fn() -> bool) {
+        for _ in 0..MAX_STEPS {
+            settle(network, clock);
+            if condition() {
+                return;
+            }
+            clock.advance(STEP);
+        }
+        panic!(
+            "Condition not reached after {:?} of virtual time",
+            clock.elapsed()
         );
-        log("Done waiting for synchronization_done condvar".to_string());
+    }
+
+    fn coordinator_of(node: &Arc<Mutex<BlockchainNode>>) -> u64 {
+        node.lock().unwrap().dist_mutex.coordinator_id
+    }
+
+    fn height_of(node: &Arc<Mutex<BlockchainNode>>) -> u64 {
+        node.lock().unwrap().blockchain.height()
+    }
+
+    fn elect(
+        network: &MemoryNetwork,
+        clock: &VirtualClock,
+        nodes: &[Arc<Mutex<BlockchainNode>>],
+        from: usize,
+        expected: u64,
+    ) {
+        let candidate = nodes[from].clone();
+        let election = clock.spawn_task(move || BlockchainNode::begin_election(candidate));
+        run_until(network, clock, || {
+            election.is_finished() && nodes.iter().all(|node| coordinator_of(node) == expected)
+        });
+    }
+
+    #[test]
+    fn test_election_picks_highest_id() {
+        let (network, clock, nodes) = cluster(&[7101, 7102, 7103], &[]);
+        elect(&network, &clock, &nodes, 0, 7103);
+        assert_eq!(
+            *nodes[0].lock().unwrap().leader_id.lock().unwrap(),
+            Some(7103)
+        );
+    }
+
+    /// Levanta un cluster que elige coordinador con el algoritmo `election`, con los nodos
+    /// enviandose heartbeats (para que cada uno conozca el ID de los demas).
+    fn heartbeat_cluster(
+        ports: &[u16],
+        election: ElectionKind,
+    ) -> (
+        Arc<MemoryNetwork>,
+        Arc<VirtualClock>,
+        Vec<Arc<Mutex<BlockchainNode>>>,
+    ) {
+        let (network, clock, nodes) = cluster_with(ports, &[], |config| {
+            config.election = election;
+        });
+        for node in &nodes {
+            let node = node.clone();
+            clock.spawn_task(move || BlockchainNode::start_heartbeat(node));
+        }
+        run_until(&network, &clock, || {
+            nodes.iter().all(|node| {
+                let node = node.lock().unwrap();
+                node.neighbor_addresses()
+                    .iter()
+                    .all(|neighbor| node.identity_at(neighbor).is_some())
+            })
+        });
+        (network, clock, nodes)
+    }
+
+    #[test]
+    fn test_ring_election_picks_highest_identity() {
+        let (network, clock, nodes) =
+            heartbeat_cluster(&[8301, 8302, 8303, 8304], ElectionKind::Ring);
+        elect(&network, &clock, &nodes, 1, 8304);
+        assert_eq!(
+            *nodes[0].lock().unwrap().leader_id.lock().unwrap(),
+            Some(8304)
+        );
+    }
+
+    #[test]
+    fn test_ring_election_before_ids_are_known() {
+        // Los IDs van en orden inverso a las direcciones, y nadie conoce todavia los de los demas.
+        let (network, clock, nodes) = cluster_with(&[9061, 9062, 9063, 9064], &[], |config| {
+            config.election = ElectionKind::Ring;
+            config.node_id = 10000 - config.listen_address.port() as u64;
+        });
+        elect(&network, &clock, &nodes, 2, 939);
+        add_grade(&network, &clock, &nodes, 3, "ana");
+    }
+
+    #[test]
+    fn test_ring_election_skips_dead_successor() {
+        let (network, clock, nodes) =
+            heartbeat_cluster(&[8401, 8402, 8403, 8404], ElectionKind::Ring);
+        elect(&network, &clock, &nodes, 0, 8404);
+
+        // Se cae el coordinador, que es el sucesor de 8403 en el anillo.
+        network.unbind(&address(8404));
+        nodes[3]
+            .lock()
+            .unwrap()
+            .partition(&[address(8401), address(8402), address(8403)]);
+        run_until(&network, &clock, || {
+            nodes[2].lock().unwrap().peer_status(&address(8404)) == PeerStatus::Dead
+        });
+        elect(&network, &clock, &nodes[..3], 1, 8403);
+        add_grade(&network, &clock, &nodes[..3], 0, "ana");
+    }
+
+    #[test]
+    fn test_followers_elect_a_new_coordinator_when_its_heartbeats_stop() {
+        let (network, clock, nodes) = heartbeat_cluster(&[8701, 8702, 8703], ElectionKind::Bully);
+        elect(&network, &clock, &nodes, 0, 8703);
+        let term = term_of(&nodes[0]);
+
+        // Mientras el coordinador envia heartbeats nadie inicia elecciones.
+        let until = clock.elapsed() + Duration::from_secs(10);
+        run_until(&network, &clock, || clock.elapsed() >= until);
+        assert!(nodes.iter().all(|node| term_of(node) == term));
+
+        network.unbind(&address(8703));
+        nodes[2]
+            .lock()
+            .unwrap()
+            .partition(&[address(8701), address(8702)]);
+        let crashed_at = clock.elapsed();
+        run_until(&network, &clock, || {
+            nodes[..2].iter().all(|node| coordinator_of(node) == 8702)
+        });
+        // Antes de que un add_grade se diera cuenta por el timeout de OK_ACQUIRE.
+        assert!(clock.elapsed() - crashed_at < Timeouts::default().ok_acquire);
+        assert!(term_of(&nodes[0]) > term);
+        add_grade(&network, &clock, &nodes[..2], 0, "ana");
+    }
+
+    #[test]
+    fn test_healed_coordinator_follows_the_one_elected_in_its_absence() {
+        let (network, clock, nodes) = heartbeat_cluster(&[9081, 9082, 9083], ElectionKind::Bully);
+        elect(&network, &clock, &nodes, 0, 9083);
+        nodes[2]
+            .lock()
+            .unwrap()
+            .partition(&[address(9081), address(9082)]);
+        run_until(&network, &clock, || {
+            nodes[..2].iter().all(|node| coordinator_of(node) == 9082)
+        });
+        assert_eq!(coordinator_of(&nodes[2]), 9083);
+
+        nodes[2].lock().unwrap().heal(None);
+        run_until(&network, &clock, || coordinator_of(&nodes[2]) == 9082);
+        assert_eq!(term_of(&nodes[2]), term_of(&nodes[1]));
+        add_grade(&network, &clock, &nodes, 2, "ana");
+    }
+
+    #[test]
+    fn test_replayed_signed_message_is_dropped() {
+        let (_network, _clock, nodes) = cluster_with(&[8801, 8802], &[], |config| {
+            config.keyring = Keyring::new(Some(String::from("secret")), Vec::new());
+        });
+        let mut envelope =
+            Envelope::new(nodes[1].lock().unwrap().identity(), String::from("PING;1"));
+        Keyring::new(Some(String::from("secret")), Vec::new()).seal(
+            &mut envelope,
+            MessageNonce {
+                session: 1,
+                counter: 1,
+            },
+        );
+        let raw_message = envelope.as_string();
+
+        assert!(BlockchainNode::open_message(&nodes[0], &raw_message, &address(8802)).is_some());
+        assert!(BlockchainNode::open_message(&nodes[0], &raw_message, &address(8802)).is_none());
+        assert_eq!(nodes[0].lock().unwrap().replayed_messages, 1);
+    }
+
+    #[test]
+    fn test_invalid_records_from_coordinator_are_rejected() {
+        let (network, clock, nodes) = cluster(&[8901, 8902], &[]);
+        elect(&network, &clock, &nodes, 0, 8902);
+        let head = nodes[0].lock().unwrap().blockchain.head();
+        let mut other_chain = Blockchain::new();
+        other_chain.add_grade(String::from("ana"), 7.0);
+        let record = other_chain.last_record().unwrap().clone();
+        let bad_hash = BlockchainRecord {
+            hash: record.hash + 1,
+            ..record.clone()
+        };
+
+        for (previous_hash, record) in [(head.hash, bad_hash), (head.hash + 1, record)] {
+            let message = AddGradeMessage::FromCoordinator(0, previous_hash, record);
+            receive(&nodes[0], &nodes[1], &message.as_string());
+        }
+
+        let announcements = network
+            .in_flight_messages()
+            .into_iter()
+            .filter(|(from, _, message)| {
+                *from == address(8901) && message.contains("AnnounceHead:")
+            })
+            .count();
+        assert_eq!(announcements, 2);
+        let node = nodes[0].lock().unwrap();
+        assert_eq!(node.blockchain.height(), 0);
+        assert_eq!(node.rejected_records.get(&8902), Some(&2));
+    }
+
+    #[test]
+    fn test_add_grade_reaches_every_node() {
+        let (network, clock, nodes) = cluster(&[7201, 7202, 7203], &[]);
+        elect(&network, &clock, &nodes, 2, 7203);
+        let writer = nodes[0].clone();
+        let add_grade =
+            clock.spawn_task(move || BlockchainNode::add_grade(writer, String::from("juan"), 8.0));
+        run_until(&network, &clock, || {
+            add_grade.is_finished() && nodes.iter().all(|node| height_of(node) == 1)
+        });
+        assert_eq!(add_grade.join().unwrap(), Ok(()));
+        let record = nodes[1].lock().unwrap().blockchain.last_record().cloned();
+        assert_eq!(
+            record.map(|record| record.student_name),
+            Some(String::from("juan"))
+        );
+    }
+
+    #[test]
+    fn test_new_node_synchronizes_longest_chain() {
+        let (network, clock, nodes) = cluster(&[7301, 7302, 7303], &[]);
+        {
+            let mut source = nodes[2].lock().unwrap();
+            source.blockchain.add_grade(String::from("ana"), 9.0);
+            source.blockchain.add_grade(String::from("juan"), 8.0);
+        }
+        let joiner = nodes[0].clone();
+        let sync = clock.spawn_task(move || BlockchainNode::ask_for_blockchain(joiner));
+        run_until(&network, &clock, || sync.is_finished());
+        assert_eq!(height_of(&nodes[0]), 2);
+        assert_eq!(
+            nodes[0].lock().unwrap().blockchain.head(),
+            nodes[2].lock().unwrap().blockchain.head()
+        );
+    }
+
+    #[test]
+    fn test_silent_neighbor_is_declared_dead() {
+        let (network, clock, nodes) = cluster(&[7401, 7402], &[7403]);
+        let pinger = nodes[0].clone();
+        clock.spawn_task(move || BlockchainNode::start_heartbeat(pinger));
+        run_until(&network, &clock, || {
+            nodes[0].lock().unwrap().peer_status(&address(7403)) == PeerStatus::Dead
+        });
+        assert!(clock.elapsed() >= Timeouts::default().dead_after);
+        assert_eq!(
+            nodes[0].lock().unwrap().peer_status(&address(7402)),
+            PeerStatus::Alive
+        );
+    }
+
+    #[test]
+    fn test_replaying_a_capture_reproduces_the_chain() {
+        let path = temp_path("replay");
+        let ports = [7501, 7502, 7503];
+        let (network, clock, nodes) = cluster_with(&ports, &[], |config| {
+            if config.listen_address.port() == 7502 {
+                config.capture_path = Some(path.clone());
+            }
+        });
+        elect(&network, &clock, &nodes, 2, 7503);
+        add_grade(&network, &clock, &nodes, 1, "ana");
+
+        let records = MessageCapture::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(records
+            .iter()
+            .any(|record| record.direction == FaultDirection::Send));
+        assert!(records.iter().any(|record| {
+            record.direction == FaultDirection::Receive
+                && record.payload.starts_with("GRADE_FROM_COORDINATOR")
+        }));
+
+        let (_network, replay_clock, replay_nodes) = cluster(&[7502], &[7501, 7503]);
+        crate::replay::replay_records(&replay_nodes[0], &replay_clock, &records);
+        assert_eq!(
+            replay_nodes[0].lock().unwrap().blockchain.head(),
+            nodes[0].lock().unwrap().blockchain.head()
+        );
+        assert_eq!(coordinator_of(&replay_nodes[0]), 7503);
+    }
+
+    #[test]
+    fn test_replayed_unauthorized_grade_is_rejected() {
+        let path = temp_path("replay-unauthorized");
+        let ports = [9001, 9002, 9003];
+        let (network, clock, nodes) = cluster_with(&ports, &[], |config| {
+            if config.listen_address.port() == 9003 {
+                config.capture_path = Some(path.clone());
+            }
+        });
+        elect(&network, &clock, &nodes, 2, 9003);
+        let grade = AddGradeMessage::ToCoordinator(String::from("eve"), 10.0).as_string();
+        nodes[0]
+            .lock()
+            .unwrap()
+            .send(grade.as_bytes(), &address(9003));
+        settle(&network, &clock);
+        assert_eq!(nodes[2].lock().unwrap().unauthorized_grades, 1);
+        assert_eq!(height_of(&nodes[2]), 0);
+
+        let records = MessageCapture::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(records.iter().any(|record| {
+            record.direction == FaultDirection::Receive
+                && record.payload.starts_with("GRADE_TO_COORDINATOR")
+        }));
+
+        let (_network, replay_clock, replay_nodes) = cluster(&[9003], &[9001, 9002]);
+        crate::replay::replay_records(&replay_nodes[0], &replay_clock, &records);
+        assert_eq!(replay_nodes[0].lock().unwrap().unauthorized_grades, 1);
+        assert_eq!(height_of(&replay_nodes[0]), 0);
+    }
+
+    fn add_grade(
+        network: &MemoryNetwork,
+        clock: &VirtualClock,
+        nodes: &[Arc<Mutex<BlockchainNode>>],
+        writer: usize,
+        name: &str,
+    ) {
+        let writer = nodes[writer].clone();
+        let name = name.to_string();
+        let add_grade = clock.spawn_task(move || BlockchainNode::add_grade(writer, name, 7.0));
+        let height = height_of(&nodes[0]) + 1;
+        run_until(network, clock, || {
+            add_grade.is_finished() && nodes.iter().all(|node| height_of(node) == height)
+        });
+    }
+
+    /// Le entrega a `node` un mensaje como si lo hubiera enviado `sender`.
+    fn receive(
+        node: &Arc<Mutex<BlockchainNode>>,
+        sender: &Arc<Mutex<BlockchainNode>>,
+        payload: &str,
+    ) {
+        let (identity, address) = {
+            let sender = sender.lock().unwrap();
+            (sender.identity(), sender.address())
+        };
+        let envelope = Envelope::new(identity, String::from(payload));
+        BlockchainNode::handle_incoming_message(node.clone(), envelope, &address);
+    }
+
+    fn term_of(node: &Arc<Mutex<BlockchainNode>>) -> u64 {
+        node.lock().unwrap().term()
+    }
+
+    #[test]
+    fn test_stale_coordinator_announcement_is_ignored() {
+        let (network, clock, nodes) = cluster(&[8501, 8502, 8503, 8504], &[]);
+        elect(&network, &clock, &nodes, 0, 8504);
+        let old_term = term_of(&nodes[3]);
+        assert!(old_term > 0);
+
+        // Se cae el coordinador y se elige a 8503 con un term posterior.
+        network.unbind(&address(8504));
+        nodes[3]
+            .lock()
+            .unwrap()
+            .partition(&[address(8501), address(8502), address(8503)]);
+        elect(&network, &clock, &nodes[..3], 0, 8503);
+        let term = term_of(&nodes[2]);
+        assert!(term > old_term);
+
+        // Un anuncio atrasado de 8502 no cambia nada.
+        receive(&nodes[0], &nodes[1], &format!("C;{}", old_term));
+        settle(&network, &clock);
+        assert_eq!(coordinator_of(&nodes[0]), 8503);
+        assert_eq!(term_of(&nodes[0]), term);
+
+        // El anuncio atrasado del coordinador caido no lo vuelve a poner: como 8504 le ganaria
+        // a 8503 se hace una eleccion nueva, que gana 8503 con un term posterior.
+        receive(&nodes[0], &nodes[3], &format!("C;{}", old_term));
+        assert_eq!(coordinator_of(&nodes[0]), 8503);
+        run_until(&network, &clock, || {
+            nodes[..3]
+                .iter()
+                .all(|node| term_of(node) > term && coordinator_of(node) == 8503)
+        });
+    }
+
+    #[test]
+    fn test_stale_lock_grant_is_ignored() {
+        let (network, clock, nodes) = cluster(&[8601, 8602, 8603], &[]);
+        elect(&network, &clock, &nodes, 0, 8603);
+        let term = term_of(&nodes[2]);
+        let got_grant = || {
+            *nodes[0]
+                .lock()
+                .unwrap()
+                .dist_mutex
+                .got_acquire_confirmation
+                .0
+                .lock()
+                .unwrap()
+        };
+
+        receive(&nodes[0], &nodes[2], &format!("OKACQ;{}", term));
+        receive(&nodes[0], &nodes[1], &format!("OKACQ;{}", term));
+        assert!(!got_grant());
+        assert_eq!(coordinator_of(&nodes[0]), 8603);
+
+        nodes[0].lock().unwrap().dist_mutex.acquire_pending = Some(8603);
+        receive(&nodes[0], &nodes[2], &format!("OKACQ;{}", term - 1));
+        assert!(!got_grant());
+        receive(&nodes[0], &nodes[1], &format!("OKACQ;{}", term + 1));
+        assert!(!got_grant());
+        assert_eq!(coordinator_of(&nodes[0]), 8603);
+        assert_eq!(term_of(&nodes[0]), term);
+        receive(&nodes[0], &nodes[2], &format!("OKACQ;{}", term));
+        assert!(got_grant());
+    }
+
+    #[test]
+    fn test_add_grade_gives_up_after_retry_policy() {
+        let (network, clock, nodes) = cluster(&[8201, 8202, 8203], &[]);
+        elect(&network, &clock, &nodes, 2, 8203);
+        nodes[2]
+            .lock()
+            .unwrap()
+            .add_fault(FaultRule::from_args(&["recv", "*", "ACQUI", "drop=100"]).unwrap());
+        {
+            let mut writer = nodes[0].lock().unwrap();
+            writer.timeouts.ok_acquire = Duration::from_secs(1);
+            writer.retry_policy.max_attempts = 3;
+        }
+
+        let writer = nodes[0].clone();
+        let add_grade =
+            clock.spawn_task(move || BlockchainNode::add_grade(writer, String::from("ana"), 7.0));
+        run_until(&network, &clock, || add_grade.is_finished());
+        let error = add_grade.join().unwrap().unwrap_err();
+        assert!(error.contains("after 3 attempts"), "{}", error);
+        assert!(nodes.iter().all(|node| height_of(node) == 0));
+        assert_eq!(coordinator_of(&nodes[0]), 8203);
+    }
+
+    #[test]
+    fn test_add_grade_waits_no_longer_than_retry_deadline() {
+        let (network, clock, nodes) = cluster(&[9051, 9052, 9053], &[]);
+        elect(&network, &clock, &nodes, 2, 9053);
+        nodes[2]
+            .lock()
+            .unwrap()
+            .add_fault(FaultRule::from_args(&["recv", "*", "ACQUI", "drop=100"]).unwrap());
+        {
+            let mut writer = nodes[0].lock().unwrap();
+            writer.timeouts.ok_acquire = Duration::from_secs(30);
+            writer.retry_policy.deadline = Duration::from_secs(1);
+        }
+
+        let start = clock.elapsed();
+        let writer = nodes[0].clone();
+        let add_grade =
+            clock.spawn_task(move || BlockchainNode::add_grade(writer, String::from("ana"), 7.0));
+        run_until(&network, &clock, || add_grade.is_finished());
+        assert!(add_grade.join().unwrap().is_err());
+        assert!(clock.elapsed() - start <= Duration::from_secs(1) + STEP);
+    }
+
+    #[test]
+    fn test_joining_node_syncs_and_adds_grades() {
+        let (network, clock, mut nodes) = cluster(&[7601, 7602, 7603], &[]);
+        elect(&network, &clock, &nodes, 2, 7603);
+        add_grade(&network, &clock, &nodes, 0, "ana");
+
+        let joiner = start_node(&network, &clock, node_config(7600, vec![address(7601)]));
+        let joining = joiner.clone();
+        let join = clock.spawn_task(move || BlockchainNode::join_cluster(joining, address(7601)));
+        run_until(&network, &clock, || join.is_finished());
+        assert!(join.join().unwrap());
+        assert_eq!(coordinator_of(&joiner), 7603);
+        assert!(!joiner.lock().unwrap().outranks_coordinator());
+        for node in &nodes {
+            assert!(node
+                .lock()
+                .unwrap()
+                .neighbor_addresses
+                .contains(&address(7600)));
+        }
+        assert_eq!(joiner.lock().unwrap().neighbor_addresses.len(), 3);
+
+        let syncing = joiner.clone();
+        let sync = clock.spawn_task(move || BlockchainNode::ask_for_blockchain(syncing));
+        run_until(&network, &clock, || sync.is_finished());
+        assert_eq!(height_of(&joiner), 1);
+
+        nodes.push(joiner);
+        add_grade(&network, &clock, &nodes, 3, "juan");
+    }
+
+    #[test]
+    fn test_unsolicited_members_are_ignored() {
+        let (network, clock, nodes) = cluster(&[9021, 9022, 9023], &[]);
+        elect(&network, &clock, &nodes, 0, 9023);
+        let members = "MEMBERS;7;9022,0,127.0.0.1:9022;127.0.0.1:9022,127.0.0.1:9099";
+        receive(&nodes[0], &nodes[1], members);
+        assert_eq!(coordinator_of(&nodes[0]), 9023);
+        assert_eq!(term_of(&nodes[0]), term_of(&nodes[2]));
+        assert!(!nodes[0]
+            .lock()
+            .unwrap()
+            .neighbor_addresses
+            .contains(&address(9099)));
+
+        // Mientras espera el MEMBERS de 9021 tampoco se acepta el de otro miembro.
+        let joiner = start_node(&network, &clock, node_config(9020, vec![address(9021)]));
+        let joining = joiner.clone();
+        let join = clock.spawn_task(move || BlockchainNode::join_cluster(joining, address(9021)));
+        clock.wait_until_idle();
+        receive(&joiner, &nodes[1], members);
+        assert_eq!(term_of(&joiner), 0);
+        run_until(&network, &clock, || join.is_finished());
+        assert!(join.join().unwrap());
+        assert_eq!(coordinator_of(&joiner), 9023);
+    }
+
+    #[test]
+    fn test_leaving_coordinator_hands_over_its_queue() {
+        let (network, clock, nodes) = cluster(&[7801, 7802, 7803], &[]);
+        elect(&network, &clock, &nodes, 2, 7803);
+
+        let holder = nodes[1].clone();
+        let acquire =
+            clock.spawn_task(move || DistMutex::acquire(holder, Timeouts::default().ok_acquire));
+        run_until(&network, &clock, || acquire.is_finished());
+        assert_eq!(acquire.join().unwrap(), Ok(()));
+
+        let writer = nodes[0].clone();
+        let add_grade =
+            clock.spawn_task(move || BlockchainNode::add_grade(writer, String::from("juan"), 8.0));
+        run_until(&network, &clock, || {
+            nodes[2]
+                .lock()
+                .unwrap()
+                .coordinator_state
+                .waiting_nodes_queue
+                .len()
+                == 1
+        });
+
+        let leaving = nodes[2].clone();
+        let leave = clock.spawn_task(move || BlockchainNode::leave(leaving));
+        run_until(&network, &clock, || nodes[2].lock().unwrap().leaving);
+        {
+            let mut holder = nodes[1].lock().unwrap();
+            let message = AddGradeMessage::ToCoordinator(String::from("ana"), 9.0).as_string();
+            holder
+                .messenger
+                .send(message.as_bytes(), &holder.dist_mutex.coordinator_addr);
+            holder.dist_mutex.release();
+        }
+
+        let remaining = &nodes[..2];
+        run_until(&network, &clock, || {
+            leave.is_finished()
+                && add_grade.is_finished()
+                && remaining.iter().all(|node| height_of(node) == 2)
+        });
+        assert_eq!(add_grade.join().unwrap(), Ok(()));
+        for node in remaining {
+            assert_eq!(coordinator_of(node), 7802);
+            assert!(!node
+                .lock()
+                .unwrap()
+                .neighbor_addresses
+                .contains(&address(7803)));
+        }
+        assert_eq!(height_of(&nodes[2]), 1);
+    }
+
+    #[test]
+    fn test_handoff_from_non_coordinator_is_ignored() {
+        let (network, clock, nodes) = cluster(&[9011, 9012, 9013], &[]);
+        elect(&network, &clock, &nodes, 2, 9013);
+        let term = term_of(&nodes[0]);
+
+        let handoff = MembershipMessage::Handoff(term, vec![]).as_string();
+        receive(&nodes[0], &nodes[1], &handoff);
+        settle(&network, &clock);
+        assert_eq!(coordinator_of(&nodes[0]), 9013);
+        assert_eq!(term_of(&nodes[0]), term);
+        assert!(nodes[0]
+            .lock()
+            .unwrap()
+            .neighbor_addresses
+            .contains(&address(9012)));
+
+        let stale_handoff = MembershipMessage::Handoff(term - 1, vec![]).as_string();
+        receive(&nodes[0], &nodes[2], &stale_handoff);
+        settle(&network, &clock);
+        assert_eq!(coordinator_of(&nodes[0]), 9013);
+        assert_eq!(term_of(&nodes[0]), term);
+    }
+
+    #[test]
+    fn test_restarted_node_recovers_chain_and_rejoins() {
+        let data_dir = temp_path("restart");
+        let mut config = node_config(7901, vec![address(7902), address(7903)]);
+        config.data_dir = Some(data_dir.clone());
+        let (network, clock, others) = cluster(&[7902, 7903], &[7901]);
+        let mut nodes = vec![start_node(&network, &clock, config)];
+        nodes.extend(others);
+        elect(&network, &clock, &nodes, 2, 7903);
+        add_grade(&network, &clock, &nodes, 0, "ana");
+
+        // Se cae el nodo y el cluster sigue agregando registros sin el.
+        network.unbind(&address(7901));
+        add_grade(&network, &clock, &nodes[1..], 0, "juan");
+        assert_eq!(height_of(&nodes[0]), 1);
+
+        let mut config = node_config(7901, vec![address(7902), address(7903)]);
+        config.data_dir = Some(data_dir.clone());
+        nodes[0] = start_node(&network, &clock, config);
+        assert!(nodes[0].lock().unwrap().recovered_chain());
+        assert_eq!(height_of(&nodes[0]), 1);
+
+        let returning = nodes[0].clone();
+        let rejoin = clock.spawn_task(move || {
+            let joined = BlockchainNode::rejoin_cluster(returning.clone());
+            BlockchainNode::ask_for_blockchain(returning);
+            joined
+        });
+        run_until(&network, &clock, || rejoin.is_finished());
+        assert!(rejoin.join().unwrap());
+        assert_eq!(height_of(&nodes[0]), 2);
+        assert_eq!(coordinator_of(&nodes[0]), 7903);
+        assert!(!nodes[0].lock().unwrap().outranks_coordinator());
+
+        add_grade(&network, &clock, &nodes, 0, "pedro");
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn test_nodes_discover_each_other_from_seeds() {
+        let data_dir = temp_path("discovery");
+        let network = MemoryNetwork::new();
+        let clock = Arc::new(VirtualClock::new());
+        // 8001 es la semilla de 8002 y 8003, y 8002 la de 8004.
+        let seeds = [
+            (8001, vec![]),
+            (8002, vec![8001]),
+            (8003, vec![8001]),
+            (8004, vec![8002]),
+        ];
+        let nodes = seeds
+            .iter()
+            .map(|(port, seeds)| {
+                let mut config =
+                    node_config(*port, seeds.iter().map(|seed| address(*seed)).collect());
+                config.data_dir = Some(data_dir.clone());
+                start_node(&network, &clock, config)
+            })
+            .collect::<Vec<Arc<Mutex<BlockchainNode>>>>();
+        let discoveries = nodes
+            .iter()
+            .map(|node| {
+                let node = node.clone();
+                clock.spawn_task(move || BlockchainNode::discover_peers(node))
+            })
+            .collect::<Vec<TaskHandle<()>>>();
+        run_until(&network, &clock, || {
+            discoveries.iter().all(|discovery| discovery.is_finished())
+        });
+        for node in &nodes {
+            assert_eq!(node.lock().unwrap().neighbor_addresses.len(), 3);
+        }
+        let mut known_peers = AddressBook::open(&data_dir, 8004).unwrap().load().unwrap();
+        known_peers.sort();
+        assert_eq!(
+            known_peers,
+            vec![address(8001), address(8002), address(8003)]
+        );
+
+        elect(&network, &clock, &nodes, 0, 8004);
+        add_grade(&network, &clock, &nodes, 0, "ana");
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+
+    #[test]
+    fn test_gossip_skips_own_and_departed_addresses() {
+        let (network, clock, nodes) = cluster(&[9031, 9032, 9033], &[]);
+        let own_identity = nodes[0].lock().unwrap().identity();
+        nodes[0]
+            .lock()
+            .unwrap()
+            .peer_directory
+            .record(own_identity, address(9039));
+        receive(&nodes[0], &nodes[2], &MembershipMessage::Leave.as_string());
+
+        let peers = vec![address(9031), address(9033), address(9039)];
+        receive(
+            &nodes[0],
+            &nodes[1],
+            &MembershipMessage::Peers(peers).as_string(),
+        );
+        settle(&network, &clock);
+        assert_eq!(
+            nodes[0].lock().unwrap().neighbor_addresses,
+            vec![address(9032)]
+        );
+
+        receive(
+            &nodes[0],
+            &nodes[2],
+            &MembershipMessage::Peers(vec![]).as_string(),
+        );
+        assert!(nodes[0]
+            .lock()
+            .unwrap()
+            .neighbor_addresses
+            .contains(&address(9033)));
+    }
+
+    #[test]
+    fn test_dead_neighbors_are_not_gossiped() {
+        let network = MemoryNetwork::new();
+        let clock = Arc::new(VirtualClock::new());
+        let nodes = [
+            start_node(
+                &network,
+                &clock,
+                node_config(9041, vec![address(9042), address(9043)]),
+            ),
+            start_node(&network, &clock, node_config(9042, Vec::new())),
+        ];
+        let pinger = nodes[0].clone();
+        clock.spawn_task(move || BlockchainNode::start_heartbeat(pinger));
+        run_until(&network, &clock, || {
+            nodes[0].lock().unwrap().peer_status(&address(9043)) == PeerStatus::Dead
+        });
+
+        nodes[0].lock().unwrap().send_peers(&address(9042));
+        settle(&network, &clock);
+        assert!(!nodes[1]
+            .lock()
+            .unwrap()
+            .neighbor_addresses
+            .contains(&address(9043)));
+    }
+
+    #[test]
+    fn test_nodes_discover_their_cluster_by_announcements() {
+        let network = MemoryNetwork::new();
+        let clock = Arc::new(VirtualClock::new());
+        let start = |port: u16, cluster_name: &str| {
+            let mut config = node_config(port, Vec::new());
+            config.discovery = Some(
+                DiscoveryConfig::new(
+                    cluster_name.to_string(),
+                    None,
+                    Some((8100, 8105)),
+                    &address(port),
+                )
+                .unwrap(),
+            );
+            start_node(&network, &clock, config)
+        };
+        let nodes = vec![start(8101, "lab"), start(8102, "lab"), start(8103, "lab")];
+        let stranger = start(8104, "other");
+        let discoveries = nodes
+            .iter()
+            .chain(std::iter::once(&stranger))
+            .map(|node| {
+                let node = node.clone();
+                clock.spawn_task(move || BlockchainNode::discover_peers(node))
+            })
+            .collect::<Vec<TaskHandle<()>>>();
+        run_until(&network, &clock, || {
+            discoveries.iter().all(|discovery| discovery.is_finished())
+        });
+        for node in &nodes {
+            let neighbors = node.lock().unwrap().neighbor_addresses.clone();
+            assert_eq!(neighbors.len(), 2);
+            assert!(!neighbors.contains(&address(8104)));
+        }
+        assert!(stranger.lock().unwrap().neighbor_addresses.is_empty());
+
+        elect(&network, &clock, &nodes, 0, 8103);
+        add_grade(&network, &clock, &nodes, 0, "ana");
     }
 }
@@ -0,0 +1,115 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::blockchain_node::BlockchainNode;
+use crate::election_algorithm::ElectionAlgorithm;
+use crate::election_message::ElectionMessage;
+use crate::logger::log;
+use crate::node_identity::NodeIdentity;
+use crate::peer_status::PeerStatus;
+
+/// Algoritmo Bully: el nodo le envia ELECTION a los vecinos vivos que le ganarian y, si ninguno
+/// responde OK a tiempo, queda como coordinador y lo anuncia a todos.
+pub struct BullyElection {
+    got_ok: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl Default for BullyElection {
+    #[allow(clippy::mutex_atomic)]
+    fn default() -> Self {
+        BullyElection {
+            got_ok: Arc::new((Mutex::new(false), Condvar::new())),
+        }
+    }
+}
+
+impl ElectionAlgorithm for BullyElection {
+    /// Al finalizar, el nodo con mayor prioridad (y a igual prioridad, mayor ID) es quien queda
+    /// como coordinador.
+    #[allow(clippy::mutex_atomic)]
+    fn begin_election(&self, arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let mut sent_elections = 0;
+        let (clock, ok_election_timeout) = {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            let term = match _self.start_election() {
+                Some(term) => term,
+                None => return,
+            };
+            *self.got_ok.0.lock().unwrap() = false;
+
+            for neighbor in _self.neighbor_addresses() {
+                // A los vecinos de los que todavia no se conoce el ID tambien se les envia ELECTION
+                if let Some(identity) = _self.identity_at(neighbor) {
+                    if identity < _self.identity() {
+                        continue;
+                    }
+                }
+                if _self.peer_status(neighbor) == PeerStatus::Dead {
+                    log(format!("\t\tSkipping ELECTION to dead {:?}", neighbor));
+                    continue;
+                }
+                sent_elections += 1;
+                log(format!("\t\tSending ELECTION to {:?}", neighbor));
+                _self.send(&ElectionMessage::Election(term).as_bytes(), neighbor);
+            }
+            (_self.clock(), _self.timeouts().ok_election)
+        };
+
+        let got_ok = if sent_elections == 0 {
+            log("No hay vecinos vivos con mayor ID. No espero respuestas.".to_string());
+            false
+        } else {
+            log("Enviando mensaje ELECTION a vecinos. Esperando sus respuestas...".to_string());
+            clock.wait_until(&self.got_ok, true, ok_election_timeout)
+        };
+        if !got_ok {
+            let mut _self = arc_mutex_self.lock().unwrap();
+            _self.make_leader();
+            _self.end_election();
+        } else {
+            let _is_in_election = { arc_mutex_self.lock().unwrap().election_in_progress() };
+            while !clock.wait_until(&_is_in_election, false, ok_election_timeout) {}
+        }
+    }
+
+    fn process_message(
+        &self,
+        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
+        message: &str,
+        sender: &SocketAddr,
+        sender_identity: NodeIdentity,
+    ) -> bool {
+        match ElectionMessage::from_bytes(message.as_bytes()) {
+            Some(ElectionMessage::Election(term)) => {
+                let self_identity = {
+                    let mut _self = arc_mutex_self.lock().unwrap();
+                    _self.observe_term(term);
+                    _self.identity()
+                };
+                log(format!(
+                    "Quieren hacer elecciones desde {} (term {}) y yo soy {}!",
+                    sender_identity, term, self_identity
+                ));
+                if self_identity > sender_identity {
+                    let clock = {
+                        let _self = arc_mutex_self.lock().unwrap();
+                        _self.send(&ElectionMessage::OkElection.as_bytes(), sender);
+                        _self.clock()
+                    };
+                    clock.spawn(Box::new(move || {
+                        BlockchainNode::begin_election(arc_mutex_self);
+                    }));
+                }
+                true
+            }
+            Some(ElectionMessage::OkElection) => {
+                log("Recibi OkElection. No seré el coordinador.".to_string());
+                *self.got_ok.0.lock().unwrap() = true;
+                self.got_ok.1.notify_all();
+                true
+            }
+            // El anuncio del coordinador lo procesa el nodo, sea cual sea el algoritmo.
+            Some(ElectionMessage::Coordinator(_)) | None => false,
+        }
+    }
+}
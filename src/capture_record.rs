@@ -0,0 +1,92 @@
+use std::net::SocketAddr;
+
+use crate::fault_direction::FaultDirection;
+use crate::node_identity::NodeIdentity;
+
+/// Mensaje enviado o recibido por un nodo, tal como queda en el archivo de captura.
+/// El mensaje se guarda ya descifrado y sin el sobre.
+///
+/// Representacion: `<timestamp ms> <send|recv> <vecino> <id>,<prioridad> <mensaje>`, donde el
+/// vecino es el destinatario o el emisor y la identidad es la del nodo que envio el mensaje.
+/// Ejemplo: `1700000000000 recv 127.0.0.1:6061 6061,0 GRADE_FROM_COORDINATOR;0;0;juan;8;123`
+#[derive(PartialEq, Debug, Clone)]
+pub struct CaptureRecord {
+    pub timestamp: u64,
+    pub direction: FaultDirection,
+    pub peer: SocketAddr,
+    pub sender: NodeIdentity,
+    pub payload: String,
+}
+
+impl CaptureRecord {
+    pub fn as_string(&self) -> String {
+        format!(
+            "{} {} {} {},{} {}",
+            self.timestamp,
+            self.direction,
+            self.peer,
+            self.sender.id,
+            self.sender.priority,
+            self.payload
+        )
+    }
+
+    pub fn from_string(string: &str) -> Option<CaptureRecord> {
+        let fields = string.splitn(5, ' ').collect::<Vec<&str>>();
+        if fields.len() != 5 {
+            return None;
+        }
+        let (id, priority) = fields[3].split_once(',')?;
+        Some(CaptureRecord {
+            timestamp: fields[0].parse().ok()?,
+            direction: fields[1].parse().ok()?,
+            peer: fields[2].parse().ok()?,
+            sender: NodeIdentity::new(id.parse().ok()?, priority.parse().ok()?),
+            payload: fields[4].to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> CaptureRecord {
+        CaptureRecord {
+            timestamp: 1700000000000,
+            direction: FaultDirection::Receive,
+            peer: "[::1]:6061".parse().unwrap(),
+            sender: NodeIdentity::new(6061, 2),
+            payload: String::from("GRADE_TO_COORDINATOR;juan;8"),
+        }
+    }
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!(
+            record().as_string(),
+            "1700000000000 recv [::1]:6061 6061,2 GRADE_TO_COORDINATOR;juan;8"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(
+            CaptureRecord::from_string(&record().as_string()),
+            Some(record())
+        );
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        assert_eq!(CaptureRecord::from_string(""), None);
+        assert_eq!(
+            CaptureRecord::from_string("1 sideways 127.0.0.1:1 1,0 PING;1"),
+            None
+        );
+        assert_eq!(
+            CaptureRecord::from_string("1 send 127.0.0.1:1 1 PING;1"),
+            None
+        );
+    }
+}
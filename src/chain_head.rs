@@ -0,0 +1,42 @@
+/// Cabeza de una cadena: cantidad de registros y hash del ultimo (0 si esta vacia).
+/// Alcanza para saber si a un nodo le faltan registros que tiene otro.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ChainHead {
+    pub height: u64,
+    pub hash: u64,
+}
+
+impl ChainHead {
+    pub fn as_string(&self) -> String {
+        format!("{},{}", self.height, self.hash)
+    }
+
+    pub fn from_string(string: &str) -> Option<ChainHead> {
+        let (height, hash) = string.split_once(',')?;
+        Some(ChainHead {
+            height: height.parse::<u64>().ok()?,
+            hash: hash.parse::<u64>().ok()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let head = ChainHead {
+            height: 3,
+            hash: 1234,
+        };
+        assert_eq!(head.as_string(), "3,1234");
+        assert_eq!(ChainHead::from_string("3,1234"), Some(head));
+    }
+
+    #[test]
+    fn test_invalid_from_string() {
+        assert_eq!(ChainHead::from_string("3"), None);
+        assert_eq!(ChainHead::from_string("x,1"), None);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::blockchain::{Blockchain, BlockchainRecord};
+use crate::chain_head::ChainHead;
+use crate::data_file::DataFile;
+use crate::logger::log;
+
+/// Archivo en el que un nodo iniciado con `--data-dir` guarda su cadena, un registro por linea
+/// (`<nombre>,<nota>,<hash>`), para recuperarla si se reinicia con la misma identidad.
+/// Cada nodo usa su propio archivo (`node-<id>.chain`), asi que varios pueden compartir directorio.
+pub struct ChainStore {
+    file: DataFile,
+    /// Cabeza de la cadena que quedo escrita en el archivo, si se sabe.
+    saved: Option<ChainHead>,
+}
+
+impl ChainStore {
+    pub fn open(dir: &str, node_id: u64) -> Result<ChainStore, String> {
+        Ok(ChainStore {
+            file: DataFile::open(dir, node_id, "chain", "chain file")?,
+            saved: None,
+        })
+    }
+
+    /// Lee la cadena guardada, o `None` si el nodo nunca guardo una. Si el archivo quedo
+    /// corrupto (por ejemplo por una caida a mitad de escritura) se conservan los registros que
+    /// encadenan hasta la primera linea invalida; el resto se vuelve a pedir a los vecinos.
+    pub fn load(&self) -> Result<Option<Blockchain>, String> {
+        let content = match self.file.read()? {
+            Some(content) => content,
+            None => return Ok(None),
+        };
+        let mut blockchain = Blockchain::new();
+        for (number, line) in content.lines().enumerate() {
+            let appended = match BlockchainRecord::from_string(line) {
+                Some(record) => blockchain.append_verified(record),
+                None => false,
+            };
+            if !appended {
+                log(format!(
+                    "Invalid record at line {} of {:?}. Keeping the first {} records",
+                    number + 1,
+                    self.file.path(),
+                    blockchain.height()
+                ));
+                break;
+            }
+        }
+        Ok(Some(blockchain))
+    }
+
+    /// Guarda la cadena. Si solo crecio desde la ultima vez se agregan al final los registros
+    /// nuevos; si no (se descartaron registros, o no se sabe que hay en el archivo) se reemplaza
+    /// el archivo entero.
+    pub fn save(&mut self, blockchain: &Blockchain) -> Result<(), String> {
+        let head = blockchain.head();
+        let result = match self.saved {
+            Some(saved) if saved == head => return Ok(()),
+            Some(saved) if blockchain.hash_at(saved.height) == Some(saved.hash) => {
+                self.file.append(&lines(
+                    &blockchain.records_from(saved.height, head.height - saved.height),
+                ))
+            }
+            _ => self
+                .file
+                .replace(&lines(&blockchain.records_from(0, head.height))),
+        };
+        // Si la escritura fallo no se sabe que quedo en el archivo: la proxima se reescribe.
+        self.saved = result.as_ref().ok().map(|_| head);
+        result
+    }
+}
+
+fn lines(records: &[BlockchainRecord]) -> String {
+    records
+        .iter()
+        .map(|record| format!("{}\n", record.as_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_file::temp_path;
+    use std::fs;
+
+    fn blockchain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.add_grade(String::from("ana"), 9.0);
+        blockchain.add_grade(String::from("juan"), 7.5);
+        blockchain
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = temp_path("chain-store-round-trip");
+        let mut store = ChainStore::open(&dir, 6060).unwrap();
+        assert_eq!(store.load(), Ok(None));
+        store.save(&blockchain()).unwrap();
+        assert_eq!(store.load(), Ok(Some(blockchain())));
+        assert_eq!(ChainStore::open(&dir, 6061).unwrap().load(), Ok(None));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_keeps_records_before_corrupt_line() {
+        let dir = temp_path("chain-store-corrupt");
+        let mut store = ChainStore::open(&dir, 6060).unwrap();
+        store.save(&blockchain()).unwrap();
+        let content = fs::read_to_string(store.file.path()).unwrap();
+        let first_line = content.lines().next().unwrap();
+        fs::write(store.file.path(), format!("{}\npedro,8", first_line)).unwrap();
+        let loaded = store.load().unwrap().unwrap();
+        assert_eq!(loaded.height(), 1);
+        assert_eq!(loaded.hash_at(1), blockchain().hash_at(1));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_appends_new_records_and_rewrites_discarded_ones() {
+        let dir = temp_path("chain-store-append");
+        let mut store = ChainStore::open(&dir, 6060).unwrap();
+        let mut chain = blockchain();
+        store.save(&chain).unwrap();
+        chain.add_grade(String::from("pedro"), 6.0);
+        store.save(&chain).unwrap();
+        assert_eq!(store.load().unwrap().unwrap().head(), chain.head());
+
+        chain.truncate(1);
+        chain.add_grade(String::from("maria"), 10.0);
+        store.save(&chain).unwrap();
+        assert_eq!(store.load(), Ok(Some(chain)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,240 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::chain_head::ChainHead;
+
+/// Cantidad maxima de registros que se piden (y se envian) en un mismo rango.
+pub const RANGE_SIZE: u64 = 16;
+
+/// Estado de la sincronizacion incremental de la cadena. Se sincroniza desde el vecino con la
+/// cabeza mas alta que se conozca, pidiendole rangos acotados a partir de la altura propia.
+/// Si un rango no llega se vuelve a pedir desde la altura alcanzada, sin empezar de cero.
+///
+/// Al iniciar el nodo se juntan las cabezas de los vecinos durante una ventana de tiempo y se
+/// ordenan como candidatas: primero la mas alta y, a igual altura, la que tengan mas vecinos.
+/// Si la cadena de una candidata resulta invalida se pasa a la siguiente.
+#[derive(Default)]
+pub struct ChainSync {
+    collecting: bool,
+    heads: HashMap<SocketAddr, ChainHead>,
+    candidates: VecDeque<(SocketAddr, ChainHead)>,
+    source: Option<SocketAddr>,
+    target: Option<ChainHead>,
+    start_height: u64,
+    requested_at: Option<Instant>,
+}
+
+impl ChainSync {
+    /// Empieza a juntar las cabezas de los vecinos en lugar de sincronizar con la primera que llegue.
+    pub fn start_collecting(&mut self) {
+        self.collecting = true;
+        self.heads.clear();
+    }
+
+    /// Termina la ventana de recoleccion y deja encoladas como candidatas las cabezas que estan
+    /// mas adelante que la cadena propia. Devuelve las cabezas recibidas agrupadas y ordenadas
+    /// por preferencia (primero las que tienen mas vecinos de acuerdo y, entre esas, las mas
+    /// altas), con los vecinos que tienen cada una.
+    pub fn finish_collecting(&mut self, own_height: u64) -> Vec<(ChainHead, Vec<SocketAddr>)> {
+        self.collecting = false;
+        let mut groups: Vec<(ChainHead, Vec<SocketAddr>)> = Vec::new();
+        for (addr, head) in self.heads.drain() {
+            match groups
+                .iter_mut()
+                .find(|(group_head, _)| *group_head == head)
+            {
+                Some((_, addresses)) => addresses.push(addr),
+                None => groups.push((head, vec![addr])),
+            }
+        }
+        for (_, addresses) in groups.iter_mut() {
+            addresses.sort();
+        }
+        groups.sort_by(|(a_head, a_addresses), (b_head, b_addresses)| {
+            b_addresses
+                .len()
+                .cmp(&a_addresses.len())
+                .then(b_head.height.cmp(&a_head.height))
+                .then(a_head.hash.cmp(&b_head.hash))
+        });
+        self.candidates = groups
+            .iter()
+            .filter(|(head, _)| head.height > own_height)
+            .flat_map(|(head, addresses)| addresses.iter().map(move |addr| (*addr, *head)))
+            .collect();
+        groups
+    }
+
+    /// Pasa a sincronizar desde la siguiente candidata que este mas adelante que la cadena propia.
+    /// Devuelve `false` si no quedan candidatas.
+    pub fn next_candidate(&mut self, own_height: u64) -> bool {
+        while let Some((addr, head)) = self.candidates.pop_front() {
+            if head.height > own_height {
+                self.start(addr, head, own_height);
+                return true;
+            }
+        }
+        self.source = None;
+        self.target = None;
+        false
+    }
+
+    /// Registra la cabeza anunciada por un vecino. Devuelve `true` si pasa a ser la fuente de
+    /// la sincronizacion, es decir si esta mas adelante que la cadena propia y que el objetivo actual.
+    /// Durante la ventana de recoleccion solo se la guarda como candidata.
+    pub fn observe(&mut self, addr: SocketAddr, head: ChainHead, own_height: u64) -> bool {
+        if self.collecting {
+            self.heads.insert(addr, head);
+            return false;
+        }
+        let current_target = self.target.map_or(own_height, |target| target.height);
+        if head.height <= own_height || head.height <= current_target {
+            return false;
+        }
+        self.start(addr, head, own_height);
+        true
+    }
+
+    fn start(&mut self, addr: SocketAddr, head: ChainHead, own_height: u64) {
+        self.source = Some(addr);
+        self.target = Some(head);
+        self.start_height = own_height;
+        self.requested_at = None;
+    }
+
+    pub fn source(&self) -> Option<SocketAddr> {
+        self.source
+    }
+
+    /// Altura de la cadena propia al empezar a sincronizar desde la fuente actual. Si su cadena
+    /// resulta invalida se descartan los registros recibidos de ella a partir de esta altura.
+    pub fn start_height(&self) -> u64 {
+        self.start_height
+    }
+
+    pub fn record_request(&mut self, now: Instant) {
+        self.requested_at = Some(now);
+    }
+
+    /// Hay una sincronizacion en curso cuyo ultimo pedido no tuvo respuesta a tiempo.
+    pub fn is_stalled(&self, now: Instant, timeout: Duration) -> bool {
+        match (self.source, self.requested_at) {
+            (Some(_), Some(requested_at)) => now.saturating_duration_since(requested_at) >= timeout,
+            _ => false,
+        }
+    }
+
+    pub fn is_caught_up(&self, own_height: u64) -> bool {
+        self.target.is_none_or(|target| own_height >= target.height)
+    }
+
+    /// Al llegar a la altura anunciada por la fuente, el ultimo hash tiene que ser el anunciado.
+    pub fn matches_target(&self, own_head: ChainHead) -> bool {
+        self.target
+            .is_none_or(|target| target.height != own_head.height || target == own_head)
+    }
+
+    /// Termina (o abandona) la sincronizacion en curso.
+    pub fn finish(&mut self) {
+        *self = ChainSync::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn head(height: u64) -> ChainHead {
+        ChainHead { height, hash: 0 }
+    }
+
+    #[test]
+    fn test_observe_picks_the_highest_head() {
+        let mut sync = ChainSync::default();
+        assert!(!sync.observe(peer(1), head(2), 2));
+        assert!(sync.observe(peer(1), head(5), 2));
+        assert!(!sync.observe(peer(2), head(4), 2));
+        assert!(sync.observe(peer(3), head(9), 2));
+        assert_eq!(sync.source(), Some(peer(3)));
+        assert!(!sync.is_caught_up(5));
+        assert!(sync.is_caught_up(9));
+    }
+
+    #[test]
+    fn test_collecting_ranks_majority_then_longest() {
+        let mut sync = ChainSync::default();
+        sync.start_collecting();
+        let majority = ChainHead { height: 5, hash: 1 };
+        let minority = ChainHead { height: 5, hash: 2 };
+        assert!(!sync.observe(peer(1), minority, 0));
+        assert!(!sync.observe(peer(2), majority, 0));
+        assert!(!sync.observe(peer(3), majority, 0));
+        assert!(!sync.observe(peer(4), head(3), 0));
+        assert!(!sync.observe(peer(5), head(0), 0));
+        assert!(!sync.observe(peer(6), head(3), 0));
+        assert!(!sync.observe(peer(7), head(9), 0));
+        assert_eq!(sync.source(), None);
+
+        let groups = sync.finish_collecting(0);
+        assert_eq!(
+            groups,
+            vec![
+                (majority, vec![peer(2), peer(3)]),
+                (head(3), vec![peer(4), peer(6)]),
+                (head(9), vec![peer(7)]),
+                (minority, vec![peer(1)]),
+                (head(0), vec![peer(5)]),
+            ]
+        );
+        let mut sources = Vec::new();
+        while sync.next_candidate(0) {
+            sources.push(sync.source().unwrap());
+        }
+        assert_eq!(
+            sources,
+            vec![peer(2), peer(3), peer(4), peer(6), peer(7), peer(1)]
+        );
+        assert_eq!(sync.source(), None);
+    }
+
+    #[test]
+    fn test_next_candidate_skips_heads_already_reached() {
+        let mut sync = ChainSync::default();
+        sync.start_collecting();
+        sync.observe(peer(1), head(9), 2);
+        sync.observe(peer(2), head(4), 2);
+        sync.finish_collecting(2);
+        assert!(sync.next_candidate(2));
+        assert_eq!(sync.start_height(), 2);
+        assert!(!sync.next_candidate(4));
+    }
+
+    #[test]
+    fn test_matches_target() {
+        let mut sync = ChainSync::default();
+        let target = ChainHead { height: 5, hash: 7 };
+        sync.observe(peer(1), target, 0);
+        assert!(sync.matches_target(head(3)));
+        assert!(sync.matches_target(target));
+        assert!(!sync.matches_target(head(5)));
+    }
+
+    #[test]
+    fn test_stalled_request() {
+        let start = Instant::now();
+        let mut sync = ChainSync::default();
+        sync.observe(peer(1), head(5), 0);
+        sync.record_request(start);
+        let timeout = Duration::from_secs(1);
+        assert!(!sync.is_stalled(start, timeout));
+        assert!(sync.is_stalled(start + timeout, timeout));
+        sync.finish();
+        assert!(!sync.is_stalled(start + timeout, timeout));
+        assert_eq!(sync.source(), None);
+    }
+}
@@ -0,0 +1,23 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Fuente de tiempo del nodo. En produccion es el reloj del sistema (`SystemClock`); en los
+/// tests, un reloj virtual (`VirtualClock`) que solo avanza cuando el test lo indica.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    fn sleep(&self, duration: Duration);
+
+    /// Espera a que `flag` valga `value` (avisado por su `Condvar`) o a que pase `timeout`.
+    /// Devuelve si `flag` llego a valer `value`.
+    fn wait_until(
+        &self,
+        flag: &Arc<(Mutex<bool>, Condvar)>,
+        value: bool,
+        timeout: Duration,
+    ) -> bool;
+
+    /// Corre `task` en un thread propio. El reloj virtual cuenta estas tareas para saber cuando
+    /// no queda ninguna que pueda avanzar sin que pase el tiempo.
+    fn spawn(&self, task: Box<dyn FnOnce() + Send>);
+}
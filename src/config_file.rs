@@ -0,0 +1,186 @@
+use std::fs;
+
+use crate::toml_value::TomlValue;
+
+/// Archivo de configuracion en un subconjunto de TOML: secciones (`[nodo]`, `[a.b]`),
+/// claves `clave = valor` y comentarios con `#`. Los arrays pueden ocupar varias lineas.
+/// Cada valor queda asociado a su clave completa (`seccion.clave`) y a la linea en la que esta.
+#[derive(Debug, PartialEq, Default)]
+pub struct ConfigFile {
+    pub entries: Vec<(String, TomlValue, usize)>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &str) -> Result<ConfigFile, Vec<String>> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| vec![format!("Couldn't read config file {:?}: {}", path, error)])?;
+        ConfigFile::parse(&content).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|error| format!("{}: {}", path, error))
+                .collect()
+        })
+    }
+
+    /// Interpreta el contenido del archivo. Devuelve todos los errores encontrados, no solo
+    /// el primero.
+    pub fn parse(content: &str) -> Result<ConfigFile, Vec<String>> {
+        let mut config_file = ConfigFile::default();
+        let mut errors = Vec::new();
+        let mut section = String::new();
+        let mut lines = content.lines().enumerate();
+        while let Some((index, line)) = lines.next() {
+            let number = index + 1;
+            let line = strip_comment(line);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[') {
+                match name.strip_suffix(']').map(str::trim) {
+                    Some(name) if is_key_path(name) => section = name.to_string(),
+                    _ => errors.push(format!("line {}: Invalid section {:?}", number, line)),
+                }
+                continue;
+            }
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) if is_key(key.trim()) => (key.trim(), value.trim().to_string()),
+                _ => {
+                    errors.push(format!("line {}: Expected <key> = <value>", number));
+                    continue;
+                }
+            };
+            // Un array sin cerrar sigue en las lineas siguientes.
+            let mut value = value;
+            while value.starts_with('[') && !is_balanced(&value) {
+                match lines.next() {
+                    Some((_, next)) => {
+                        value.push(' ');
+                        value.push_str(strip_comment(next).trim());
+                    }
+                    None => break,
+                }
+            }
+            let full_key = if section.is_empty() {
+                key.to_string()
+            } else {
+                format!("{}.{}", section, key)
+            };
+            if config_file.get(&full_key).is_some() {
+                errors.push(format!("line {}: Duplicated key {}", number, full_key));
+                continue;
+            }
+            match TomlValue::parse(&value) {
+                Ok(value) => config_file.entries.push((full_key, value, number)),
+                Err(error) => errors.push(format!("line {}: {}", number, error)),
+            }
+        }
+        if errors.is_empty() {
+            Ok(config_file)
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&TomlValue> {
+        self.entries
+            .iter()
+            .find(|(entry_key, _, _)| entry_key == key)
+            .map(|(_, value, _)| value)
+    }
+}
+
+/// Saca el comentario de una linea, sin confundir un `#` dentro de un string.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, character) in line.char_indices() {
+        match (quote, character) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if character == open && !escaped => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(character),
+            (None, '#') => return &line[..index],
+            _ => {}
+        }
+        escaped = false;
+    }
+    line
+}
+
+/// Si los corchetes fuera de strings estan balanceados.
+fn is_balanced(value: &str) -> bool {
+    let mut depth = 0;
+    let mut quote = None;
+    for character in value.chars() {
+        match (quote, character) {
+            (Some(open), _) if character == open => quote = None,
+            (None, '"') | (None, '\'') => quote = Some(character),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0
+}
+
+fn is_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.chars().all(|character| {
+            character.is_ascii_alphanumeric() || character == '_' || character == '-'
+        })
+}
+
+fn is_key_path(path: &str) -> bool {
+    path.split('.').all(|key| is_key(key.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let content = "# nodo 1\n\
+            top = 1\n\
+            [node]\n\
+            listen = \"127.0.0.1:6060\" # escucha\n\
+            peers = [\n  \"127.0.0.1:6061\", # uno\n  \"127.0.0.1:6062\",\n]\n\
+            [security.node_keys]\n\
+            3 = \"tres # no es comentario\"\n";
+        let config_file = ConfigFile::parse(content).unwrap();
+        assert_eq!(config_file.get("top"), Some(&TomlValue::Integer(1)));
+        assert_eq!(
+            config_file.get("node.listen"),
+            Some(&TomlValue::String(String::from("127.0.0.1:6060")))
+        );
+        assert_eq!(
+            config_file.get("node.peers"),
+            Some(&TomlValue::Array(vec![
+                TomlValue::String(String::from("127.0.0.1:6061")),
+                TomlValue::String(String::from("127.0.0.1:6062")),
+            ]))
+        );
+        assert_eq!(
+            config_file.get("security.node_keys.3"),
+            Some(&TomlValue::String(String::from("tres # no es comentario")))
+        );
+        assert_eq!(config_file.entries[1].2, 4);
+    }
+
+    #[test]
+    fn test_parse_reports_every_error() {
+        let content = "[node\nid = \nid = 1\nid = 2\nnot a key\n";
+        assert_eq!(
+            ConfigFile::parse(content),
+            Err(vec![
+                String::from("line 1: Invalid section \"[node\""),
+                String::from("line 2: Missing value"),
+                String::from("line 4: Duplicated key id"),
+                String::from("line 5: Expected <key> = <value>"),
+            ])
+        );
+    }
+}
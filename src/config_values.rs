@@ -0,0 +1,304 @@
+use crate::config_file::ConfigFile;
+use crate::retry_policy::RETRY_SETTINGS;
+use crate::timeouts::TIMEOUT_NAMES;
+use crate::toml_value::TomlValue;
+
+/// Valores de configuracion tal como se leyeron, antes de validarlos. Se arman a partir del
+/// archivo de configuracion y de los argumentos de linea de comandos, que tienen prioridad.
+#[derive(Debug, PartialEq, Default)]
+pub struct ConfigValues {
+    pub config_path: Option<String>,
+    pub node_id: Option<String>,
+    pub priority: Option<String>,
+    pub listen_address: Option<String>,
+    pub advertise: Option<String>,
+    pub neighbors: Option<Vec<String>>,
+    pub join: Option<String>,
+    pub data_dir: Option<String>,
+    pub auto_repair: Option<bool>,
+    pub capture_path: Option<String>,
+    pub replay_path: Option<String>,
+    pub cluster_key: Option<String>,
+    pub node_keys: Vec<String>,
+    pub encrypt: Option<bool>,
+    pub cluster_name: Option<String>,
+    pub discovery_address: Option<String>,
+    pub discovery_ports: Option<String>,
+    pub election: Option<String>,
+    pub log_enabled: Option<bool>,
+    pub log_file: Option<String>,
+    /// `<nombre>=<milisegundos>`, como en `--timeout`.
+    pub timeouts: Vec<String>,
+    /// `<nombre>=<valor>`, como en `--retry`.
+    pub retry: Vec<String>,
+}
+
+impl ConfigValues {
+    /// Lee los flags y los argumentos posicionales (`<listen address> <neighbor 1> ...`).
+    /// Devuelve tambien los errores de los flags a los que les falta el valor.
+    pub fn from_args(all_args: &[String]) -> (ConfigValues, Vec<String>) {
+        let mut values = ConfigValues::default();
+        let mut errors = Vec::new();
+        let mut positional = Vec::new();
+        let mut iter = all_args.iter();
+        while let Some(arg) = iter.next() {
+            let list = match arg.as_str() {
+                "--node-key" => Some(&mut values.node_keys),
+                "--timeout" => Some(&mut values.timeouts),
+                "--retry" => Some(&mut values.retry),
+                _ => None,
+            };
+            if let Some(list) = list {
+                match iter.next() {
+                    Some(value) => list.push(value.clone()),
+                    None => errors.push(format!("Missing value for {}", arg)),
+                }
+                continue;
+            }
+            let target = match arg.as_str() {
+                "--config" => &mut values.config_path,
+                "--id" => &mut values.node_id,
+                "--priority" => &mut values.priority,
+                "--join" => &mut values.join,
+                "--advertise" => &mut values.advertise,
+                "--data-dir" => &mut values.data_dir,
+                "--capture" => &mut values.capture_path,
+                "--replay" => &mut values.replay_path,
+                "--cluster-key" => &mut values.cluster_key,
+                "--discover" => &mut values.cluster_name,
+                "--discovery-address" => &mut values.discovery_address,
+                "--discovery-ports" => &mut values.discovery_ports,
+                "--election" => &mut values.election,
+                "--log-file" => &mut values.log_file,
+                "--encrypt" => {
+                    values.encrypt = Some(true);
+                    continue;
+                }
+                "--auto-repair" => {
+                    values.auto_repair = Some(true);
+                    continue;
+                }
+                "--quiet" => {
+                    values.log_enabled = Some(false);
+                    continue;
+                }
+                _ => {
+                    positional.push(arg.clone());
+                    continue;
+                }
+            };
+            match iter.next() {
+                Some(value) => *target = Some(value.clone()),
+                None => errors.push(format!("Missing value for {}", arg)),
+            }
+        }
+        if !positional.is_empty() {
+            values.listen_address = Some(positional.remove(0));
+        }
+        if !positional.is_empty() {
+            values.neighbors = Some(positional);
+        }
+        (values, errors)
+    }
+
+    /// Toma los valores del archivo de configuracion. Devuelve tambien los errores de las claves
+    /// desconocidas o con un valor del tipo equivocado.
+    pub fn from_file(config_file: &ConfigFile) -> (ConfigValues, Vec<String>) {
+        let mut values = ConfigValues::default();
+        let mut errors = Vec::new();
+        for (key, value, line) in &config_file.entries {
+            let result = match key.as_str() {
+                "node.id" => integer(value).map(|id| values.node_id = Some(id)),
+                "node.priority" => integer(value).map(|priority| values.priority = Some(priority)),
+                "node.listen" => string(value).map(|listen| values.listen_address = Some(listen)),
+                "node.peers" => strings(value).map(|peers| values.neighbors = Some(peers)),
+                "node.join" => string(value).map(|join| values.join = Some(join)),
+                "node.advertise" => {
+                    string(value).map(|advertise| values.advertise = Some(advertise))
+                }
+                "node.data_dir" => string(value).map(|dir| values.data_dir = Some(dir)),
+                "node.auto_repair" => {
+                    boolean(value).map(|repair| values.auto_repair = Some(repair))
+                }
+                "discovery.cluster" => string(value).map(|name| values.cluster_name = Some(name)),
+                "discovery.address" => {
+                    string(value).map(|address| values.discovery_address = Some(address))
+                }
+                "discovery.ports" => {
+                    string(value).map(|ports| values.discovery_ports = Some(ports))
+                }
+                "algorithms.election" => {
+                    string(value).map(|election| values.election = Some(election))
+                }
+                "logging.enabled" => {
+                    boolean(value).map(|enabled| values.log_enabled = Some(enabled))
+                }
+                "logging.file" => string(value).map(|file| values.log_file = Some(file)),
+                "security.cluster_key" => string(value).map(|key| values.cluster_key = Some(key)),
+                "security.encrypt" => boolean(value).map(|encrypt| values.encrypt = Some(encrypt)),
+                _ => match key.split_once('.') {
+                    Some(("timeouts", name)) if TIMEOUT_NAMES.contains(&name) => integer(value)
+                        .map(|millis| values.timeouts.push(format!("{}={}", name, millis))),
+                    Some(("retry", name)) if RETRY_SETTINGS.contains(&name) => integer(value)
+                        .map(|setting| values.retry.push(format!("{}={}", name, setting))),
+                    _ => match key.strip_prefix("security.node_keys.") {
+                        Some(id) => string(value)
+                            .map(|node_key| values.node_keys.push(format!("{}={}", id, node_key))),
+                        None => Err(String::from("unknown setting")),
+                    },
+                },
+            };
+            if let Err(error) = result {
+                errors.push(format!("line {}: {}: {}", line, key, error));
+            }
+        }
+        (values, errors)
+    }
+
+    /// Combina con `other`, cuyos valores tienen prioridad. Las claves por nodo, los timeouts y
+    /// los parametros de reintento de ambos se conservan; si se repite uno vale el de `other`,
+    /// que queda despues.
+    pub fn overridden_by(self, other: ConfigValues) -> ConfigValues {
+        let mut node_keys = self.node_keys;
+        node_keys.extend(other.node_keys);
+        let mut timeouts = self.timeouts;
+        timeouts.extend(other.timeouts);
+        let mut retry = self.retry;
+        retry.extend(other.retry);
+        ConfigValues {
+            config_path: other.config_path.or(self.config_path),
+            node_id: other.node_id.or(self.node_id),
+            priority: other.priority.or(self.priority),
+            listen_address: other.listen_address.or(self.listen_address),
+            advertise: other.advertise.or(self.advertise),
+            neighbors: other.neighbors.or(self.neighbors),
+            join: other.join.or(self.join),
+            data_dir: other.data_dir.or(self.data_dir),
+            auto_repair: other.auto_repair.or(self.auto_repair),
+            capture_path: other.capture_path.or(self.capture_path),
+            replay_path: other.replay_path.or(self.replay_path),
+            cluster_key: other.cluster_key.or(self.cluster_key),
+            node_keys,
+            encrypt: other.encrypt.or(self.encrypt),
+            cluster_name: other.cluster_name.or(self.cluster_name),
+            discovery_address: other.discovery_address.or(self.discovery_address),
+            discovery_ports: other.discovery_ports.or(self.discovery_ports),
+            election: other.election.or(self.election),
+            log_enabled: other.log_enabled.or(self.log_enabled),
+            log_file: other.log_file.or(self.log_file),
+            timeouts,
+            retry,
+        }
+    }
+}
+
+fn type_error(expected: &str, value: &TomlValue) -> String {
+    format!("expected {}, found {}", expected, value.type_name())
+}
+
+fn string(value: &TomlValue) -> Result<String, String> {
+    match value {
+        TomlValue::String(string) => Ok(string.clone()),
+        _ => Err(type_error("a string", value)),
+    }
+}
+
+/// Los enteros se pasan a texto para validarlos igual que los de la linea de comandos.
+fn integer(value: &TomlValue) -> Result<String, String> {
+    match value {
+        TomlValue::Integer(integer) => Ok(integer.to_string()),
+        _ => Err(type_error("an integer", value)),
+    }
+}
+
+fn boolean(value: &TomlValue) -> Result<bool, String> {
+    match value {
+        TomlValue::Boolean(boolean) => Ok(*boolean),
+        _ => Err(type_error("a boolean", value)),
+    }
+}
+
+fn strings(value: &TomlValue) -> Result<Vec<String>, String> {
+    match value {
+        TomlValue::Array(values) => values.iter().map(string).collect(),
+        _ => Err(type_error("an array of strings", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        let (values, errors) = ConfigValues::from_args(&args(&[
+            "--config",
+            "node.toml",
+            "--node-key",
+            "3=tres",
+            "--quiet",
+            "--timeout",
+            "ok_acquire_ms=2000",
+            "6060",
+            "127.0.0.1:6061",
+            "--id",
+        ]));
+        assert_eq!(values.config_path, Some(String::from("node.toml")));
+        assert_eq!(values.node_keys, vec![String::from("3=tres")]);
+        assert_eq!(values.log_enabled, Some(false));
+        assert_eq!(values.timeouts, vec![String::from("ok_acquire_ms=2000")]);
+        assert_eq!(values.listen_address, Some(String::from("6060")));
+        assert_eq!(values.neighbors, Some(vec![String::from("127.0.0.1:6061")]));
+        assert_eq!(errors, vec![String::from("Missing value for --id")]);
+    }
+
+    #[test]
+    fn test_from_file() {
+        let config_file = ConfigFile::parse(
+            "[node]\nid = 1\npeers = [\"127.0.0.1:6061\"]\ncolor = \"azul\"\nauto_repair = 1\n\
+             [security.node_keys]\n3 = \"tres\"\n\
+             [timeouts]\nok_election_ms = 1_500\nheartbeat_ms = 100\n[retry]\nmax_attempts = 3\n",
+        )
+        .unwrap();
+        let (values, errors) = ConfigValues::from_file(&config_file);
+        assert_eq!(values.node_id, Some(String::from("1")));
+        assert_eq!(values.neighbors, Some(vec![String::from("127.0.0.1:6061")]));
+        assert_eq!(values.node_keys, vec![String::from("3=tres")]);
+        assert_eq!(values.timeouts, vec![String::from("ok_election_ms=1500")]);
+        assert_eq!(values.retry, vec![String::from("max_attempts=3")]);
+        assert_eq!(
+            errors,
+            vec![
+                String::from("line 4: node.color: unknown setting"),
+                String::from("line 5: node.auto_repair: expected a boolean, found an integer"),
+                String::from("line 10: timeouts.heartbeat_ms: unknown setting"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_args_override_file() {
+        let file = ConfigValues {
+            node_id: Some(String::from("1")),
+            priority: Some(String::from("2")),
+            node_keys: vec![String::from("3=tres")],
+            ..ConfigValues::default()
+        };
+        let args = ConfigValues {
+            node_id: Some(String::from("5")),
+            node_keys: vec![String::from("3=otra")],
+            ..ConfigValues::default()
+        };
+        let values = file.overridden_by(args);
+        assert_eq!(values.node_id, Some(String::from("5")));
+        assert_eq!(values.priority, Some(String::from("2")));
+        assert_eq!(
+            values.node_keys,
+            vec![String::from("3=tres"), String::from("3=otra")]
+        );
+    }
+}
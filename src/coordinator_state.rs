@@ -4,21 +4,21 @@ use std::{
 };
 pub struct CoordinatorState {
     pub lock_taken: Arc<Mutex<bool>>,
-    pub lock_owner_addr: Arc<Mutex<String>>,
+    pub lock_owner: Arc<Mutex<Option<u64>>>,
     pub got_release_confirmation: Arc<(Mutex<bool>, Condvar)>,
-    pub waiting_nodes_queue: VecDeque<String>,
+    pub waiting_nodes_queue: VecDeque<u64>,
 }
 
 impl CoordinatorState {
     #[allow(clippy::mutex_atomic)]
     pub fn new() -> CoordinatorState {
         let lock_taken = Arc::new(Mutex::new(false));
-        let lock_owner_addr = Arc::new(Mutex::new(String::new()));
+        let lock_owner = Arc::new(Mutex::new(None));
         let got_release_confirmation = Arc::new((Mutex::new(false), Condvar::new()));
         let waiting_nodes_queue = VecDeque::new();
         CoordinatorState {
             lock_taken,
-            lock_owner_addr,
+            lock_owner,
             got_release_confirmation,
             waiting_nodes_queue,
         }
@@ -28,19 +28,28 @@ impl CoordinatorState {
         *self.lock_taken.lock().unwrap()
     }
 
-    pub fn enqueue_requestor(&mut self, sender_addr: String) {
-        self.waiting_nodes_queue.push_back(sender_addr);
+    pub fn enqueue_requestor(&mut self, requestor_id: u64) {
+        self.waiting_nodes_queue.push_back(requestor_id);
     }
 
-    pub fn deque_requestor(&mut self) -> Option<String> {
+    pub fn deque_requestor(&mut self) -> Option<u64> {
         self.waiting_nodes_queue.pop_front()
     }
 
+    /// Vacia la cola de espera, devolviendo los IDs en orden.
+    pub fn take_waiting_nodes(&mut self) -> Vec<u64> {
+        self.waiting_nodes_queue.drain(..).collect()
+    }
+
     pub fn set_taken(&self, taken: bool) {
         *self.lock_taken.lock().unwrap() = taken;
     }
 
-    pub fn set_lock_owner_addr(&self, lock_owner_addr: String) {
-        *self.lock_owner_addr.lock().unwrap() = lock_owner_addr;
+    pub fn lock_owner(&self) -> Option<u64> {
+        *self.lock_owner.lock().unwrap()
+    }
+
+    pub fn set_lock_owner(&self, lock_owner: Option<u64>) {
+        *self.lock_owner.lock().unwrap() = lock_owner;
     }
 }
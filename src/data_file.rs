@@ -0,0 +1,96 @@
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Archivo de un nodo en su directorio de datos (`node-<id>.<extension>`). Se reemplaza
+/// escribiendo a un archivo temporal que despues se renombra, para no dejar a medias la version
+/// anterior si el nodo se cae mientras escribe.
+pub struct DataFile {
+    path: PathBuf,
+    /// Que guarda el archivo, para los mensajes de error.
+    description: &'static str,
+}
+
+impl DataFile {
+    pub fn open(
+        dir: &str,
+        node_id: u64,
+        extension: &str,
+        description: &'static str,
+    ) -> Result<DataFile, String> {
+        fs::create_dir_all(dir)
+            .map_err(|error| format!("Couldn't create data directory {:?}: {}", dir, error))?;
+        Ok(DataFile {
+            path: PathBuf::from(dir).join(format!("node-{}.{}", node_id, extension)),
+            description,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Contenido del archivo, o `None` si todavia no se escribio.
+    pub fn read(&self) -> Result<Option<String>, String> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => Ok(Some(content)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(format!(
+                "Couldn't read {} {:?}: {}",
+                self.description, self.path, error
+            )),
+        }
+    }
+
+    pub fn replace(&self, content: &str) -> Result<(), String> {
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, content)
+            .and_then(|_| fs::rename(&temporary, &self.path))
+            .map_err(|error| self.write_error(error))
+    }
+
+    /// Agrega `content` al final del archivo, que ya tiene que existir.
+    pub fn append(&self, content: &str) -> Result<(), String> {
+        OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|error| self.write_error(error))
+    }
+
+    fn write_error(&self, error: std::io::Error) -> String {
+        format!(
+            "Couldn't write {} {:?}: {}",
+            self.description, self.path, error
+        )
+    }
+}
+
+/// Ruta temporal (archivo o directorio) propia del test `name` y del proceso que corre los tests.
+#[cfg(test)]
+pub fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("{}-{}", name, std::process::id()))
+        .to_string_lossy()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_replace_and_append() {
+        let dir = temp_path("data-file");
+        let file = DataFile::open(&dir, 6060, "lines", "test file").unwrap();
+        assert_eq!(file.read(), Ok(None));
+        file.replace("a\n").unwrap();
+        file.append("b\n").unwrap();
+        assert_eq!(file.read(), Ok(Some(String::from("a\nb\n"))));
+        file.replace("c\n").unwrap();
+        assert_eq!(file.read(), Ok(Some(String::from("c\n"))));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,123 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+/// Puertos a los que se anuncia un nodo si no se indican otros.
+pub const DEFAULT_DISCOVERY_PORTS: (u16, u16) = (6060, 6069);
+
+/// Configuracion del descubrimiento de vecinos por broadcast (`--discover`). El nodo anuncia
+/// el nombre de su cluster a cada puerto del rango en `address`, y solo lo suman los nodos del
+/// mismo cluster.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiscoveryConfig {
+    pub cluster_name: String,
+    pub address: IpAddr,
+    pub ports: (u16, u16),
+}
+
+impl DiscoveryConfig {
+    /// Si no se indica a donde anunciarse se usa la misma direccion de loopback si el nodo escucha
+    /// en loopback (el broadcast no sale de un socket ligado a loopback) y si no el broadcast IPv4.
+    pub fn new(
+        cluster_name: String,
+        address: Option<IpAddr>,
+        ports: Option<(u16, u16)>,
+        listen_address: &SocketAddr,
+    ) -> Result<DiscoveryConfig, String> {
+        if cluster_name.is_empty()
+            || cluster_name
+                .chars()
+                .any(|character| character == ';' || character == ',' || character.is_whitespace())
+        {
+            return Err(format!("Invalid cluster name: {:?}", cluster_name));
+        }
+        let address = match address {
+            Some(address) => address,
+            None if listen_address.ip().is_loopback() => listen_address.ip(),
+            None if listen_address.is_ipv4() => IpAddr::V4(Ipv4Addr::BROADCAST),
+            None => {
+                return Err(String::from(
+                    "--discover on an IPv6 address requires --discovery-address",
+                ))
+            }
+        };
+        Ok(DiscoveryConfig {
+            cluster_name,
+            address,
+            ports: ports.unwrap_or(DEFAULT_DISCOVERY_PORTS),
+        })
+    }
+
+    /// Direcciones a las que se envia el anuncio, sin la propia.
+    pub fn targets(&self, own_address: &SocketAddr) -> Vec<SocketAddr> {
+        (self.ports.0..=self.ports.1)
+            .map(|port| SocketAddr::new(self.address, port))
+            .filter(|target| target != own_address)
+            .collect()
+    }
+}
+
+/// Interpreta un rango de puertos `<desde>-<hasta>`.
+pub fn parse_ports(value: &str) -> Result<(u16, u16), String> {
+    let invalid = || format!("Invalid port range (expected <from>-<to>): {:?}", value);
+    let (from, to) = value.split_once('-').ok_or_else(invalid)?;
+    match (from.parse::<u16>(), to.parse::<u16>()) {
+        (Ok(from), Ok(to)) if from <= to => Ok((from, to)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(address: &str) -> SocketAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn test_default_address_depends_on_listen_address() {
+        let loopback =
+            DiscoveryConfig::new(String::from("lab"), None, None, &address("127.0.0.1:6060"))
+                .unwrap();
+        assert_eq!(loopback.address, "127.0.0.1".parse::<IpAddr>().unwrap());
+        assert_eq!(loopback.ports, DEFAULT_DISCOVERY_PORTS);
+        let any = DiscoveryConfig::new(String::from("lab"), None, None, &address("0.0.0.0:6060"))
+            .unwrap();
+        assert_eq!(any.address, IpAddr::V4(Ipv4Addr::BROADCAST));
+        assert!(
+            DiscoveryConfig::new(String::from("lab"), None, None, &address("[::]:6060")).is_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_cluster_name() {
+        for name in ["", "a;b", "a b", "a,b"] {
+            assert!(
+                DiscoveryConfig::new(name.to_string(), None, None, &address("127.0.0.1:6060"))
+                    .is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn test_targets_skip_own_address() {
+        let config = DiscoveryConfig::new(
+            String::from("lab"),
+            None,
+            Some((6060, 6062)),
+            &address("127.0.0.1:6061"),
+        )
+        .unwrap();
+        assert_eq!(
+            config.targets(&address("127.0.0.1:6061")),
+            vec![address("127.0.0.1:6060"), address("127.0.0.1:6062")]
+        );
+    }
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports("6060-6069"), Ok((6060, 6069)));
+        assert!(parse_ports("6069-6060").is_err());
+        assert!(parse_ports("6060").is_err());
+        assert!(parse_ports("a-b").is_err());
+    }
+}
@@ -1,64 +1,101 @@
 use std::{
-    net::UdpSocket,
+    net::SocketAddr,
     sync::{Arc, Condvar, Mutex},
     time::Duration,
 };
 
-use crate::sender::send;
+use crate::messenger::Messenger;
+use crate::peer_status::PeerStatus;
 use crate::{acquire_message::AcquireMessage, blockchain_node::BlockchainNode, logger::log};
 
 pub struct DistMutex {
-    pub coordinator_addr: String,
-    pub socket_to_coordinator: UdpSocket,
+    pub coordinator_id: u64,
+    pub coordinator_addr: SocketAddr,
+    pub messenger: Messenger,
     pub got_acquire_confirmation: Arc<(Mutex<bool>, Condvar)>,
+    /// ID del coordinador al que se le envio el ACQUIRE que espera respuesta. Solo se acepta el
+    /// OK_ACQUIRE de ese nodo; si no hay ninguno pendiente, se ignora.
+    pub acquire_pending: Option<u64>,
+    /// Nodo que respondio el ACQUIRE pendiente con un term posterior antes de anunciarse como
+    /// coordinador. Su OK_ACQUIRE se toma si el anuncio llega mientras el ACQUIRE sigue pendiente.
+    pub early_grant: Option<u64>,
 }
 
 impl DistMutex {
     #[allow(clippy::mutex_atomic)]
-    pub fn new(coordinator_addr: String, socket_to_coordinator: UdpSocket) -> DistMutex {
+    pub fn new(
+        coordinator_id: u64,
+        coordinator_addr: SocketAddr,
+        messenger: Messenger,
+    ) -> DistMutex {
         let got_acquire_confirmation = Arc::new((Mutex::new(false), Condvar::new()));
         DistMutex {
+            coordinator_id,
             coordinator_addr,
-            socket_to_coordinator,
+            messenger,
             got_acquire_confirmation,
+            acquire_pending: None,
+            early_grant: None,
         }
     }
 
+    /// Pide el lock al coordinador y espera el OK_ACQUIRE, como mucho `timeout`.
     #[allow(clippy::mutex_atomic)]
-    pub fn acquire(blockchain_node: Arc<Mutex<BlockchainNode>>) -> Result<(), ()> {
+    pub fn acquire(
+        blockchain_node: Arc<Mutex<BlockchainNode>>,
+        timeout: Duration,
+    ) -> Result<(), ()> {
         {
-            let node = blockchain_node.lock().unwrap();
+            let mut node = blockchain_node.lock().unwrap();
+            *node.dist_mutex.got_acquire_confirmation.0.lock().unwrap() = false;
+            node.dist_mutex.acquire_pending = Some(node.dist_mutex.coordinator_id);
+            node.dist_mutex.early_grant = None;
             log(format!(
-                "Sending ACQUIRE to coordinator: {:?}",
-                node.dist_mutex.coordinator_addr
+                "Sending ACQUIRE to coordinator {} at {:?}",
+                node.dist_mutex.coordinator_id, node.dist_mutex.coordinator_addr
             ));
-            send(
-                node.dist_mutex.socket_to_coordinator.try_clone().unwrap(),
+            node.dist_mutex.messenger.send(
                 &AcquireMessage::Acquire.as_bytes(),
                 &node.dist_mutex.coordinator_addr,
             );
 
             log("Waiting for OK_ACQUIRE message".to_string());
         }
-        const OK_ACQUIRE_TIMEOUT: Duration = Duration::from_secs(5);
-
-        let _got_acquire_confirmation = {
-            blockchain_node
-                .lock()
-                .unwrap()
-                .dist_mutex
-                .got_acquire_confirmation
-                .clone()
+        let (_got_acquire_confirmation, clock, timeouts) = {
+            let node = blockchain_node.lock().unwrap();
+            (
+                node.dist_mutex.got_acquire_confirmation.clone(),
+                node.clock(),
+                node.timeouts(),
+            )
         };
 
-        let got_acquire_confirmation = _got_acquire_confirmation.1.wait_timeout_while(
-            _got_acquire_confirmation.0.lock().unwrap(),
-            OK_ACQUIRE_TIMEOUT,
-            |dont_got_it| !*dont_got_it,
-        );
-        if *got_acquire_confirmation.unwrap().0 {
-            log("Got OK_ACQUIRE message".to_string());
+        // Se espera de a intervalos cortos para abandonar apenas el detector de fallas
+        // de por caido al coordinador, sin esperar el timeout completo.
+        let deadline = clock.now() + timeout;
+        let mut got_it = false;
+        while clock.now() < deadline {
+            got_it = clock.wait_until(
+                &_got_acquire_confirmation,
+                true,
+                timeouts
+                    .heartbeat_interval
+                    .min(deadline.saturating_duration_since(clock.now())),
+            );
+            if got_it {
+                break;
+            }
             let node = blockchain_node.lock().unwrap();
+            if node.peer_status(&node.dist_mutex.coordinator_addr) == PeerStatus::Dead {
+                log("Coordinator is dead, giving up on OK_ACQUIRE".to_string());
+                break;
+            }
+        }
+        let mut node = blockchain_node.lock().unwrap();
+        node.dist_mutex.acquire_pending = None;
+        node.dist_mutex.early_grant = None;
+        if got_it {
+            log("Got OK_ACQUIRE message".to_string());
             *node.dist_mutex.got_acquire_confirmation.0.lock().unwrap() = false;
             Ok(())
         } else {
@@ -70,17 +107,38 @@ impl DistMutex {
 
     pub fn release(&mut self) {
         log(format!(
-            "Sending RELEASE to coordinator with addr: {:?}",
-            self.coordinator_addr
+            "Sending RELEASE to coordinator {} at {:?}",
+            self.coordinator_id, self.coordinator_addr
         ));
-        send(
-            self.socket_to_coordinator.try_clone().unwrap(),
-            &AcquireMessage::Release.as_bytes(),
-            &self.coordinator_addr.clone(),
-        );
+        self.messenger
+            .send(&AcquireMessage::Release.as_bytes(), &self.coordinator_addr);
+    }
+
+    /// Cambia el coordinador. Un ACQUIRE pendiente pasa a esperar la respuesta del nuevo, que
+    /// la da si recibio la cola del anterior (HANDOFF), y puede haberla dado ya.
+    pub fn set_coordinator(&mut self, coordinator_id: u64, coordinator_addr: SocketAddr) {
+        self.coordinator_id = coordinator_id;
+        self.coordinator_addr = coordinator_addr;
+        if self.acquire_pending.is_some() {
+            self.acquire_pending = Some(coordinator_id);
+            if self.early_grant.take() == Some(coordinator_id) {
+                log(format!(
+                    "Node {} announced itself as coordinator: taking its OK_ACQUIRE",
+                    coordinator_id
+                ));
+                self.grant();
+            }
+        }
+    }
+
+    /// Despierta al ACQUIRE pendiente con el lock concedido.
+    #[allow(clippy::mutex_atomic)]
+    pub fn grant(&self) {
+        *self.got_acquire_confirmation.0.lock().unwrap() = true;
+        self.got_acquire_confirmation.1.notify_all();
     }
 
-    pub fn is_coordinator(&self, addr: String) -> bool {
-        addr == *self.coordinator_addr
+    pub fn is_coordinator(&self, id: u64) -> bool {
+        id == self.coordinator_id
     }
 }
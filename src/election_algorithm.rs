@@ -0,0 +1,23 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::blockchain_node::BlockchainNode;
+use crate::node_identity::NodeIdentity;
+
+/// Algoritmo de eleccion de lider del nodo (`BullyElection` o `RingElection`). El resto del nodo
+/// solo inicia elecciones y le pasa los mensajes recibidos; el resultado queda en el nodo con
+/// `set_coordinator` o `make_leader`.
+pub trait ElectionAlgorithm: Send + Sync {
+    /// Inicia una eleccion y vuelve cuando el nodo conoce al nuevo coordinador (o se dejo de
+    /// esperarlo). Si ya hay una eleccion en curso en el nodo no hace nada.
+    fn begin_election(&self, node: Arc<Mutex<BlockchainNode>>);
+
+    /// Procesa un mensaje recibido. Devuelve `false` si no es un mensaje de este algoritmo.
+    fn process_message(
+        &self,
+        node: Arc<Mutex<BlockchainNode>>,
+        message: &str,
+        sender: &SocketAddr,
+        sender_identity: NodeIdentity,
+    ) -> bool;
+}
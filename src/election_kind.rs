@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use crate::bully_election::BullyElection;
+use crate::election_algorithm::ElectionAlgorithm;
+use crate::ring_election::RingElection;
+
+/// Algoritmos de eleccion de lider que se pueden elegir con `--election`.
+pub const ELECTION_ALGORITHMS: [&str; 2] = ["bully", "ring"];
+
+/// Algoritmo de eleccion configurado para el nodo.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ElectionKind {
+    #[default]
+    Bully,
+    Ring,
+}
+
+impl ElectionKind {
+    pub fn from_name(name: &str) -> Option<ElectionKind> {
+        match name {
+            "bully" => Some(ElectionKind::Bully),
+            "ring" => Some(ElectionKind::Ring),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ElectionKind::Bully => "bully",
+            ElectionKind::Ring => "ring",
+        }
+    }
+
+    pub fn algorithm(&self) -> Arc<dyn ElectionAlgorithm> {
+        match self {
+            ElectionKind::Bully => Arc::new(BullyElection::default()),
+            ElectionKind::Ring => Arc::new(RingElection),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        for name in ELECTION_ALGORITHMS {
+            assert_eq!(ElectionKind::from_name(name).unwrap().name(), name);
+        }
+        assert_eq!(ElectionKind::from_name("raft"), None);
+    }
+}
@@ -1,45 +1,51 @@
 /// Contiene los distintos tipos de mensajes durante el proceso de eleccion (algoritmo Bully)
 /// - **Election**: El nodo que envia este mensaje desea empezar un proceso de eleccion de lider.
+///   Lleva el term de la eleccion.
 /// - **OkElection**: El nodo que envia este mensaje es porque recibio un mensaje Election previamente, tiene mayor ID que quien le envia Election
-/// y continuará con el proceso de eleccion.
-/// - **Coordinator**: Un nodo envia este mensaje cuando detecta que debe ser el lider. Los que reciben estos mensajes actualizan su referencia al nuevo Lider
+///   y continuará con el proceso de eleccion.
+/// - **Coordinator**: Un nodo envia este mensaje cuando detecta que debe ser el lider. Los que reciben estos mensajes actualizan su referencia al nuevo Lider,
+///   salvo que el term del anuncio sea anterior al que ya conocen.
 ///
 /// |     Tipo      |  Representacion Binaria   |
 /// |---------------|------------------------   |
-/// |   Election    |            b'E'           |
+/// |   Election    |         b'E;<term>'       |
 /// |   OkElection  |            b'O'           |
-/// |   Coordinator |            b'C'           |
+/// |   Coordinator |         b'C;<term>'       |
 #[derive(PartialEq, Debug)]
 pub enum ElectionMessage {
-    Election,
+    Election(u64),
     OkElection,
-    Coordinator,
+    Coordinator(u64),
 }
 
 impl ElectionMessage {
     /// Devuelve la representacion binaria del mensaje para enviar por un puerto.
     /// ```rust
-    /// ElectionMessage::Election.as_bytes() // => b'E'
+    /// ElectionMessage::Election(3).as_bytes() // => b"E;3"
     /// ```
-    pub fn as_bytes(&self) -> [u8; 1] {
+    pub fn as_bytes(&self) -> Vec<u8> {
         match self {
-            ElectionMessage::Election => *b"E",
-            ElectionMessage::OkElection => *b"O",
-            ElectionMessage::Coordinator => *b"C",
+            ElectionMessage::Election(term) => format!("E;{}", term).into_bytes(),
+            ElectionMessage::OkElection => b"O".to_vec(),
+            ElectionMessage::Coordinator(term) => format!("C;{}", term).into_bytes(),
         }
     }
 
-    /// Recibe un caracter binario. Devuelve el tipo de mensaje que corresponde a esa representacion binaria.
+    /// Recibe un mensaje binario. Devuelve el tipo de mensaje que corresponde a esa representacion binaria.
     ///```rust
-    ///ElectionMessage::from_bytes(b'C'); // => Some(ElectionMessage::Coordinator)
+    ///ElectionMessage::from_bytes(b"C;3"); // => Some(ElectionMessage::Coordinator(3))
     ///ElectionMessage::from_bytes(b"Whatever"); // => None
     ///```
-
     pub fn from_bytes(bytes: &[u8]) -> Option<ElectionMessage> {
-        match bytes {
-            b"E" => Some(ElectionMessage::Election),
-            b"O" => Some(ElectionMessage::OkElection),
-            b"C" => Some(ElectionMessage::Coordinator),
+        if bytes == b"O" {
+            return Some(ElectionMessage::OkElection);
+        }
+        let string = std::str::from_utf8(bytes).ok()?;
+        let (kind, term) = string.split_once(';')?;
+        let term = term.parse::<u64>().ok()?;
+        match kind {
+            "E" => Some(ElectionMessage::Election(term)),
+            "C" => Some(ElectionMessage::Coordinator(term)),
             _ => None,
         }
     }
@@ -51,24 +57,34 @@ mod tests {
 
     #[test]
     fn test_as_bytes() {
-        assert_eq!([b'E'], ElectionMessage::Election.as_bytes());
-        assert_eq!([b'O'], ElectionMessage::OkElection.as_bytes());
-        assert_eq!([b'C'], ElectionMessage::Coordinator.as_bytes());
+        assert_eq!(b"E;1".to_vec(), ElectionMessage::Election(1).as_bytes());
+        assert_eq!(b"O".to_vec(), ElectionMessage::OkElection.as_bytes());
+        assert_eq!(
+            b"C;12".to_vec(),
+            ElectionMessage::Coordinator(12).as_bytes()
+        );
     }
 
     #[test]
     fn from_bytes() {
         assert_eq!(
-            Some(ElectionMessage::Election),
-            ElectionMessage::from_bytes(&[b'E'])
+            Some(ElectionMessage::Election(1)),
+            ElectionMessage::from_bytes(b"E;1")
         );
         assert_eq!(
             Some(ElectionMessage::OkElection),
-            ElectionMessage::from_bytes(&[b'O'])
+            ElectionMessage::from_bytes(b"O")
         );
         assert_eq!(
-            Some(ElectionMessage::Coordinator),
-            ElectionMessage::from_bytes(&[b'C'])
+            Some(ElectionMessage::Coordinator(12)),
+            ElectionMessage::from_bytes(b"C;12")
         );
     }
+
+    #[test]
+    fn test_none_from_bytes() {
+        for bytes in [&b"E"[..], b"C", b"C;", b"C;x", b"O;1", b"X;1"] {
+            assert_eq!(None, ElectionMessage::from_bytes(bytes));
+        }
+    }
 }
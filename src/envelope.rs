@@ -0,0 +1,151 @@
+use crate::message_nonce::MessageNonce;
+use crate::node_identity::NodeIdentity;
+
+/// Sobre con el que viaja todo mensaje entre nodos. Anuncia la identidad del emisor
+/// para no depender de la direccion desde la que llega el datagrama y, si el cluster
+/// usa claves precompartidas, lleva el MAC y el nonce del mensaje. Si el cluster cifra sus
+/// mensajes, el mensaje viaja cifrado (en hexadecimal).
+///
+/// Representacion: `<id>,<prioridad>[,<mac>[,<sesion>,<contador>]]|<mensaje>`.
+/// Ejemplo: `3,0,9f86d081884c7d659a2feaa0c55ad015|ACQUI`
+/// El MAC se calcula sobre `<id>,<prioridad>[,<sesion>,<contador>]|<mensaje>`.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Envelope {
+    pub sender: NodeIdentity,
+    pub payload: String,
+    pub mac: Option<String>,
+    pub nonce: Option<MessageNonce>,
+}
+
+impl Envelope {
+    pub fn new(sender: NodeIdentity, payload: String) -> Envelope {
+        Envelope {
+            sender,
+            payload,
+            mac: None,
+            nonce: None,
+        }
+    }
+
+    /// Contenido autenticado por el MAC.
+    pub fn signed_content(&self) -> String {
+        match &self.nonce {
+            Some(nonce) => format!(
+                "{},{},{},{}|{}",
+                self.sender.id, self.sender.priority, nonce.session, nonce.counter, self.payload
+            ),
+            None => format!(
+                "{},{}|{}",
+                self.sender.id, self.sender.priority, self.payload
+            ),
+        }
+    }
+
+    pub fn as_string(&self) -> String {
+        match (&self.mac, &self.nonce) {
+            (Some(mac), Some(nonce)) => format!(
+                "{},{},{},{},{}|{}",
+                self.sender.id,
+                self.sender.priority,
+                mac,
+                nonce.session,
+                nonce.counter,
+                self.payload
+            ),
+            (Some(mac), None) => format!(
+                "{},{},{}|{}",
+                self.sender.id, self.sender.priority, mac, self.payload
+            ),
+            _ => format!(
+                "{},{}|{}",
+                self.sender.id, self.sender.priority, self.payload
+            ),
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<Envelope> {
+        let (header, payload) = string.split_once('|')?;
+        let fields = header.split(',').collect::<Vec<&str>>();
+        let (mac, nonce) = match fields.len() {
+            2 => (None, None),
+            3 => (Some(fields[2].to_string()), None),
+            5 => (
+                Some(fields[2].to_string()),
+                Some(MessageNonce {
+                    session: fields[3].parse().ok()?,
+                    counter: fields[4].parse().ok()?,
+                }),
+            ),
+            _ => return None,
+        };
+        Some(Envelope {
+            sender: NodeIdentity::new(fields[0].parse().ok()?, fields[1].parse().ok()?),
+            payload: payload.to_string(),
+            mac,
+            nonce,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!(
+            "3,1|ACQUI",
+            Envelope::new(NodeIdentity::new(3, 1), String::from("ACQUI")).as_string()
+        );
+    }
+
+    #[test]
+    fn test_as_string_with_mac() {
+        let mut envelope = Envelope::new(NodeIdentity::new(3, 1), String::from("ACQUI"));
+        envelope.mac = Some(String::from("abcd"));
+        assert_eq!("3,1,abcd|ACQUI", envelope.as_string());
+        assert_eq!("3,1|ACQUI", envelope.signed_content());
+    }
+
+    #[test]
+    fn test_from_string() {
+        assert_eq!(
+            Envelope::from_string("3,1|GRADE_TO_COORDINATOR;juan;8"),
+            Some(Envelope::new(
+                NodeIdentity::new(3, 1),
+                String::from("GRADE_TO_COORDINATOR;juan;8")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_from_string_with_mac() {
+        let envelope = Envelope::from_string("3,1,abcd|ACQUI").unwrap();
+        assert_eq!(envelope.mac, Some(String::from("abcd")));
+        assert_eq!(envelope.payload, "ACQUI");
+    }
+
+    #[test]
+    fn test_nonce_round_trip() {
+        let mut envelope = Envelope::new(NodeIdentity::new(3, 1), String::from("a1b2"));
+        envelope.mac = Some(String::from("abcd"));
+        envelope.nonce = Some(MessageNonce {
+            session: 1000,
+            counter: 7,
+        });
+        assert_eq!("3,1,abcd,1000,7|a1b2", envelope.as_string());
+        assert_eq!("3,1,1000,7|a1b2", envelope.signed_content());
+        assert_eq!(
+            Envelope::from_string("3,1,abcd,1000,7|a1b2"),
+            Some(envelope)
+        );
+    }
+
+    #[test]
+    fn test_none_from_string() {
+        assert_eq!(Envelope::from_string("ACQUI"), None);
+        assert_eq!(Envelope::from_string("x,1|ACQUI"), None);
+        assert_eq!(Envelope::from_string("3|ACQUI"), None);
+        assert_eq!(Envelope::from_string("3,1,a,b|ACQUI"), None);
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::peer_stats::PeerStats;
+use crate::peer_status::PeerStatus;
+
+/// Detector de fallas por timeout. Lleva las estadisticas de heartbeats de cada vecino
+/// y a partir del tiempo desde el ultimo mensaje recibido decide si esta vivo, sospechado o caido.
+/// Un vecino del que nunca se recibio nada se mide desde la creacion del detector.
+pub struct FailureDetector {
+    peers: HashMap<SocketAddr, PeerStats>,
+    suspect_after: Duration,
+    dead_after: Duration,
+    started_at: Instant,
+    next_seq: u64,
+}
+
+impl FailureDetector {
+    pub fn new(suspect_after: Duration, dead_after: Duration, now: Instant) -> FailureDetector {
+        FailureDetector {
+            peers: HashMap::new(),
+            suspect_after,
+            dead_after,
+            started_at: now,
+            next_seq: 0,
+        }
+    }
+
+    /// Registra el envio de un Ping al vecino y devuelve el numero de secuencia a usar.
+    pub fn register_ping(&mut self, addr: &SocketAddr, now: Instant) -> u64 {
+        self.next_seq += 1;
+        let seq = self.next_seq;
+        self.stats_mut(addr).record_ping(seq, now);
+        seq
+    }
+
+    pub fn record_pong(&mut self, addr: &SocketAddr, seq: u64, now: Instant) {
+        self.stats_mut(addr).record_pong(seq, now);
+    }
+
+    /// Cualquier mensaje recibido de un vecino es prueba de que esta vivo.
+    pub fn record_heard(&mut self, addr: &SocketAddr, now: Instant) {
+        self.stats_mut(addr).record_heard(now);
+    }
+
+    pub fn status(&self, addr: &SocketAddr, now: Instant) -> PeerStatus {
+        let last_heard = self
+            .peers
+            .get(addr)
+            .and_then(|stats| stats.last_heard)
+            .unwrap_or(self.started_at);
+        let silence = now.saturating_duration_since(last_heard);
+        if silence >= self.dead_after {
+            PeerStatus::Dead
+        } else if silence >= self.suspect_after {
+            PeerStatus::Suspected
+        } else {
+            PeerStatus::Alive
+        }
+    }
+
+    pub fn stats(&self, addr: &SocketAddr) -> Option<&PeerStats> {
+        self.peers.get(addr)
+    }
+
+    fn stats_mut(&mut self, addr: &SocketAddr) -> &mut PeerStats {
+        self.peers.entry(*addr).or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> SocketAddr {
+        "127.0.0.1:6060".parse().unwrap()
+    }
+
+    fn detector(start: Instant) -> FailureDetector {
+        FailureDetector::new(Duration::from_secs(1), Duration::from_secs(3), start)
+    }
+
+    #[test]
+    fn test_unknown_peer_gets_a_grace_period() {
+        let start = Instant::now();
+        let detector = detector(start);
+        assert_eq!(detector.status(&peer(), start), PeerStatus::Alive);
+        assert_eq!(
+            detector.status(&peer(), start + Duration::from_secs(4)),
+            PeerStatus::Dead
+        );
+    }
+
+    #[test]
+    fn test_status_depends_on_silence() {
+        let start = Instant::now();
+        let mut detector = detector(start);
+        let heard_at = start + Duration::from_secs(10);
+        detector.record_heard(&peer(), heard_at);
+
+        assert_eq!(
+            detector.status(&peer(), heard_at + Duration::from_millis(500)),
+            PeerStatus::Alive
+        );
+        assert_eq!(
+            detector.status(&peer(), heard_at + Duration::from_secs(2)),
+            PeerStatus::Suspected
+        );
+        assert_eq!(
+            detector.status(&peer(), heard_at + Duration::from_secs(3)),
+            PeerStatus::Dead
+        );
+    }
+
+    #[test]
+    fn test_pong_revives_peer_and_measures_rtt() {
+        let start = Instant::now();
+        let mut detector = detector(start);
+        let sent_at = start + Duration::from_secs(5);
+        let seq = detector.register_ping(&peer(), sent_at);
+        detector.record_pong(&peer(), seq, sent_at + Duration::from_millis(4));
+
+        assert_eq!(
+            detector.status(&peer(), sent_at + Duration::from_millis(4)),
+            PeerStatus::Alive
+        );
+        assert_eq!(
+            detector.stats(&peer()).unwrap().last_rtt,
+            Some(Duration::from_millis(4))
+        );
+    }
+}
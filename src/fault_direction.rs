@@ -0,0 +1,37 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+
+/// Camino en el que se inyecta una falla.
+/// - **Send**: mensajes que envia el nodo.
+/// - **Receive**: mensajes que recibe el nodo, antes de procesarlos.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub enum FaultDirection {
+    Send,
+    Receive,
+}
+
+impl FromStr for FaultDirection {
+    type Err = String;
+
+    fn from_str(direction: &str) -> Result<FaultDirection, String> {
+        match direction {
+            "send" => Ok(FaultDirection::Send),
+            "recv" => Ok(FaultDirection::Receive),
+            _ => Err(format!(
+                "Invalid direction {:?}. Use send or recv",
+                direction
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FaultDirection {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let direction = match self {
+            FaultDirection::Send => "send",
+            FaultDirection::Receive => "recv",
+        };
+        write!(f, "{}", direction)
+    }
+}
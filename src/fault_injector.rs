@@ -0,0 +1,243 @@
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::fault_direction::FaultDirection;
+use crate::fault_rule::FaultRule;
+use crate::xorshift::Xorshift;
+
+/// Capa de inyeccion de fallas en el envio y la recepcion de mensajes, para poder provocar
+/// datos inconsistentes y probar su deteccion y recuperacion. A cada mensaje se le aplica la
+/// primera regla que coincida con su camino, vecino y tipo; sin reglas no se altera nada.
+/// Ademas permite simular una particion de red: todo el trafico con los vecinos particionados
+/// se descarta en ambos sentidos.
+pub struct FaultInjector {
+    rules: Vec<(FaultRule, u64)>,
+    partitioned: HashSet<SocketAddr>,
+    held: HashMap<(FaultDirection, SocketAddr), String>,
+    rng: Xorshift,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub delayed: u64,
+    pub reordered: u64,
+    pub partition_drops: u64,
+}
+
+/// Tipo de un mensaje: lo que esta antes del primer `;` o `:`.
+pub fn message_kind(payload: &str) -> &str {
+    payload.split([';', ':']).next().unwrap_or(payload)
+}
+
+impl FaultInjector {
+    pub fn new(rng: Xorshift) -> FaultInjector {
+        FaultInjector {
+            rules: Vec::new(),
+            partitioned: HashSet::new(),
+            held: HashMap::new(),
+            rng,
+            dropped: 0,
+            duplicated: 0,
+            delayed: 0,
+            reordered: 0,
+            partition_drops: 0,
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: FaultRule) {
+        self.rules.push((rule, 0));
+    }
+
+    pub fn rules(&self) -> Vec<&FaultRule> {
+        self.rules.iter().map(|(rule, _)| rule).collect()
+    }
+
+    /// Borra todas las reglas. Los mensajes retenidos para reordenar se descartan.
+    pub fn clear(&mut self) {
+        self.rules.clear();
+        self.dropped += self.held.len() as u64;
+        self.held.clear();
+    }
+
+    /// Corta el trafico en ambos sentidos con los vecinos indicados.
+    pub fn partition(&mut self, peers: &[SocketAddr]) {
+        self.partitioned.extend(peers);
+    }
+
+    /// Vuelve a conectar al vecino indicado, o a todos si no se indica ninguno.
+    pub fn heal(&mut self, peer: Option<&SocketAddr>) {
+        match peer {
+            Some(peer) => {
+                self.partitioned.remove(peer);
+            }
+            None => self.partitioned.clear(),
+        }
+    }
+
+    /// Vecinos con los que hay una particion activa, ordenados.
+    pub fn partitioned_peers(&self) -> Vec<SocketAddr> {
+        let mut peers = self
+            .partitioned
+            .iter()
+            .copied()
+            .collect::<Vec<SocketAddr>>();
+        peers.sort();
+        peers
+    }
+
+    /// Indica si hay que descartar el trafico con el vecino por estar particionado.
+    pub fn blocks(&mut self, peer: &SocketAddr) -> bool {
+        let blocked = self.partitioned.contains(peer);
+        if blocked {
+            self.partition_drops += 1;
+        }
+        blocked
+    }
+
+    /// Decide que hacer con un mensaje. Devuelve los mensajes a entregar (ninguno, el mismo,
+    /// duplicado o junto con uno retenido antes) y la demora con la que hay que entregarlos.
+    pub fn apply(
+        &mut self,
+        direction: FaultDirection,
+        peer: &SocketAddr,
+        payload: String,
+    ) -> Vec<(Duration, String)> {
+        if self.blocks(peer) {
+            return Vec::new();
+        }
+        let kind = message_kind(&payload).to_string();
+        let (rule, matched) = match self
+            .rules
+            .iter_mut()
+            .find(|(rule, _)| rule.matches(direction, peer, &kind))
+        {
+            Some((rule, matched)) => {
+                *matched += 1;
+                (rule.clone(), *matched)
+            }
+            None => return vec![(Duration::ZERO, payload)],
+        };
+        if (rule.skip_every > 0 && matched % rule.skip_every == 0)
+            || self.rng.below(100) < rule.drop_percent
+        {
+            self.dropped += 1;
+            return Vec::new();
+        }
+        let mut messages = vec![payload.clone()];
+        if self.rng.below(100) < rule.duplicate_percent {
+            self.duplicated += 1;
+            messages.push(payload.clone());
+        }
+        if rule.reorder {
+            match self.held.remove(&(direction, *peer)) {
+                Some(held) => {
+                    self.reordered += 1;
+                    messages.push(held);
+                }
+                None => {
+                    self.held.insert((direction, *peer), payload);
+                    return Vec::new();
+                }
+            }
+        }
+        if !rule.delay.is_zero() {
+            self.delayed += messages.len() as u64;
+        }
+        messages
+            .into_iter()
+            .map(|message| (rule.delay, message))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn injector(rule: &[&str]) -> FaultInjector {
+        let mut injector = FaultInjector::new(Xorshift::new(1));
+        injector.add_rule(FaultRule::from_args(rule).unwrap());
+        injector
+    }
+
+    fn send(injector: &mut FaultInjector, port: u16, payload: &str) -> Vec<(Duration, String)> {
+        injector.apply(FaultDirection::Send, &peer(port), String::from(payload))
+    }
+
+    #[test]
+    fn test_message_kind() {
+        assert_eq!(message_kind("PING;3"), "PING");
+        assert_eq!(message_kind("Range:0:a,1,2"), "Range");
+        assert_eq!(message_kind("ACQUI"), "ACQUI");
+    }
+
+    #[test]
+    fn test_without_rules_messages_pass() {
+        let mut injector = FaultInjector::new(Xorshift::new(1));
+        assert_eq!(
+            send(&mut injector, 1, "PING;1"),
+            vec![(Duration::ZERO, String::from("PING;1"))]
+        );
+    }
+
+    #[test]
+    fn test_skip_every_nth_matching_message() {
+        let mut injector = injector(&["send", "127.0.0.1:1", "GRADE_FROM_COORDINATOR", "skip=2"]);
+        assert_eq!(send(&mut injector, 1, "GRADE_FROM_COORDINATOR;0").len(), 1);
+        assert_eq!(send(&mut injector, 1, "PING;1").len(), 1);
+        assert_eq!(send(&mut injector, 2, "GRADE_FROM_COORDINATOR;1").len(), 1);
+        assert_eq!(send(&mut injector, 1, "GRADE_FROM_COORDINATOR;1").len(), 0);
+        assert_eq!(send(&mut injector, 1, "GRADE_FROM_COORDINATOR;2").len(), 1);
+        assert_eq!(injector.dropped, 1);
+    }
+
+    #[test]
+    fn test_drop_all_and_duplicate_all() {
+        let mut dropper = injector(&["send", "*", "*", "drop=100"]);
+        assert!(send(&mut dropper, 1, "PING;1").is_empty());
+        let mut duplicator = injector(&["send", "*", "*", "dup=100", "delay=10"]);
+        assert_eq!(
+            send(&mut duplicator, 1, "PING;1"),
+            vec![
+                (Duration::from_millis(10), String::from("PING;1")),
+                (Duration::from_millis(10), String::from("PING;1"))
+            ]
+        );
+        assert_eq!(duplicator.duplicated, 1);
+    }
+
+    #[test]
+    fn test_partition_and_heal() {
+        let mut injector = FaultInjector::new(Xorshift::new(1));
+        injector.partition(&[peer(1), peer(2)]);
+        assert!(send(&mut injector, 1, "PING;1").is_empty());
+        assert!(injector.blocks(&peer(2)));
+        assert_eq!(send(&mut injector, 3, "PING;1").len(), 1);
+        injector.heal(Some(&peer(1)));
+        assert_eq!(send(&mut injector, 1, "PING;1").len(), 1);
+        assert_eq!(injector.partitioned_peers(), vec![peer(2)]);
+        injector.heal(None);
+        assert!(!injector.blocks(&peer(2)));
+        assert_eq!(injector.partition_drops, 2);
+    }
+
+    #[test]
+    fn test_reorder_swaps_consecutive_messages() {
+        let mut injector = injector(&["send", "*", "*", "reorder"]);
+        assert!(send(&mut injector, 1, "A;1").is_empty());
+        assert_eq!(
+            send(&mut injector, 1, "A;2"),
+            vec![
+                (Duration::ZERO, String::from("A;2")),
+                (Duration::ZERO, String::from("A;1"))
+            ]
+        );
+        assert!(send(&mut injector, 1, "A;3").is_empty());
+        injector.clear();
+        assert_eq!(injector.dropped, 1);
+        assert_eq!(send(&mut injector, 1, "A;4").len(), 1);
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use crate::fault_direction::FaultDirection;
+use crate::ip_parser;
+
+/// Regla de inyeccion de fallas. Aplica a los mensajes de un camino (envio o recepcion), de un
+/// vecino (o de todos) y de un tipo (o de todos). El tipo de un mensaje es lo que esta antes del
+/// primer `;` o `:` (`PING`, `GRADE_FROM_COORDINATOR`, `Range`, `ACQUI`, `E`, ...).
+///
+/// Se arma a partir del comando
+/// `fault <send|recv> <vecino|*> <tipo|*> [drop=<%>] [skip=<n>] [delay=<ms>] [dup=<%>] [reorder]`:
+/// - **drop**: porcentaje de mensajes que se descartan al azar.
+/// - **skip**: se descarta uno de cada `n` mensajes, de forma deterministica.
+/// - **delay**: demora en milisegundos antes de enviar o procesar el mensaje.
+/// - **dup**: porcentaje de mensajes que se entregan dos veces.
+/// - **reorder**: cada mensaje se retiene hasta el siguiente, que se entrega antes que el.
+#[derive(PartialEq, Debug, Clone)]
+pub struct FaultRule {
+    pub direction: FaultDirection,
+    pub peer: Option<SocketAddr>,
+    pub kind: Option<String>,
+    pub drop_percent: u64,
+    pub skip_every: u64,
+    pub delay: Duration,
+    pub duplicate_percent: u64,
+    pub reorder: bool,
+}
+
+impl FaultRule {
+    pub fn from_args(args: &[&str]) -> Result<FaultRule, String> {
+        if args.len() < 3 {
+            return Err(String::from(
+                "Usage: fault <send|recv> <peer|*> <kind|*> [drop=<%>] [skip=<n>] [delay=<ms>] [dup=<%>] [reorder]",
+            ));
+        }
+        let direction = args[0].parse::<FaultDirection>()?;
+        let peer = match args[1] {
+            "*" => None,
+            peer => Some(ip_parser::canonical(
+                peer.parse::<SocketAddr>()
+                    .map_err(|_error| format!("Invalid peer address: {:?}", peer))?,
+            )),
+        };
+        let kind = match args[2] {
+            "*" => None,
+            kind => Some(kind.to_string()),
+        };
+        let mut rule = FaultRule {
+            direction,
+            peer,
+            kind,
+            drop_percent: 0,
+            skip_every: 0,
+            delay: Duration::ZERO,
+            duplicate_percent: 0,
+            reorder: false,
+        };
+        for option in &args[3..] {
+            match option.split_once('=') {
+                Some(("drop", value)) => rule.drop_percent = parse_percent("drop", value)?,
+                Some(("skip", value)) => rule.skip_every = parse_number("skip", value)?,
+                Some(("delay", value)) => {
+                    rule.delay = Duration::from_millis(parse_number("delay", value)?)
+                }
+                Some(("dup", value)) => rule.duplicate_percent = parse_percent("dup", value)?,
+                None if *option == "reorder" => rule.reorder = true,
+                _ => return Err(format!("Unknown fault option: {:?}", option)),
+            }
+        }
+        Ok(rule)
+    }
+
+    pub fn matches(&self, direction: FaultDirection, peer: &SocketAddr, kind: &str) -> bool {
+        self.direction == direction
+            && self.peer.is_none_or(|rule_peer| rule_peer == *peer)
+            && self.kind.as_ref().is_none_or(|rule_kind| rule_kind == kind)
+    }
+}
+
+fn parse_number(option: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse::<u64>()
+        .map_err(|_error| format!("Invalid value for {}: {:?}", option, value))
+}
+
+fn parse_percent(option: &str, value: &str) -> Result<u64, String> {
+    match parse_number(option, value)? {
+        percent if percent <= 100 => Ok(percent),
+        _ => Err(format!("{} must be between 0 and 100", option)),
+    }
+}
+
+impl fmt::Display for FaultRule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let peer = self.peer.map_or(String::from("*"), |peer| peer.to_string());
+        write!(
+            f,
+            "{} {} {} drop={} skip={} delay={} dup={}",
+            self.direction,
+            peer,
+            self.kind.as_deref().unwrap_or("*"),
+            self.drop_percent,
+            self.skip_every,
+            self.delay.as_millis(),
+            self.duplicate_percent
+        )?;
+        if self.reorder {
+            write!(f, " reorder")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer() -> SocketAddr {
+        "127.0.0.1:3030".parse().unwrap()
+    }
+
+    #[test]
+    fn test_from_args() {
+        let rule = FaultRule::from_args(&[
+            "send",
+            "127.0.0.1:3030",
+            "GRADE_FROM_COORDINATOR",
+            "skip=3",
+            "delay=200",
+            "reorder",
+        ])
+        .unwrap();
+        assert_eq!(
+            rule,
+            FaultRule {
+                direction: FaultDirection::Send,
+                peer: Some(peer()),
+                kind: Some(String::from("GRADE_FROM_COORDINATOR")),
+                drop_percent: 0,
+                skip_every: 3,
+                delay: Duration::from_millis(200),
+                duplicate_percent: 0,
+                reorder: true,
+            }
+        );
+        assert_eq!(
+            rule.to_string(),
+            "send 127.0.0.1:3030 GRADE_FROM_COORDINATOR drop=0 skip=3 delay=200 dup=0 reorder"
+        );
+    }
+
+    #[test]
+    fn test_from_args_errors() {
+        assert!(FaultRule::from_args(&["send", "*"]).is_err());
+        assert!(FaultRule::from_args(&["sideways", "*", "*"]).is_err());
+        assert!(FaultRule::from_args(&["send", "nowhere", "*"]).is_err());
+        assert!(FaultRule::from_args(&["send", "*", "*", "drop=101"]).is_err());
+        assert!(FaultRule::from_args(&["send", "*", "*", "explode"]).is_err());
+    }
+
+    #[test]
+    fn test_matches() {
+        let rule = FaultRule::from_args(&["recv", "*", "PING", "drop=50"]).unwrap();
+        assert!(rule.matches(FaultDirection::Receive, &peer(), "PING"));
+        assert!(!rule.matches(FaultDirection::Send, &peer(), "PING"));
+        assert!(!rule.matches(FaultDirection::Receive, &peer(), "PONG"));
+    }
+}
@@ -0,0 +1,78 @@
+/// Mensajes del protocolo de heartbeats entre vecinos.
+/// - **Ping**: Un nodo consulta si su vecino sigue vivo. Lleva un numero de secuencia.
+/// - **Pong**: Respuesta a un Ping, devolviendo el mismo numero de secuencia para poder medir el RTT.
+/// - **Leader**: El coordinador avisa periodicamente que sigue vivo. Lleva su term.
+///
+/// |     Tipo      |  Representacion   |
+/// |---------------|-------------------|
+/// |   Ping        |   PING;<seq>      |
+/// |   Pong        |   PONG;<seq>      |
+/// |   Leader      |   LEADER;<term>   |
+#[derive(PartialEq, Debug)]
+pub enum HeartbeatMessage {
+    Ping(u64),
+    Pong(u64),
+    Leader(u64),
+}
+
+impl HeartbeatMessage {
+    pub fn as_string(&self) -> String {
+        match self {
+            HeartbeatMessage::Ping(seq) => format!("PING;{}", seq),
+            HeartbeatMessage::Pong(seq) => format!("PONG;{}", seq),
+            HeartbeatMessage::Leader(term) => format!("LEADER;{}", term),
+        }
+    }
+
+    pub fn from_string(string: String) -> Option<HeartbeatMessage> {
+        let tokens = string.split(';').collect::<Vec<&str>>();
+        if tokens.len() != 2 {
+            return None;
+        }
+        let number = tokens[1].parse::<u64>().ok()?;
+        match tokens[0] {
+            "PING" => Some(HeartbeatMessage::Ping(number)),
+            "PONG" => Some(HeartbeatMessage::Pong(number)),
+            "LEADER" => Some(HeartbeatMessage::Leader(number)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!("PING;7", HeartbeatMessage::Ping(7).as_string());
+        assert_eq!("PONG;42", HeartbeatMessage::Pong(42).as_string());
+        assert_eq!("LEADER;3", HeartbeatMessage::Leader(3).as_string());
+    }
+
+    #[test]
+    fn test_from_string() {
+        assert_eq!(
+            HeartbeatMessage::from_string(String::from("PING;7")),
+            Some(HeartbeatMessage::Ping(7))
+        );
+        assert_eq!(
+            HeartbeatMessage::from_string(String::from("PONG;42")),
+            Some(HeartbeatMessage::Pong(42))
+        );
+        assert_eq!(
+            HeartbeatMessage::from_string(String::from("LEADER;3")),
+            Some(HeartbeatMessage::Leader(3))
+        );
+    }
+
+    #[test]
+    fn test_none_from_string() {
+        assert_eq!(HeartbeatMessage::from_string(String::from("PING")), None);
+        assert_eq!(HeartbeatMessage::from_string(String::from("PING;x")), None);
+        assert_eq!(
+            HeartbeatMessage::from_string(String::from("GRADE_TO_COORDINATOR;a;1")),
+            None
+        );
+    }
+}
@@ -1,11 +1,95 @@
-pub fn local_address_with_port(port: &str) -> String {
-    "127.0.0.1:".to_owned() + port
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// Interpreta la direccion de escucha del nodo. Acepta solo un puerto (se escucha en 127.0.0.1),
+/// una direccion IPv4 o IPv6 con puerto (`0.0.0.0:6060`, `[::]:6060`) o un hostname con puerto.
+pub fn parse_listen_address(address: &str) -> Result<SocketAddr, String> {
+    if let Ok(port) = address.parse::<u16>() {
+        return Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port));
+    }
+    if let Ok(socket_addr) = address.parse::<SocketAddr>() {
+        return Ok(socket_addr);
+    }
+    resolve(address, None)
+}
+
+/// Resuelve la direccion de un vecino (`ip:puerto`, `[ipv6]:puerto` o `hostname:puerto`).
+/// Si el hostname resuelve a varias direcciones se prefiere la de la misma familia que `local`.
+pub fn resolve_neighbor(address: &str, local: &SocketAddr) -> Result<SocketAddr, String> {
+    resolve(address, Some(local)).map(canonical)
+}
+
+fn resolve(address: &str, local: Option<&SocketAddr>) -> Result<SocketAddr, String> {
+    let candidates = address
+        .to_socket_addrs()
+        .map_err(|error| format!("Couldn't resolve {:?}: {}", address, error))?
+        .collect::<Vec<SocketAddr>>();
+    let preferred = local.and_then(|local| {
+        candidates
+            .iter()
+            .find(|candidate| candidate.is_ipv4() == local.is_ipv4())
+    });
+    preferred
+        .or_else(|| candidates.first())
+        .copied()
+        .ok_or_else(|| format!("{:?} did not resolve to any address", address))
+}
+
+/// Forma canonica de una direccion para identificar a un nodo: las direcciones IPv4 mapeadas
+/// en IPv6 (`[::ffff:127.0.0.1]:6060`) se convierten a IPv4 (`127.0.0.1:6060`).
+pub fn canonical(address: SocketAddr) -> SocketAddr {
+    SocketAddr::new(address.ip().to_canonical(), address.port())
+}
+
+/// Direccion a usar para enviar a `address` desde un socket escuchando en `local`.
+/// Un socket IPv6 solo puede enviar a direcciones IPv4 si estan mapeadas en IPv6.
+pub fn for_socket(address: &SocketAddr, local: &SocketAddr) -> SocketAddr {
+    match (address.ip(), local) {
+        (IpAddr::V4(ip), SocketAddr::V6(_)) => {
+            SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), address.port())
+        }
+        _ => *address,
+    }
 }
 
-pub fn get_port_from_dir(dir: &str) -> Option<usize> {
-    dir.split(':')
-        .last()
-        .map(|port| port.parse::<usize>().unwrap())
+/// Si un socket escuchando en `local` puede enviarle a `address`: tienen que ser de la misma
+/// familia, salvo que se escuche en `::`, desde donde tambien se llega a direcciones IPv4.
+pub fn can_reach(local: &SocketAddr, address: &SocketAddr) -> bool {
+    local.is_ipv4() == address.is_ipv4() || (local.is_ipv6() && local.ip().is_unspecified())
+}
+
+/// Direccion por la que el propio nodo es alcanzable, que es la que se les da a los vecinos.
+/// Si se escucha en todas las interfaces (`0.0.0.0` o `::`) se usa la de la interfaz por la que
+/// se sale hacia `peer`, que se averigua conectando un socket UDP (sin enviar nada).
+pub fn reachable_address(
+    listen_address: &SocketAddr,
+    peer: Option<&SocketAddr>,
+) -> Result<SocketAddr, String> {
+    if !listen_address.ip().is_unspecified() {
+        return Ok(canonical(*listen_address));
+    }
+    let peer = peer.ok_or_else(|| {
+        format!(
+            "Listening on {} requires --advertise <address> (there is no neighbor to find out \
+             which interface reaches the cluster)",
+            listen_address
+        )
+    })?;
+    let any_port = match listen_address {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    UdpSocket::bind(any_port)
+        .and_then(|socket| {
+            socket.connect(for_socket(peer, listen_address))?;
+            socket.local_addr()
+        })
+        .map(|local| canonical(SocketAddr::new(local.ip(), listen_address.port())))
+        .map_err(|error| {
+            format!(
+                "Couldn't find out the address of {} that reaches {}: {}. Use --advertise <address>",
+                listen_address, peer, error
+            )
+        })
 }
 
 #[cfg(test)]
@@ -13,7 +97,90 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_parse_ip() {
-        assert_eq!(Some(8080), get_port_from_dir("127.0.0.1:8080"))
+    fn test_parse_listen_address_with_only_port() {
+        assert_eq!(
+            parse_listen_address("8080"),
+            Ok("127.0.0.1:8080".parse().unwrap())
+        )
+    }
+
+    #[test]
+    fn test_parse_listen_address_any_interface() {
+        assert_eq!(
+            parse_listen_address("0.0.0.0:8080"),
+            Ok("0.0.0.0:8080".parse().unwrap())
+        );
+        assert_eq!(
+            parse_listen_address("[::]:8080"),
+            Ok("[::]:8080".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_resolve_neighbor_bracketed_ipv6() {
+        let local = "[::]:8080".parse().unwrap();
+        assert_eq!(
+            resolve_neighbor("[::1]:8081", &local),
+            Ok("[::1]:8081".parse().unwrap())
+        )
+    }
+
+    #[test]
+    fn test_resolve_neighbor_hostname_prefers_local_family() {
+        let local = "127.0.0.1:8080".parse().unwrap();
+        assert!(resolve_neighbor("localhost:8081", &local)
+            .unwrap()
+            .is_ipv4())
+    }
+
+    #[test]
+    fn test_resolve_neighbor_invalid() {
+        let local = "127.0.0.1:8080".parse().unwrap();
+        assert!(resolve_neighbor("127.0.0.1", &local).is_err())
+    }
+
+    #[test]
+    fn test_canonical_unmaps_ipv4() {
+        assert_eq!(
+            canonical("[::ffff:127.0.0.1]:8080".parse().unwrap()),
+            "127.0.0.1:8080".parse().unwrap()
+        )
+    }
+
+    #[test]
+    fn test_for_socket_maps_ipv4_on_ipv6_socket() {
+        let address = "127.0.0.1:8080".parse().unwrap();
+        assert_eq!(
+            for_socket(&address, &"[::]:9090".parse().unwrap()),
+            "[::ffff:127.0.0.1]:8080".parse().unwrap()
+        );
+        assert_eq!(
+            for_socket(&address, &"0.0.0.0:9090".parse().unwrap()),
+            address
+        );
+    }
+
+    #[test]
+    fn test_can_reach() {
+        let ipv4 = "127.0.0.1:8080".parse().unwrap();
+        let ipv6 = "[::1]:8080".parse().unwrap();
+        assert!(can_reach(&ipv4, &"10.0.0.1:8081".parse().unwrap()));
+        assert!(!can_reach(&ipv4, &ipv6));
+        assert!(!can_reach(&ipv6, &ipv4));
+        assert!(can_reach(&"[::]:8080".parse().unwrap(), &ipv4));
+    }
+
+    #[test]
+    fn test_reachable_address() {
+        let peer = "127.0.0.1:9090".parse().unwrap();
+        assert_eq!(
+            reachable_address(&"0.0.0.0:8080".parse().unwrap(), Some(&peer)),
+            Ok("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(
+            reachable_address(&"10.0.0.1:8080".parse().unwrap(), None),
+            Ok("10.0.0.1:8080".parse().unwrap())
+        );
+        assert!(reachable_address(&"0.0.0.0:8080".parse().unwrap(), None).is_err());
     }
 }
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use crate::chacha20::chacha20;
+use crate::envelope::Envelope;
+use crate::hmac::{constant_time_eq, hmac_sha256};
+use crate::message_nonce::MessageNonce;
+use crate::sha256::{from_hex, to_hex};
+
+/// Cantidad de bytes del HMAC-SHA256 que viajan en cada mensaje (HMAC-SHA256-128).
+const MAC_LEN: usize = 16;
+
+/// Claves precompartidas del cluster. Cada mensaje se autentica con la clave propia del nodo
+/// que lo envia si esta configurada, o si no con la clave del cluster.
+/// Sin ninguna clave configurada la autenticacion esta deshabilitada.
+/// Si ademas se habilita el cifrado, el mensaje se cifra con ChaCha20 antes de calcular
+/// el MAC (encrypt-then-MAC), con una clave derivada de la del emisor.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Keyring {
+    cluster_key: Option<Vec<u8>>,
+    node_keys: HashMap<u64, Vec<u8>>,
+    encrypt: bool,
+}
+
+impl Keyring {
+    pub fn new(cluster_key: Option<String>, node_keys: Vec<(u64, String)>) -> Keyring {
+        Keyring {
+            cluster_key: cluster_key.map(String::into_bytes),
+            node_keys: node_keys
+                .into_iter()
+                .map(|(id, key)| (id, key.into_bytes()))
+                .collect(),
+            encrypt: false,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.cluster_key.is_some() || !self.node_keys.is_empty()
+    }
+
+    pub fn set_encryption(&mut self, encrypt: bool) {
+        self.encrypt = encrypt;
+    }
+
+    pub fn encrypts(&self) -> bool {
+        self.encrypt && self.is_enabled()
+    }
+
+    fn key_for(&self, sender_id: u64) -> Option<&[u8]> {
+        self.node_keys
+            .get(&sender_id)
+            .or(self.cluster_key.as_ref())
+            .map(Vec::as_slice)
+    }
+
+    /// Clave de cifrado de los mensajes de `sender_id`. Se deriva por emisor para que dos nodos
+    /// que comparten la clave del cluster nunca cifren con el mismo par clave/nonce.
+    fn encryption_key(&self, sender_id: u64) -> Option<[u8; 32]> {
+        let key = self.key_for(sender_id)?;
+        Some(hmac_sha256(
+            key,
+            format!("tp3-encryption-key:{}", sender_id).as_bytes(),
+        ))
+    }
+
+    /// Prepara un sobre para enviarlo: le agrega el nonce, lo cifra si corresponde y le agrega
+    /// el MAC. El nonce va aunque no se cifre, para que el MAC lo cubra y el receptor pueda
+    /// rechazar mensajes repetidos.
+    pub fn seal(&self, envelope: &mut Envelope, nonce: MessageNonce) {
+        if !self.is_enabled() {
+            return;
+        }
+        envelope.nonce = Some(nonce);
+        if self.encrypts() {
+            if let Some(key) = self.encryption_key(envelope.sender.id) {
+                envelope.payload = to_hex(&chacha20(
+                    &key,
+                    &nonce.as_bytes(),
+                    envelope.payload.as_bytes(),
+                ));
+            }
+        }
+        envelope.mac = self.sign(envelope.sender.id, &envelope.signed_content());
+    }
+
+    /// Verifica el MAC de un sobre recibido y, si el cluster cifra sus mensajes, lo descifra.
+    /// Devuelve `None` si el sobre no es autentico, no tiene nonce o no esta cifrado cuando
+    /// deberia.
+    pub fn open(&self, mut envelope: Envelope) -> Option<Envelope> {
+        if self.is_enabled() && envelope.nonce.is_none() {
+            return None;
+        }
+        if !self.verify(
+            envelope.sender.id,
+            &envelope.signed_content(),
+            envelope.mac.as_deref(),
+        ) {
+            return None;
+        }
+        if self.encrypts() {
+            let nonce = envelope.nonce?;
+            let key = self.encryption_key(envelope.sender.id)?;
+            let plaintext = chacha20(&key, &nonce.as_bytes(), &from_hex(&envelope.payload)?);
+            envelope.payload = String::from_utf8(plaintext).ok()?;
+        }
+        Some(envelope)
+    }
+
+    /// MAC (en hexadecimal) de un mensaje enviado por `sender_id`.
+    pub fn sign(&self, sender_id: u64, content: &str) -> Option<String> {
+        let key = self.key_for(sender_id)?;
+        Some(to_hex(&hmac_sha256(key, content.as_bytes())[..MAC_LEN]))
+    }
+
+    /// Verifica el MAC de un mensaje que dice venir de `sender_id`.
+    /// Si la autenticacion esta deshabilitada todo mensaje es valido.
+    pub fn verify(&self, sender_id: u64, content: &str, mac: Option<&str>) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+        let (key, mac) = match (self.key_for(sender_id), mac.and_then(from_hex)) {
+            (Some(key), Some(mac)) => (key, mac),
+            _ => return false,
+        };
+        constant_time_eq(&hmac_sha256(key, content.as_bytes())[..MAC_LEN], &mac)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_identity::NodeIdentity;
+
+    #[test]
+    fn test_disabled_keyring_accepts_everything() {
+        let keyring = Keyring::default();
+        assert_eq!(keyring.sign(1, "3,0|C"), None);
+        assert!(keyring.verify(1, "3,0|C", None));
+    }
+
+    #[test]
+    fn test_cluster_key_signs_and_verifies() {
+        let keyring = Keyring::new(Some(String::from("secret")), vec![]);
+        let mac = keyring.sign(3, "3,0|C").unwrap();
+        assert_eq!(mac.len(), MAC_LEN * 2);
+        assert!(keyring.verify(3, "3,0|C", Some(&mac)));
+        assert!(!keyring.verify(3, "3,0|E", Some(&mac)));
+        assert!(!keyring.verify(3, "3,0|C", None));
+        assert!(!keyring.verify(3, "3,0|C", Some("zz")));
+    }
+
+    #[test]
+    fn test_different_cluster_key_is_rejected() {
+        let mac = Keyring::new(Some(String::from("secret")), vec![])
+            .sign(3, "3,0|C")
+            .unwrap();
+        let other = Keyring::new(Some(String::from("other")), vec![]);
+        assert!(!other.verify(3, "3,0|C", Some(&mac)));
+    }
+
+    fn sealed(keyring: &Keyring, payload: &str) -> Envelope {
+        let mut envelope = Envelope::new(NodeIdentity::new(3, 0), String::from(payload));
+        keyring.seal(
+            &mut envelope,
+            MessageNonce {
+                session: 1000,
+                counter: 1,
+            },
+        );
+        envelope
+    }
+
+    #[test]
+    fn test_seal_and_open_without_encryption() {
+        let keyring = Keyring::new(Some(String::from("secret")), vec![]);
+        let envelope = sealed(&keyring, "GRADE_TO_COORDINATOR;juan;8");
+        assert_eq!(envelope.payload, "GRADE_TO_COORDINATOR;juan;8");
+        assert!(envelope.nonce.is_some());
+        assert_eq!(
+            keyring.open(envelope).unwrap().payload,
+            "GRADE_TO_COORDINATOR;juan;8"
+        );
+    }
+
+    #[test]
+    fn test_open_rejects_envelope_without_nonce() {
+        let keyring = Keyring::new(Some(String::from("secret")), vec![]);
+        let mut envelope = sealed(&keyring, "GRADE_TO_COORDINATOR;juan;8");
+        envelope.nonce = None;
+        envelope.mac = keyring.sign(3, &envelope.signed_content());
+        assert_eq!(keyring.open(envelope), None);
+    }
+
+    #[test]
+    fn test_disabled_keyring_seals_nothing() {
+        let envelope = sealed(&Keyring::default(), "C");
+        assert_eq!(envelope.mac, None);
+        assert_eq!(envelope.nonce, None);
+    }
+
+    #[test]
+    fn test_seal_and_open_with_encryption() {
+        let mut keyring = Keyring::new(Some(String::from("secret")), vec![]);
+        keyring.set_encryption(true);
+        let envelope = sealed(&keyring, "GRADE_TO_COORDINATOR;juan;8");
+        assert!(!envelope.payload.contains("juan"));
+        assert!(envelope.nonce.is_some());
+        assert_eq!(
+            keyring.open(envelope).unwrap().payload,
+            "GRADE_TO_COORDINATOR;juan;8"
+        );
+    }
+
+    #[test]
+    fn test_open_rejects_tampered_ciphertext() {
+        let mut keyring = Keyring::new(Some(String::from("secret")), vec![]);
+        keyring.set_encryption(true);
+        let mut envelope = sealed(&keyring, "GRADE_TO_COORDINATOR;juan;8");
+        envelope.payload.replace_range(0..2, "00");
+        assert_eq!(keyring.open(envelope), None);
+    }
+
+    #[test]
+    fn test_open_rejects_plaintext_when_encryption_is_required() {
+        let plain = Keyring::new(Some(String::from("secret")), vec![]);
+        let mut encrypting = plain.clone();
+        encrypting.set_encryption(true);
+        assert_eq!(encrypting.open(sealed(&plain, "C")), None);
+    }
+
+    #[test]
+    fn test_node_keys_take_precedence() {
+        let keyring = Keyring::new(
+            Some(String::from("cluster")),
+            vec![(3, String::from("node-3"))],
+        );
+        let mac = keyring.sign(3, "3,0|C").unwrap();
+        let cluster_only = Keyring::new(Some(String::from("cluster")), vec![]);
+        assert!(keyring.verify(3, "3,0|C", Some(&mac)));
+        assert!(!cluster_only.verify(3, "3,0|C", Some(&mac)));
+    }
+}
@@ -0,0 +1,15 @@
+/// Configuracion de los logs del nodo. Por defecto se loguea a la salida estandar.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LogConfig {
+    pub enabled: bool,
+    pub file: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig {
+            enabled: true,
+            file: None,
+        }
+    }
+}
@@ -1,16 +1,49 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::log_config::LogConfig;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+/// Aplica la configuracion de logs. Con un archivo, los logs se agregan al final de este en
+/// lugar de mezclarse con los comandos en la salida estandar.
+pub fn init(config: &LogConfig) -> Result<(), String> {
+    ENABLED.store(config.enabled, Ordering::Relaxed);
+    if let Some(path) = &config.file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|error| format!("Couldn't open log file {:?}: {}", path, error))?;
+        *LOG_FILE.lock().unwrap() = Some(file);
+    }
+    Ok(())
+}
+
 pub fn log(formatted_string: String) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
         .as_millis()
         .to_string();
-    println!(
+    let line = format!(
         "[{}] [{:?}] {}",
         timestamp,
         thread::current().id(),
         formatted_string
     );
+    match LOG_FILE.lock().unwrap().as_mut() {
+        Some(file) => {
+            let _ = writeln!(file, "{}", line);
+        }
+        None => println!("{}", line),
+    }
 }
@@ -1,62 +1,152 @@
 use std::io::{stdin, stdout, Write};
+use std::net::SocketAddr;
 use std::process::exit;
 use std::sync::{Arc, Mutex};
 use std::{env, thread};
 
 use crate::blockchain_node::BlockchainNode;
+use crate::fault_rule::FaultRule;
 use crate::logger::log;
+use crate::node_config::NodeConfig;
 
 mod acquire_message;
 mod add_grade_message;
+mod address_book;
 mod blockchain;
 mod blockchain_message;
 mod blockchain_node;
+mod bully_election;
+mod capture_record;
+mod chacha20;
+mod chain_head;
+mod chain_store;
+mod chain_sync;
+mod clock;
+mod config_file;
+mod config_values;
 mod coordinator_state;
+mod data_file;
+mod discovery_config;
 mod dist_mutex;
+mod election_algorithm;
+mod election_kind;
 mod election_message;
+mod envelope;
+mod failure_detector;
+mod fault_direction;
+mod fault_injector;
+mod fault_rule;
+mod heartbeat_message;
+mod hmac;
 mod ip_parser;
+mod keyring;
+mod log_config;
 mod logger;
+mod membership_message;
+mod memory_network;
+mod memory_transport;
+mod message_capture;
+mod message_nonce;
+mod messenger;
+mod node_config;
+mod node_identity;
+mod peer_directory;
+mod peer_stats;
+mod peer_status;
+mod pending_records;
+mod reconciliation;
+mod replay;
+mod replay_guard;
+mod retry_policy;
+mod ring_election;
+mod ring_message;
 mod sender;
+mod sha256;
+mod system_clock;
+#[cfg(test)]
+mod task_handle;
+mod timeouts;
+mod toml_value;
+mod transport;
+mod udp_transport;
+mod virtual_clock;
+mod xorshift;
 
 pub const BUFFER_SIZE: usize = 2;
 
 #[allow(clippy::mutex_atomic)]
 fn main() {
     let args: Vec<String> = env::args().collect();
+    let config = match NodeConfig::from_args(&args[1..]) {
+        Ok(config) => config,
+        Err(error) => {
+            println!("{}", error);
+            exit(1);
+        }
+    };
+    if let Err(error) = logger::init(&config.logging) {
+        println!("{}", error);
+        exit(1);
+    }
     log(format!("Received args = {:?}", args));
-
-    if args.len() - 1 < 2 {
-        panic!(
-            "Required args: port ip1:port1 ip2:port2. Try: cargo run 6060 127.0.0.1:6061 127.0.0.1:6062"
-        );
+    log(format!("listen_address = {:?}", config.listen_address));
+    log(format!(
+        "neighbor_addresses = {:?}",
+        config.neighbor_addresses
+    ));
+    if config.derived_id {
+        log(format!(
+            "Warning: no --id given, using {} derived from {}. It changes with the address; pass --id to keep it",
+            config.node_id, config.advertise_address
+        ));
     }
 
-    let port = args[1].clone();
-    let neighbor_addresses: Vec<String> = args
-        .into_iter()
-        .enumerate()
-        .filter_map(|(i, e)| if i > 1 { Some(e) } else { None })
-        .collect();
-    log(format!("neighbor_addresses = {:?}", neighbor_addresses));
-
-    start_node(&port, neighbor_addresses);
+    if let Some(path) = config.replay_path.clone() {
+        if let Err(error) = replay::replay(config, &path) {
+            println!("{}", error);
+            exit(1);
+        }
+        return;
+    }
+    start_node(config);
 }
 
-fn start_node(port: &str, neighbor_addresses: Vec<String>) {
-    let numeric_port = port.parse::<usize>().unwrap();
-    let node = Arc::new(Mutex::new(BlockchainNode::new(
-        numeric_port,
-        neighbor_addresses,
-    )));
+fn start_node(config: NodeConfig) {
+    let join_address = config.join_address;
+    let node = Arc::new(Mutex::new(BlockchainNode::new(config)));
     let cloned_node = node.clone();
 
     thread::spawn(move || {
         BlockchainNode::listen(cloned_node);
     });
 
+    let heartbeat_node = node.clone();
+    thread::spawn(move || {
+        BlockchainNode::start_heartbeat(heartbeat_node);
+    });
+
+    let anti_entropy_node = node.clone();
+    thread::spawn(move || {
+        BlockchainNode::start_anti_entropy(anti_entropy_node);
+    });
+
+    // Un nodo que se reinicia con su cadena guardada vuelve a entrar a traves de sus vecinos.
+    let recovered_chain = node.lock().unwrap().recovered_chain();
+    let joined = match join_address {
+        Some(contact) => BlockchainNode::join_cluster(node.clone(), contact),
+        None if recovered_chain => BlockchainNode::rejoin_cluster(node.clone()),
+        None => false,
+    };
+
+    BlockchainNode::discover_peers(node.clone());
     BlockchainNode::ask_for_blockchain(node.clone());
 
-    BlockchainNode::begin_election(node.clone());
+    // Al entrar (o volver) a un cluster que ya esta andando se conserva su coordinador, salvo
+    // que el nodo le gane la eleccion.
+    let outranks_coordinator = node.lock().unwrap().outranks_coordinator();
+    if !joined || outranks_coordinator {
+        BlockchainNode::begin_election(node.clone());
+    }
     loop {
         prompt_loop(node.clone());
     }
@@ -93,7 +183,9 @@ fn execute_command(raw_command: String, node: Arc<Mutex<BlockchainNode>>) {
                         "Received add_grade command with params: {:?} {:?}",
                         student_name, grade
                     ));
-                    let _ = BlockchainNode::add_grade(node, student_name, grade);
+                    if let Err(error) = BlockchainNode::add_grade(node, student_name, grade) {
+                        println!("{}", error);
+                    }
                 }
                 Err(_error) => {
                     log("Invalid grade number for add_grade command".to_string());
@@ -111,17 +203,24 @@ fn execute_command(raw_command: String, node: Arc<Mutex<BlockchainNode>>) {
         }
         "quit" => {
             log("Received quit command".to_string());
+            BlockchainNode::leave(node);
             exit(0);
         }
         "ping" => {
             log("Received ping command".to_string());
             match node.lock() {
-                Ok(node) => node.ping_neighbors(),
+                Ok(mut node) => node.ping_neighbors(),
                 Err(error) => {
                     panic!("{}", error.to_string())
                 }
             }
         }
+        "peers" => match node.lock() {
+            Ok(node) => node.print_peers(),
+            Err(error) => {
+                panic!("{}", error.to_string())
+            }
+        },
         "make_coordinator" => {
             log("Received make_coordinator command".to_string());
             match node.lock() {
@@ -132,6 +231,55 @@ fn execute_command(raw_command: String, node: Arc<Mutex<BlockchainNode>>) {
             }
         }
 
+        "fault" => {
+            log(format!(
+                "Received fault command: {:?}",
+                &parsed_command[1..]
+            ));
+            let node = node.lock().unwrap();
+            match parsed_command.get(1) {
+                Some(&"list") => node.print_faults(),
+                Some(&"clear") => node.clear_faults(),
+                _ => match FaultRule::from_args(&parsed_command[1..]) {
+                    Ok(rule) => node.add_fault(rule),
+                    Err(error) => println!("{}", error),
+                },
+            }
+        }
+
+        "partition" => {
+            log(format!(
+                "Received partition command: {:?}",
+                &parsed_command[1..]
+            ));
+            match parse_peers(&parsed_command[1..]) {
+                Ok(peers) if !peers.is_empty() => node.lock().unwrap().partition(&peers),
+                Ok(_) => println!("Usage: partition <peer> [<peer>...]"),
+                Err(error) => println!("{}", error),
+            }
+        }
+
+        "heal" => {
+            log(format!("Received heal command: {:?}", &parsed_command[1..]));
+            match parse_peers(&parsed_command[1..]) {
+                Ok(peers) if peers.is_empty() => node.lock().unwrap().heal(None),
+                Ok(peers) => {
+                    let node = node.lock().unwrap();
+                    for peer in &peers {
+                        node.heal(Some(peer));
+                    }
+                }
+                Err(error) => println!("{}", error),
+            }
+        }
+
+        "repair" => {
+            log("Received repair command".to_string());
+            if !BlockchainNode::repair_chain(node) {
+                println!("Chain is valid. Nothing to repair");
+            }
+        }
+
         "begin_election" => {
             log("Received begin_election command".to_string());
             BlockchainNode::begin_election(node);
@@ -141,7 +289,18 @@ fn execute_command(raw_command: String, node: Arc<Mutex<BlockchainNode>>) {
             print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
         }
         _ => {
-            log("Ups! Didn't understand that. Available commands: add_grade, print, quit, ping, make_coordinator, begin_election, clear".to_string());
+            log("Ups! Didn't understand that. Available commands: add_grade, print, quit, ping, peers, repair, fault, partition, heal, make_coordinator, begin_election, clear".to_string());
         }
     }
 }
+
+fn parse_peers(peers: &[&str]) -> Result<Vec<SocketAddr>, String> {
+    peers
+        .iter()
+        .map(|peer| {
+            peer.parse::<SocketAddr>()
+                .map(ip_parser::canonical)
+                .map_err(|_error| format!("Invalid peer address: {:?}", peer))
+        })
+        .collect()
+}
@@ -0,0 +1,221 @@
+use std::net::SocketAddr;
+
+use crate::node_identity::{self, NodeIdentity};
+
+/// Mensajes para sumar nodos al cluster sin reiniciarlo.
+/// - **Join**: Un nodo nuevo le pide a cualquier miembro entrar al cluster.
+/// - **Members**: Respuesta a un Join con el term y el coordinador actual (identidad y
+///   direccion) y la direccion de los demas miembros.
+/// - **MemberAdded**: El miembro que recibio el Join avisa a los demas la direccion del nodo nuevo.
+/// - **Leave**: Un nodo avisa que sale del cluster, para que lo saquen de sus vecinos.
+/// - **Handoff**: El coordinador que sale le pasa el rol a su sucesor, junto con su term y los
+///   nodos que esperan el lock (identidad y direccion, ya que el sucesor puede no conocerlos), en
+///   orden.
+/// - **Peers**: Un nodo le pasa a otro la direccion de los vecinos que conoce, para que los
+///   nodos iniciados con solo algunas semillas descubran al resto del cluster.
+/// - **Announce**: Un nodo iniciado con `--discover` se anuncia en la red local con el nombre de
+///   su cluster. Los nodos del mismo cluster lo suman y le responden con un Peers.
+///
+/// |     Tipo      |  Representacion                                               |
+/// |---------------|---------------------------------------------------------------|
+/// |   Join        |   JOIN                                                        |
+/// |   Members     |   MEMBERS;<term>;<id>,<prioridad>,<direccion>;<direccion>,... |
+/// |   MemberAdded |   MEMBER_ADDED;<direccion>                                    |
+/// |   Leave       |   LEAVE                                                       |
+/// |   Handoff     |   HANDOFF;<term>;<id>,<prioridad>,<direccion>;...             |
+/// |   Peers       |   PEERS;<direccion>,<direccion>,...                           |
+/// |   Announce    |   ANNOUNCE;<cluster>                                          |
+#[derive(PartialEq, Debug)]
+pub enum MembershipMessage {
+    Join,
+    Members(u64, NodeIdentity, SocketAddr, Vec<SocketAddr>),
+    MemberAdded(SocketAddr),
+    Leave,
+    Handoff(u64, Vec<(NodeIdentity, SocketAddr)>),
+    Peers(Vec<SocketAddr>),
+    Announce(String),
+}
+
+impl MembershipMessage {
+    pub fn as_string(&self) -> String {
+        match self {
+            MembershipMessage::Join => String::from("JOIN"),
+            MembershipMessage::Members(term, coordinator, coordinator_addr, members) => format!(
+                "MEMBERS;{};{};{}",
+                term,
+                node_identity::format_node(coordinator, coordinator_addr),
+                join_addresses(members)
+            ),
+            MembershipMessage::MemberAdded(member) => format!("MEMBER_ADDED;{}", member),
+            MembershipMessage::Leave => String::from("LEAVE"),
+            MembershipMessage::Handoff(term, queue) => {
+                vec![String::from("HANDOFF"), term.to_string()]
+                    .into_iter()
+                    .chain(
+                        queue.iter().map(|(identity, address)| {
+                            node_identity::format_node(identity, address)
+                        }),
+                    )
+                    .collect::<Vec<String>>()
+                    .join(";")
+            }
+            MembershipMessage::Peers(peers) => format!("PEERS;{}", join_addresses(peers)),
+            MembershipMessage::Announce(cluster_name) => format!("ANNOUNCE;{}", cluster_name),
+        }
+    }
+
+    pub fn from_string(string: &str) -> Option<MembershipMessage> {
+        let tokens = string.split(';').collect::<Vec<&str>>();
+        match tokens[..] {
+            ["JOIN"] => Some(MembershipMessage::Join),
+            ["MEMBERS", term, coordinator, members] => {
+                let (coordinator, coordinator_addr) = node_identity::parse_node(coordinator)?;
+                Some(MembershipMessage::Members(
+                    term.parse().ok()?,
+                    coordinator,
+                    coordinator_addr,
+                    parse_addresses(members)?,
+                ))
+            }
+            ["MEMBER_ADDED", member] => Some(MembershipMessage::MemberAdded(member.parse().ok()?)),
+            ["LEAVE"] => Some(MembershipMessage::Leave),
+            ["HANDOFF", term, ref queue @ ..] => Some(MembershipMessage::Handoff(
+                term.parse().ok()?,
+                queue
+                    .iter()
+                    .map(|node| node_identity::parse_node(node))
+                    .collect::<Option<Vec<(NodeIdentity, SocketAddr)>>>()?,
+            )),
+            ["PEERS", peers] => Some(MembershipMessage::Peers(parse_addresses(peers)?)),
+            ["ANNOUNCE", cluster_name] if !cluster_name.is_empty() => {
+                Some(MembershipMessage::Announce(cluster_name.to_string()))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn join_addresses(addresses: &[SocketAddr]) -> String {
+    addresses
+        .iter()
+        .map(|address| address.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+/// Interpreta una lista de direcciones separadas por coma, que puede estar vacia.
+fn parse_addresses(addresses: &str) -> Option<Vec<SocketAddr>> {
+    if addresses.is_empty() {
+        return Some(Vec::new());
+    }
+    addresses
+        .split(',')
+        .map(|address| address.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn members() -> MembershipMessage {
+        MembershipMessage::Members(
+            4,
+            NodeIdentity::new(6062, 1),
+            "127.0.0.1:6062".parse().unwrap(),
+            vec![
+                "127.0.0.1:6060".parse().unwrap(),
+                "[::1]:6061".parse().unwrap(),
+            ],
+        )
+    }
+
+    fn handoff() -> MembershipMessage {
+        MembershipMessage::Handoff(
+            4,
+            vec![
+                (
+                    NodeIdentity::new(6060, 0),
+                    "127.0.0.1:6060".parse().unwrap(),
+                ),
+                (NodeIdentity::new(6061, 2), "[::1]:6061".parse().unwrap()),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!(MembershipMessage::Join.as_string(), "JOIN");
+        assert_eq!(
+            members().as_string(),
+            "MEMBERS;4;6062,1,127.0.0.1:6062;127.0.0.1:6060,[::1]:6061"
+        );
+        assert_eq!(
+            MembershipMessage::MemberAdded("127.0.0.1:6063".parse().unwrap()).as_string(),
+            "MEMBER_ADDED;127.0.0.1:6063"
+        );
+        assert_eq!(MembershipMessage::Leave.as_string(), "LEAVE");
+        assert_eq!(
+            MembershipMessage::Handoff(1, Vec::new()).as_string(),
+            "HANDOFF;1"
+        );
+        assert_eq!(
+            handoff().as_string(),
+            "HANDOFF;4;6060,0,127.0.0.1:6060;6061,2,[::1]:6061"
+        );
+        assert_eq!(
+            MembershipMessage::Peers(vec!["127.0.0.1:6060".parse().unwrap()]).as_string(),
+            "PEERS;127.0.0.1:6060"
+        );
+        assert_eq!(
+            MembershipMessage::Announce(String::from("lab")).as_string(),
+            "ANNOUNCE;lab"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for message in [
+            MembershipMessage::Join,
+            members(),
+            MembershipMessage::Members(
+                0,
+                NodeIdentity::new(1, 0),
+                "127.0.0.1:6060".parse().unwrap(),
+                Vec::new(),
+            ),
+            MembershipMessage::MemberAdded("[::1]:6063".parse().unwrap()),
+            MembershipMessage::Leave,
+            MembershipMessage::Handoff(0, Vec::new()),
+            handoff(),
+            MembershipMessage::Peers(Vec::new()),
+            MembershipMessage::Peers(vec![
+                "127.0.0.1:6060".parse().unwrap(),
+                "[::1]:6061".parse().unwrap(),
+            ]),
+            MembershipMessage::Announce(String::from("lab")),
+        ] {
+            assert_eq!(
+                MembershipMessage::from_string(&message.as_string()),
+                Some(message)
+            );
+        }
+    }
+
+    #[test]
+    fn test_none_from_string() {
+        assert_eq!(MembershipMessage::from_string("JOIN;"), None);
+        assert_eq!(MembershipMessage::from_string("MEMBERS;1,0;"), None);
+        assert_eq!(MembershipMessage::from_string("MEMBER_ADDED;nope"), None);
+        assert_eq!(MembershipMessage::from_string("PING;1"), None);
+        assert_eq!(
+            MembershipMessage::from_string("MEMBERS;x;1,0,127.0.0.1:6060;"),
+            None
+        );
+        assert_eq!(MembershipMessage::from_string("HANDOFF"), None);
+        assert_eq!(MembershipMessage::from_string("HANDOFF;1;1,x"), None);
+        assert_eq!(MembershipMessage::from_string("HANDOFF;1;1,0"), None);
+        assert_eq!(MembershipMessage::from_string("PEERS;127.0.0.1"), None);
+        assert_eq!(MembershipMessage::from_string("ANNOUNCE;"), None);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::memory_transport::MemoryTransport;
+use crate::transport::Datagram;
+
+/// Red en memoria que conecta `MemoryTransport`s dentro de un mismo proceso.
+///
+/// Los mensajes enviados quedan en vuelo hasta que el test los entrega con `deliver_next` o
+/// `deliver_all`, siempre en el orden en que se enviaron. Como UDP, los mensajes a direcciones
+/// sin transporte asociado se pierden.
+#[derive(Default)]
+pub struct MemoryNetwork {
+    in_flight: Mutex<VecDeque<(SocketAddr, SocketAddr, Vec<u8>)>>,
+    inboxes: Mutex<HashMap<SocketAddr, Sender<Datagram>>>,
+    /// Mensajes entregados que el nodo que los recibe todavia no termino de procesar.
+    unprocessed: (Mutex<usize>, Condvar),
+}
+
+impl MemoryNetwork {
+    pub fn new() -> Arc<MemoryNetwork> {
+        Arc::new(MemoryNetwork::default())
+    }
+
+    /// Crea el transporte de un nodo que recibe en `address`.
+    pub fn bind(network: &Arc<MemoryNetwork>, address: SocketAddr) -> MemoryTransport {
+        let (sender, receiver) = channel();
+        network.inboxes.lock().unwrap().insert(address, sender);
+        MemoryTransport::new(address, network.clone(), receiver)
+    }
+
+    /// Desconecta el transporte que recibe en `address`, como si el nodo se cayera: los mensajes
+    /// que le lleguen se pierden y deja de recibir.
+    #[cfg(test)]
+    pub fn unbind(&self, address: &SocketAddr) {
+        self.inboxes.lock().unwrap().remove(address);
+    }
+
+    pub fn enqueue(&self, from: SocketAddr, to: SocketAddr, buffer: Vec<u8>) {
+        self.in_flight.lock().unwrap().push_back((from, to, buffer));
+    }
+
+    /// Entrega el mensaje en vuelo mas antiguo. Devuelve `false` si no habia ninguno.
+    #[cfg(test)]
+    pub fn deliver_next(&self) -> bool {
+        let message = self.in_flight.lock().unwrap().pop_front();
+        match message {
+            Some((from, to, buffer)) => {
+                if let Some(inbox) = self.inboxes.lock().unwrap().get(&to) {
+                    let mut unprocessed = self.unprocessed.0.lock().unwrap();
+                    if inbox.send((buffer, from)).is_ok() {
+                        *unprocessed += 1;
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Entrega todos los mensajes en vuelo y devuelve cuantos eran.
+    #[cfg(test)]
+    pub fn deliver_all(&self) -> usize {
+        let mut delivered = 0;
+        while self.deliver_next() {
+            delivered += 1;
+        }
+        delivered
+    }
+
+    /// Mensajes en vuelo, como (emisor, destino, contenido), sin entregarlos.
+    #[cfg(test)]
+    pub fn in_flight_messages(&self) -> Vec<(SocketAddr, SocketAddr, String)> {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(from, to, buffer)| (*from, *to, String::from_utf8_lossy(buffer).to_string()))
+            .collect()
+    }
+
+    /// Avisa que el nodo termino de procesar un mensaje entregado.
+    pub fn processed(&self) {
+        *self.unprocessed.0.lock().unwrap() -= 1;
+        self.unprocessed.1.notify_all();
+    }
+
+    /// Espera a que los nodos terminen de procesar todos los mensajes entregados.
+    #[cfg(test)]
+    pub fn wait_until_processed(&self) {
+        let _unprocessed = self
+            .unprocessed
+            .1
+            .wait_while(self.unprocessed.0.lock().unwrap(), |unprocessed| {
+                *unprocessed > 0
+            })
+            .unwrap();
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::Transport;
+
+    fn address(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_messages_wait_until_delivered() {
+        let network = MemoryNetwork::new();
+        let first = MemoryNetwork::bind(&network, address(1));
+        let _second = MemoryNetwork::bind(&network, address(2));
+        first.send_to(b"hola", &address(2));
+        assert_eq!(network.in_flight(), 1);
+        assert!(network.deliver_next());
+        assert!(!network.deliver_next());
+    }
+
+    #[test]
+    fn test_messages_are_delivered_in_order() {
+        let network = MemoryNetwork::new();
+        let first = MemoryNetwork::bind(&network, address(1));
+        let second = MemoryNetwork::bind(&network, address(2));
+        first.send_to(b"uno", &address(2));
+        first.send_to(b"dos", &address(2));
+        assert_eq!(network.deliver_all(), 2);
+        assert_eq!(second.recv_from().unwrap(), (b"uno\n".to_vec(), address(1)));
+        assert_eq!(second.recv_from().unwrap(), (b"dos\n".to_vec(), address(1)));
+    }
+
+    #[test]
+    fn test_messages_to_unknown_addresses_are_lost() {
+        let network = MemoryNetwork::new();
+        let first = MemoryNetwork::bind(&network, address(1));
+        first.send_to(b"hola", &address(3));
+        assert_eq!(network.deliver_all(), 1);
+        assert_eq!(network.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_unbound_transport_stops_receiving() {
+        let network = MemoryNetwork::new();
+        let first = MemoryNetwork::bind(&network, address(1));
+        let second = MemoryNetwork::bind(&network, address(2));
+        network.unbind(&address(2));
+        first.send_to(b"hola", &address(2));
+        assert_eq!(network.deliver_all(), 1);
+        assert_eq!(
+            second.recv_from().unwrap_err().kind(),
+            std::io::ErrorKind::NotConnected
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+
+use crate::memory_network::MemoryNetwork;
+use crate::transport::{Datagram, Transport};
+
+/// Transporte de un nodo conectado a una `MemoryNetwork`. Cada mensaje viaja entero en un solo
+/// fragmento, terminado en `\n` como en UDP.
+/// Se da por procesado un mensaje recibido cuando el nodo vuelve a pedir el siguiente.
+pub struct MemoryTransport {
+    address: SocketAddr,
+    network: Arc<MemoryNetwork>,
+    inbox: Mutex<Receiver<Datagram>>,
+    processing: Mutex<bool>,
+}
+
+impl MemoryTransport {
+    pub fn new(
+        address: SocketAddr,
+        network: Arc<MemoryNetwork>,
+        inbox: Receiver<Datagram>,
+    ) -> MemoryTransport {
+        MemoryTransport {
+            address,
+            network,
+            inbox: Mutex::new(inbox),
+            processing: Mutex::new(false),
+        }
+    }
+}
+
+impl Transport for MemoryTransport {
+    fn send_to(&self, buffer: &[u8], address: &SocketAddr) {
+        let mut message = buffer.to_vec();
+        message.push(b'\n');
+        self.network.enqueue(self.address, *address, message);
+    }
+
+    fn recv_from(&self) -> io::Result<Datagram> {
+        let mut processing = self.processing.lock().unwrap();
+        if *processing {
+            self.network.processed();
+        }
+        let datagram = self.inbox.lock().unwrap().recv();
+        *processing = datagram.is_ok();
+        datagram.map_err(|error| io::Error::new(io::ErrorKind::NotConnected, error))
+    }
+}
@@ -0,0 +1,113 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::capture_record::CaptureRecord;
+use crate::fault_direction::FaultDirection;
+use crate::logger::log;
+use crate::node_identity::NodeIdentity;
+
+/// Archivo en el que un nodo iniciado con `--capture` registra cada mensaje que envia y recibe,
+/// una linea por mensaje (ver `CaptureRecord`). Se comparte entre los threads del nodo.
+pub struct MessageCapture {
+    file: Mutex<File>,
+}
+
+impl MessageCapture {
+    pub fn create(path: &str) -> Result<MessageCapture, String> {
+        match File::create(path) {
+            Ok(file) => Ok(MessageCapture {
+                file: Mutex::new(file),
+            }),
+            Err(error) => Err(format!(
+                "Couldn't create capture file {:?}: {}",
+                path, error
+            )),
+        }
+    }
+
+    pub fn record(
+        &self,
+        direction: FaultDirection,
+        peer: &SocketAddr,
+        sender: NodeIdentity,
+        payload: &str,
+    ) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let record = CaptureRecord {
+            timestamp,
+            direction,
+            peer: *peer,
+            sender,
+            payload: payload.to_string(),
+        };
+        let mut file = self.file.lock().unwrap();
+        if let Err(error) = writeln!(file, "{}", record.as_string()) {
+            log(format!("Couldn't write to capture file: {}", error));
+        }
+    }
+
+    /// Lee un archivo de captura. Falla en la primera linea que no se pueda interpretar.
+    pub fn load(path: &str) -> Result<Vec<CaptureRecord>, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read capture file {:?}: {}", path, error))?;
+        content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(number, line)| {
+                CaptureRecord::from_string(line).ok_or_else(|| {
+                    format!("Invalid capture record at line {}: {:?}", number + 1, line)
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_file::temp_path;
+
+    #[test]
+    fn test_record_and_load() {
+        let path = temp_path("capture-record-and-load");
+        let capture = MessageCapture::create(&path).unwrap();
+        let peer = "127.0.0.1:6061".parse().unwrap();
+        capture.record(
+            FaultDirection::Send,
+            &peer,
+            NodeIdentity::new(1, 0),
+            "PING;1",
+        );
+        capture.record(
+            FaultDirection::Receive,
+            &peer,
+            NodeIdentity::new(2, 0),
+            "PONG;1",
+        );
+        let records = MessageCapture::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].direction, FaultDirection::Send);
+        assert_eq!(records[1].sender, NodeIdentity::new(2, 0));
+        assert_eq!(records[1].payload, "PONG;1");
+    }
+
+    #[test]
+    fn test_load_reports_invalid_line() {
+        let path = temp_path("capture-invalid-line");
+        fs::write(&path, "1 send 127.0.0.1:1 1,0 PING;1\nbasura\n").unwrap();
+        let result = MessageCapture::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            result,
+            Err(String::from("Invalid capture record at line 2: \"basura\""))
+        );
+    }
+}
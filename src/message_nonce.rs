@@ -0,0 +1,32 @@
+/// Nonce de un mensaje autenticado. `session` identifica la ejecucion del nodo emisor
+/// (milisegundos desde epoch al iniciar) y `counter` numera sus mensajes dentro de la sesion.
+/// Ademas de hacer unico el nonce del cifrador, permite rechazar mensajes repetidos.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct MessageNonce {
+    pub session: u64,
+    pub counter: u64,
+}
+
+impl MessageNonce {
+    /// Los 12 bytes de nonce de ChaCha20: 6 bytes de sesion y 6 de contador.
+    pub fn as_bytes(&self) -> [u8; 12] {
+        let mut bytes = [0u8; 12];
+        bytes[..6].copy_from_slice(&self.session.to_be_bytes()[2..]);
+        bytes[6..].copy_from_slice(&self.counter.to_be_bytes()[2..]);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_bytes() {
+        let nonce = MessageNonce {
+            session: 0x0102030405,
+            counter: 0x0a0b,
+        };
+        assert_eq!(nonce.as_bytes(), [0, 1, 2, 3, 4, 5, 0, 0, 0, 0, 0x0a, 0x0b]);
+    }
+}
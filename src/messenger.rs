@@ -0,0 +1,103 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::clock::Clock;
+use crate::envelope::Envelope;
+use crate::fault_direction::FaultDirection;
+use crate::fault_injector::FaultInjector;
+use crate::keyring::Keyring;
+use crate::message_capture::MessageCapture;
+use crate::message_nonce::MessageNonce;
+use crate::node_identity::NodeIdentity;
+use crate::transport::Transport;
+
+/// Punto unico de salida de mensajes del nodo: envuelve cada mensaje en un `Envelope`
+/// con la identidad del nodo (y su MAC, si hay claves configuradas) antes de enviarlo.
+/// Todas las copias comparten el contador de mensajes, para no repetir nunca un nonce.
+/// Antes de enviar se le aplican al mensaje las fallas configuradas en el `FaultInjector`.
+/// Con `--capture`, cada mensaje efectivamente enviado se registra en el archivo de captura.
+pub struct Messenger {
+    transport: Arc<dyn Transport>,
+    clock: Arc<dyn Clock>,
+    identity: NodeIdentity,
+    keyring: Arc<Keyring>,
+    fault_injector: Arc<Mutex<FaultInjector>>,
+    capture: Option<Arc<MessageCapture>>,
+    session: u64,
+    counter: Arc<AtomicU64>,
+}
+
+impl Messenger {
+    pub fn new(
+        transport: Arc<dyn Transport>,
+        clock: Arc<dyn Clock>,
+        identity: NodeIdentity,
+        keyring: Arc<Keyring>,
+        fault_injector: Arc<Mutex<FaultInjector>>,
+        capture: Option<Arc<MessageCapture>>,
+    ) -> Messenger {
+        let session = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        Messenger {
+            transport,
+            clock,
+            identity,
+            keyring,
+            fault_injector,
+            capture,
+            session,
+            counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn send(&self, payload: &[u8], address: &SocketAddr) {
+        let deliveries = self.fault_injector.lock().unwrap().apply(
+            FaultDirection::Send,
+            address,
+            String::from_utf8_lossy(payload).to_string(),
+        );
+        for (delay, payload) in deliveries {
+            if delay.is_zero() {
+                self.send_now(payload, address);
+            } else {
+                let messenger = self.try_clone();
+                let address = *address;
+                self.clock.spawn(Box::new(move || {
+                    messenger.clock.sleep(delay);
+                    messenger.send_now(payload, &address);
+                }));
+            }
+        }
+    }
+
+    fn send_now(&self, payload: String, address: &SocketAddr) {
+        if let Some(capture) = &self.capture {
+            capture.record(FaultDirection::Send, address, self.identity, &payload);
+        }
+        let mut envelope = Envelope::new(self.identity, payload);
+        let nonce = MessageNonce {
+            session: self.session,
+            counter: self.counter.fetch_add(1, Ordering::SeqCst) + 1,
+        };
+        self.keyring.seal(&mut envelope, nonce);
+        self.transport
+            .send_to(envelope.as_string().as_bytes(), address);
+    }
+
+    pub fn try_clone(&self) -> Messenger {
+        Messenger {
+            transport: self.transport.clone(),
+            clock: self.clock.clone(),
+            identity: self.identity,
+            keyring: self.keyring.clone(),
+            fault_injector: self.fault_injector.clone(),
+            capture: self.capture.clone(),
+            session: self.session,
+            counter: self.counter.clone(),
+        }
+    }
+}
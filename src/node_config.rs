@@ -0,0 +1,803 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use crate::config_file::ConfigFile;
+use crate::config_values::ConfigValues;
+use crate::discovery_config::{self, DiscoveryConfig};
+use crate::election_kind::{ElectionKind, ELECTION_ALGORITHMS};
+use crate::ip_parser;
+use crate::keyring::Keyring;
+use crate::log_config::LogConfig;
+use crate::node_identity;
+use crate::retry_policy::RetryPolicy;
+use crate::timeouts::Timeouts;
+
+/// Configuracion con la que se levanta un nodo.
+#[derive(Debug, PartialEq)]
+pub struct NodeConfig {
+    pub node_id: u64,
+    /// Si el ID se derivo de la direccion porque no se configuro uno.
+    pub derived_id: bool,
+    pub priority: u64,
+    pub listen_address: SocketAddr,
+    /// Direccion que se les da a los vecinos para contactar al nodo.
+    pub advertise_address: SocketAddr,
+    pub neighbor_addresses: Vec<SocketAddr>,
+    pub keyring: Keyring,
+    pub auto_repair: bool,
+    pub capture_path: Option<String>,
+    pub replay_path: Option<String>,
+    pub join_address: Option<SocketAddr>,
+    pub data_dir: Option<String>,
+    pub discovery: Option<DiscoveryConfig>,
+    pub logging: LogConfig,
+    pub election: ElectionKind,
+    pub timeouts: Timeouts,
+    pub retry_policy: RetryPolicy,
+}
+
+impl NodeConfig {
+    /// Arma la configuracion a partir de los argumentos de linea de comandos (sin el nombre del programa):
+    /// `[--config <archivo>] [--id <id>] [--priority <prioridad>] [--cluster-key <clave>]
+    /// [--node-key <id>=<clave>]... [--encrypt] [--auto-repair] [--capture <archivo> | --replay <archivo>]
+    /// [--join <miembro>] [--advertise <direccion>] [--data-dir <directorio>] [--discover <cluster> [--discovery-address <ip>]
+    /// [--discovery-ports <desde>-<hasta>]] [--election <algoritmo>] [--log-file <archivo>] [--quiet]
+    /// [--timeout <nombre>=<ms>]... [--retry <nombre>=<valor>]... <listen address> <neighbor 1> <neighbor 2> ...`
+    /// Con `--config` los valores se leen de un archivo TOML, y los argumentos tienen prioridad sobre
+    /// este (ver `ConfigValues::from_file`). Si no se indica un ID se deriva de la direccion anunciada
+    /// (ver `node_identity::default_id`).
+    /// `--encrypt` requiere alguna clave y no se puede combinar con `--capture`.
+    /// Con `--join` no hace falta indicar vecinos: se conocen al entrar al cluster a traves del
+    /// miembro indicado, que queda como vecino.
+    /// Si se escucha en todas las interfaces (`0.0.0.0` o `::`) se anuncia la interfaz por la que se
+    /// llega al primer vecino, salvo que se indique otra direccion con `--advertise`.
+    /// Con `--data-dir` el nodo guarda su cadena en ese directorio y la recupera al reiniciarse.
+    /// Con `--discover` tampoco hacen falta vecinos: se encuentran anunciandose en la red local.
+    /// Si la configuracion tiene errores se informan todos juntos, uno por linea. Si el archivo
+    /// de configuracion no se puede leer o no tiene un formato valido no se valida el resto,
+    /// porque faltaria lo que tenia.
+    pub fn from_args(all_args: &[String]) -> Result<NodeConfig, String> {
+        let (values, mut errors) = ConfigValues::from_args(all_args);
+        let values = match values.config_path.clone() {
+            Some(path) => match ConfigFile::load(&path) {
+                Ok(config_file) => {
+                    let (file_values, file_errors) = ConfigValues::from_file(&config_file);
+                    errors.extend(
+                        file_errors
+                            .into_iter()
+                            .map(|error| format!("{}: {}", path, error)),
+                    );
+                    file_values.overridden_by(values)
+                }
+                Err(file_errors) => {
+                    errors.extend(file_errors);
+                    return Err(errors.join("\n"));
+                }
+            },
+            None => values,
+        };
+        match NodeConfig::from_values(values) {
+            Ok(config) if errors.is_empty() => Ok(config),
+            Ok(_config) => Err(errors.join("\n")),
+            Err(value_errors) => {
+                errors.extend(value_errors);
+                Err(errors.join("\n"))
+            }
+        }
+    }
+
+    /// Valida los valores y arma la configuracion, juntando todos los errores.
+    pub fn from_values(values: ConfigValues) -> Result<NodeConfig, Vec<String>> {
+        let mut errors = Vec::new();
+        let node_id = parse_number("node id", values.node_id, &mut errors);
+        let priority = parse_number("priority", values.priority, &mut errors).unwrap_or(0);
+        let listen_address = match &values.listen_address {
+            Some(address) => ip_parser::parse_listen_address(address)
+                .map_err(|error| errors.push(error))
+                .ok(),
+            None => {
+                errors.push(String::from(
+                    "Required args: listen_address neighbor1 neighbor2. Try: cargo run 6060 127.0.0.1:6061 127.0.0.1:6062",
+                ));
+                None
+            }
+        };
+        // Con una direccion de escucha invalida se validan igual los vecinos, como si fuera IPv4.
+        let local = listen_address.unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0));
+        let has_neighbors = values
+            .neighbors
+            .as_ref()
+            .is_some_and(|neighbors| !neighbors.is_empty());
+        let mut neighbor_addresses = values
+            .neighbors
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|neighbor| {
+                ip_parser::resolve_neighbor(neighbor, &local)
+                    .map_err(|error| errors.push(error))
+                    .ok()
+            })
+            .collect::<Vec<SocketAddr>>();
+        let join_address = values.join.as_ref().and_then(|join| {
+            parse_address("--join", join, &local)
+                .map_err(|error| errors.push(error))
+                .ok()
+        });
+        if let Some(join_address) = join_address {
+            if !neighbor_addresses.contains(&join_address) {
+                neighbor_addresses.push(join_address);
+            }
+        }
+        let advertise_address = match &values.advertise {
+            Some(advertise) => parse_address("--advertise", advertise, &local)
+                .and_then(|address| {
+                    if address.ip().is_unspecified() {
+                        Err(format!(
+                            "Invalid value for --advertise: {} is not a reachable address",
+                            address
+                        ))
+                    } else {
+                        Ok(address)
+                    }
+                })
+                .map_err(|error| errors.push(error))
+                .ok(),
+            None => listen_address.and_then(|listen_address| {
+                ip_parser::reachable_address(&listen_address, neighbor_addresses.first())
+                    .map_err(|error| errors.push(error))
+                    .ok()
+            }),
+        };
+        if let Some(listen_address) = listen_address {
+            for address in neighbor_addresses.iter().chain(advertise_address.iter()) {
+                if !ip_parser::can_reach(&listen_address, address) {
+                    errors.push(format!(
+                        "{} and the listen address {} are of different address families",
+                        address, listen_address
+                    ));
+                }
+            }
+        }
+        let node_keys = values
+            .node_keys
+            .iter()
+            .filter_map(|node_key| {
+                parse_node_key(node_key)
+                    .map_err(|error| errors.push(error))
+                    .ok()
+            })
+            .collect();
+        let mut keyring = Keyring::new(values.cluster_key, node_keys);
+        let encrypt = values.encrypt.unwrap_or(false);
+        if encrypt && !keyring.is_enabled() {
+            errors.push(String::from(
+                "--encrypt requires --cluster-key or --node-key",
+            ));
+        }
+        keyring.set_encryption(encrypt);
+        if values.capture_path.is_some() && values.replay_path.is_some() {
+            errors.push(String::from(
+                "--capture and --replay can't be used together",
+            ));
+        }
+        if values.capture_path.is_some() && encrypt {
+            errors.push(String::from(
+                "--capture can't be used with --encrypt: captures are stored in plain text",
+            ));
+        }
+        let has_discovery_options =
+            values.discovery_address.is_some() || values.discovery_ports.is_some();
+        let discovery_address = values.discovery_address.and_then(|address| {
+            address
+                .parse::<IpAddr>()
+                .map_err(|_error| {
+                    errors.push(format!(
+                        "Invalid value for --discovery-address: {:?}",
+                        address
+                    ))
+                })
+                .ok()
+        });
+        let discovery_ports = values.discovery_ports.and_then(|ports| {
+            discovery_config::parse_ports(&ports)
+                .map_err(|error| errors.push(error))
+                .ok()
+        });
+        let discovery = match values.cluster_name {
+            Some(cluster_name) => {
+                DiscoveryConfig::new(cluster_name, discovery_address, discovery_ports, &local)
+                    .map_err(|error| errors.push(error))
+                    .ok()
+            }
+            None => {
+                if has_discovery_options {
+                    errors.push(String::from(
+                        "--discovery-address and --discovery-ports require --discover",
+                    ));
+                }
+                None
+            }
+        };
+        // Si se indicaron vecinos que no se pudieron resolver ya se informo ese error.
+        if !has_neighbors
+            && values.join.is_none()
+            && discovery.is_none()
+            && values.listen_address.is_some()
+        {
+            errors.push(String::from(
+                "At least one neighbor is required, unless --join or --discover is used",
+            ));
+        }
+        let election = match &values.election {
+            Some(name) => ElectionKind::from_name(name).unwrap_or_else(|| {
+                errors.push(format!(
+                    "Unknown election algorithm {:?}. Available: {}",
+                    name,
+                    ELECTION_ALGORITHMS.join(", ")
+                ));
+                ElectionKind::default()
+            }),
+            None => ElectionKind::default(),
+        };
+        let mut timeouts = Timeouts::default();
+        for timeout in &values.timeouts {
+            if let Err(error) = parse_setting("--timeout", timeout)
+                .and_then(|(name, millis)| timeouts.set(name, millis))
+            {
+                errors.push(error);
+            }
+        }
+        if timeouts.leader_silence <= timeouts.heartbeat_interval {
+            errors.push(format!(
+                "The leader silence timeout must be longer than the heartbeat interval ({:?})",
+                timeouts.heartbeat_interval
+            ));
+        }
+        if timeouts.suspect_after <= timeouts.heartbeat_interval {
+            errors.push(format!(
+                "The suspect timeout must be longer than the heartbeat interval ({:?})",
+                timeouts.heartbeat_interval
+            ));
+        }
+        if timeouts.dead_after <= timeouts.suspect_after {
+            errors.push(format!(
+                "The dead timeout must be longer than the suspect timeout ({:?})",
+                timeouts.suspect_after
+            ));
+        }
+        let mut retry_policy = RetryPolicy::default();
+        for setting in &values.retry {
+            if let Err(error) = parse_setting("--retry", setting)
+                .and_then(|(name, value)| retry_policy.set(name, value))
+            {
+                errors.push(error);
+            }
+        }
+        if retry_policy.initial_backoff > retry_policy.max_backoff {
+            errors.push(String::from(
+                "The initial retry backoff can't be greater than the maximum backoff",
+            ));
+        }
+        let logging = LogConfig {
+            enabled: values.log_enabled.unwrap_or(true),
+            file: values.log_file,
+        };
+        match (listen_address, advertise_address) {
+            (Some(listen_address), Some(advertise_address)) if errors.is_empty() => {
+                Ok(NodeConfig {
+                    node_id: node_id.unwrap_or(node_identity::default_id(&advertise_address)),
+                    derived_id: node_id.is_none(),
+                    priority,
+                    listen_address,
+                    advertise_address,
+                    neighbor_addresses,
+                    keyring,
+                    auto_repair: values.auto_repair.unwrap_or(false),
+                    capture_path: values.capture_path,
+                    replay_path: values.replay_path,
+                    join_address,
+                    data_dir: values.data_dir,
+                    discovery,
+                    logging,
+                    election,
+                    timeouts,
+                    retry_policy,
+                })
+            }
+            _ => Err(errors),
+        }
+    }
+}
+
+/// Resuelve la direccion (`ip:puerto` o `hostname:puerto`) que se paso en `flag`.
+fn parse_address(flag: &str, value: &str, local: &SocketAddr) -> Result<SocketAddr, String> {
+    ip_parser::resolve_neighbor(value, local)
+        .map_err(|error| format!("Invalid value for {}: {}", flag, error))
+}
+
+/// Interpreta un `<id>=<clave>` de `--node-key`.
+fn parse_node_key(value: &str) -> Result<(u64, String), String> {
+    match value.split_once('=') {
+        Some((id, key)) if !key.is_empty() => match id.parse::<u64>() {
+            Ok(id) => Ok((id, key.to_string())),
+            Err(_error) => Err(format!("Invalid node id for --node-key: {:?}", value)),
+        },
+        _ => Err(format!(
+            "Invalid value for --node-key (expected <id>=<key>): {:?}",
+            value
+        )),
+    }
+}
+
+/// Interpreta un `<nombre>=<numero>` de `--timeout` o `--retry`.
+fn parse_setting<'a>(flag: &str, value: &'a str) -> Result<(&'a str, u64), String> {
+    match value.split_once('=') {
+        Some((name, number)) => match number.parse::<u64>() {
+            Ok(number) => Ok((name, number)),
+            Err(_error) => Err(format!("Invalid number for {}: {:?}", flag, value)),
+        },
+        None => Err(format!(
+            "Invalid value for {} (expected <name>=<number>): {:?}",
+            flag, value
+        )),
+    }
+}
+
+fn parse_number(name: &str, value: Option<String>, errors: &mut Vec<String>) -> Option<u64> {
+    let value = value?;
+    match value.parse::<u64>() {
+        Ok(number) => Some(number),
+        Err(_error) => {
+            errors.push(format!("Invalid {}: {:?}", name, value));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        assert_eq!(
+            NodeConfig::from_args(&args(&["6060", "127.0.0.1:6061", "localhost:6062"])),
+            Ok(NodeConfig {
+                node_id: 0x7f00_0001_17ac,
+                derived_id: true,
+                priority: 0,
+                listen_address: "127.0.0.1:6060".parse().unwrap(),
+                advertise_address: "127.0.0.1:6060".parse().unwrap(),
+                neighbor_addresses: vec![
+                    "127.0.0.1:6061".parse().unwrap(),
+                    "127.0.0.1:6062".parse().unwrap()
+                ],
+                keyring: Keyring::default(),
+                auto_repair: false,
+                capture_path: None,
+                replay_path: None,
+                join_address: None,
+                data_dir: None,
+                discovery: None,
+                logging: LogConfig::default(),
+                election: ElectionKind::Bully,
+                timeouts: Timeouts::default(),
+                retry_policy: RetryPolicy::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_mixed_address_families() {
+        let error =
+            NodeConfig::from_args(&args(&["6060", "127.0.0.1:6061", "[::1]:6062"])).unwrap_err();
+        assert!(error.contains("different address families"));
+        let config = NodeConfig::from_args(&args(&["[::]:6060", "127.0.0.1:6061"])).unwrap();
+        assert_eq!(
+            config.neighbor_addresses,
+            vec!["127.0.0.1:6061".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_wildcard_listen_address() {
+        let config = NodeConfig::from_args(&args(&["0.0.0.0:6060", "127.0.0.1:6061"])).unwrap();
+        assert_eq!(config.listen_address, "0.0.0.0:6060".parse().unwrap());
+        assert_eq!(config.advertise_address, "127.0.0.1:6060".parse().unwrap());
+        assert!(NodeConfig::from_args(&args(&["--discover", "lab", "0.0.0.0:6060"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_with_advertise() {
+        let config = NodeConfig::from_args(&args(&[
+            "--discover",
+            "lab",
+            "--advertise",
+            "192.168.0.7:6060",
+            "0.0.0.0:6060",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.advertise_address,
+            "192.168.0.7:6060".parse().unwrap()
+        );
+        assert!(NodeConfig::from_args(&args(&[
+            "--advertise",
+            "0.0.0.0:6060",
+            "0.0.0.0:6060",
+            "127.0.0.1:6061"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_from_args_with_id_and_priority() {
+        let config = NodeConfig::from_args(&args(&[
+            "--id",
+            "3",
+            "6060",
+            "127.0.0.1:6061",
+            "--priority",
+            "2",
+        ]))
+        .unwrap();
+        assert_eq!(config.node_id, 3);
+        assert_eq!(config.priority, 2);
+        assert_eq!(
+            config.neighbor_addresses,
+            vec!["127.0.0.1:6061".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_keys() {
+        let config = NodeConfig::from_args(&args(&[
+            "--cluster-key",
+            "secret",
+            "--node-key",
+            "3=three",
+            "6060",
+            "127.0.0.1:6061",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.keyring,
+            Keyring::new(
+                Some(String::from("secret")),
+                vec![(3, String::from("three"))]
+            )
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_encryption() {
+        let config = NodeConfig::from_args(&args(&[
+            "--cluster-key",
+            "secret",
+            "--encrypt",
+            "6060",
+            "127.0.0.1:6061",
+        ]))
+        .unwrap();
+        assert!(config.keyring.encrypts());
+    }
+
+    #[test]
+    fn test_from_args_with_auto_repair() {
+        let config =
+            NodeConfig::from_args(&args(&["6060", "--auto-repair", "127.0.0.1:6061"])).unwrap();
+        assert!(config.auto_repair);
+        assert_eq!(
+            config.neighbor_addresses,
+            vec!["127.0.0.1:6061".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_from_args_with_capture_and_replay() {
+        let config =
+            NodeConfig::from_args(&args(&["--capture", "n0.cap", "6060", "127.0.0.1:6061"]))
+                .unwrap();
+        assert_eq!(config.capture_path, Some(String::from("n0.cap")));
+        let config =
+            NodeConfig::from_args(&args(&["--replay", "n0.cap", "6060", "127.0.0.1:6061"]))
+                .unwrap();
+        assert_eq!(config.replay_path, Some(String::from("n0.cap")));
+        assert!(NodeConfig::from_args(&args(&[
+            "--capture",
+            "a.cap",
+            "--replay",
+            "b.cap",
+            "6060",
+            "127.0.0.1:6061"
+        ]))
+        .is_err());
+        assert!(NodeConfig::from_args(&args(&["6060", "127.0.0.1:6061", "--capture"])).is_err());
+        assert!(NodeConfig::from_args(&args(&[
+            "--cluster-key",
+            "secret",
+            "--encrypt",
+            "--capture",
+            "n0.cap",
+            "6060",
+            "127.0.0.1:6061"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_from_args_with_join() {
+        let config = NodeConfig::from_args(&args(&["--join", "127.0.0.1:6060", "6063"])).unwrap();
+        let contact = "127.0.0.1:6060".parse().unwrap();
+        assert_eq!(config.join_address, Some(contact));
+        assert_eq!(config.neighbor_addresses, vec![contact]);
+        assert!(NodeConfig::from_args(&args(&["--join", "127.0.0.1:6060"])).is_err());
+        let error = NodeConfig::from_args(&args(&["--join", "127.0.0.1", "6063"])).unwrap_err();
+        assert!(error.starts_with("Invalid value for --join"), "{}", error);
+    }
+
+    #[test]
+    fn test_from_args_with_data_dir() {
+        let config =
+            NodeConfig::from_args(&args(&["--data-dir", "data", "6060", "127.0.0.1:6061"]))
+                .unwrap();
+        assert_eq!(config.data_dir, Some(String::from("data")));
+        assert!(NodeConfig::from_args(&args(&["6060", "127.0.0.1:6061", "--data-dir"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_with_discovery() {
+        let config = NodeConfig::from_args(&args(&[
+            "--discover",
+            "lab",
+            "--discovery-ports",
+            "7000-7009",
+            "7001",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.discovery,
+            Some(DiscoveryConfig {
+                cluster_name: String::from("lab"),
+                address: "127.0.0.1".parse().unwrap(),
+                ports: (7000, 7009),
+            })
+        );
+        assert!(config.neighbor_addresses.is_empty());
+        assert!(NodeConfig::from_args(&args(&["--discovery-ports", "7000-7009", "7001"])).is_err());
+        assert!(NodeConfig::from_args(&args(&[
+            "--discover",
+            "lab",
+            "--discovery-address",
+            "nope",
+            "7001"
+        ]))
+        .is_err());
+    }
+
+    fn config_file(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir()
+            .join(format!("{}-{}.toml", name, std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_from_args_with_config_file() {
+        let path = config_file(
+            "node-config",
+            "[node]\n\
+             id = 1\n\
+             priority = 2\n\
+             listen = \"127.0.0.1:6060\"\n\
+             peers = [\"127.0.0.1:6061\", \"127.0.0.1:6062\"]\n\
+             [algorithms]\n\
+             election = \"ring\"\n\
+             [logging]\n\
+             file = \"node.log\"\n\
+             [security]\n\
+             cluster_key = \"secret\"\n\
+             encrypt = true\n",
+        );
+        let config = NodeConfig::from_args(&args(&["--config", &path, "--priority", "5"])).unwrap();
+        assert_eq!(config.node_id, 1);
+        assert_eq!(config.priority, 5);
+        assert_eq!(config.listen_address, "127.0.0.1:6060".parse().unwrap());
+        assert_eq!(config.neighbor_addresses.len(), 2);
+        assert_eq!(config.logging.file, Some(String::from("node.log")));
+        assert_eq!(config.election, ElectionKind::Ring);
+        assert!(config.keyring.encrypts());
+
+        let config =
+            NodeConfig::from_args(&args(&["--config", &path, "6070", "127.0.0.1:6071"])).unwrap();
+        assert_eq!(config.listen_address, "127.0.0.1:6070".parse().unwrap());
+        assert_eq!(
+            config.neighbor_addresses,
+            vec!["127.0.0.1:6071".parse().unwrap()]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_from_args_reports_every_error() {
+        let path = config_file(
+            "node-config-errors",
+            "[node]\nid = \"uno\"\ncolor = 1\n[algorithms]\nelection = \"raft\"\n",
+        );
+        let error = NodeConfig::from_args(&args(&[
+            "--config",
+            &path,
+            "--priority",
+            "x",
+            "--encrypt",
+            "6060",
+            "not an address",
+        ]))
+        .unwrap_err();
+        let errors = error.lines().collect::<Vec<&str>>();
+        assert_eq!(errors.len(), 6, "{}", error);
+        assert!(errors[0].ends_with("line 2: node.id: expected an integer, found a string"));
+        assert!(errors[1].ends_with("line 3: node.color: unknown setting"));
+        assert!(errors[2].starts_with("Invalid priority"));
+        assert!(errors[3].starts_with("Couldn't resolve"));
+        assert!(errors[4].starts_with("--encrypt requires"));
+        assert!(errors[5].starts_with("Unknown election algorithm"));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_from_args_timeouts_and_retry() {
+        let path = config_file(
+            "node-config-timeouts",
+            "[timeouts]\nok_acquire_ms = 2000\nok_election_ms = 300\nheartbeat_interval_ms = 200\n\
+             anti_entropy_interval_ms = 8000\n\
+             [retry]\nmax_attempts = 3\ndeadline_ms = 20_000\njoin_attempts = 5\n\
+             election_rounds = 2\n",
+        );
+        let config = NodeConfig::from_args(&args(&[
+            "--config",
+            &path,
+            "--timeout",
+            "ok_acquire_ms=4000",
+            "--retry",
+            "initial_backoff_ms=100",
+            "6060",
+            "127.0.0.1:6061",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.timeouts,
+            Timeouts {
+                ok_acquire: Duration::from_secs(4),
+                ok_election: Duration::from_millis(300),
+                heartbeat_interval: Duration::from_millis(200),
+                anti_entropy_interval: Duration::from_secs(8),
+                ..Timeouts::default()
+            }
+        );
+        assert_eq!(
+            config.retry_policy,
+            RetryPolicy {
+                max_attempts: 3,
+                initial_backoff: Duration::from_millis(100),
+                deadline: Duration::from_secs(20),
+                join_attempts: 5,
+                election_rounds: 2,
+                ..RetryPolicy::default()
+            }
+        );
+        std::fs::remove_file(path).unwrap();
+
+        let error = NodeConfig::from_args(&args(&[
+            "--timeout",
+            "ok_acquire_ms",
+            "--timeout",
+            "ok_release_ms=0",
+            "--timeout",
+            "leader_silence_ms=500",
+            "--retry",
+            "initial_backoff_ms=9000",
+            "6060",
+            "127.0.0.1:6061",
+        ]))
+        .unwrap_err();
+        let errors = error.lines().collect::<Vec<&str>>();
+        assert_eq!(errors.len(), 4, "{}", error);
+        assert!(errors[0].starts_with("Invalid value for --timeout"));
+        assert!(errors[1].starts_with("Timeout ok_release_ms must be greater than 0"));
+        assert!(errors[2].starts_with("The leader silence timeout"));
+        assert!(errors[3].starts_with("The initial retry backoff"));
+    }
+
+    #[test]
+    fn test_from_args_failure_detector_timeouts_are_checked() {
+        let path = config_file(
+            "node-config-detector",
+            "[timeouts]\nheartbeat_interval_ms = 2000\nsuspect_after_ms = 1800\n\
+             dead_after_ms = 1800\n",
+        );
+        let error = NodeConfig::from_args(&args(&["--config", &path, "6060", "127.0.0.1:6061"]))
+            .unwrap_err();
+        let errors = error.lines().collect::<Vec<&str>>();
+        assert_eq!(errors.len(), 3, "{}", error);
+        assert!(errors[0].starts_with("The leader silence timeout"));
+        assert!(errors[1].starts_with("The suspect timeout"));
+        assert!(errors[2].starts_with("The dead timeout"));
+
+        let config = NodeConfig::from_args(&args(&[
+            "--config",
+            &path,
+            "--timeout",
+            "heartbeat_interval_ms=100",
+            "--timeout",
+            "dead_after_ms=3600",
+            "6060",
+            "127.0.0.1:6061",
+        ]))
+        .unwrap();
+        assert_eq!(
+            config.timeouts.heartbeat_interval,
+            Duration::from_millis(100)
+        );
+        assert_eq!(config.timeouts.suspect_after, Duration::from_millis(1800));
+        assert_eq!(config.timeouts.dead_after, Duration::from_millis(3600));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_from_args_missing_config_file() {
+        let error = NodeConfig::from_args(&args(&["--config", "/nonexistent/node.toml"]))
+            .err()
+            .unwrap();
+        assert_eq!(error.lines().count(), 1);
+        assert!(error.contains("/nonexistent/node.toml"));
+    }
+
+    #[test]
+    fn test_from_args_reports_only_config_file_syntax_errors() {
+        let path = config_file("node-config-syntax", "[node\nlisten = \"6060\"\n");
+        let error = NodeConfig::from_args(&args(&["--config", &path]))
+            .err()
+            .unwrap();
+        assert_eq!(error.lines().count(), 1);
+        assert!(error.starts_with(&path));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_from_args_encryption_requires_key() {
+        assert!(NodeConfig::from_args(&args(&["--encrypt", "6060", "127.0.0.1:6061"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_invalid_node_key() {
+        assert!(
+            NodeConfig::from_args(&args(&["--node-key", "3", "6060", "127.0.0.1:6061"])).is_err()
+        );
+        assert!(
+            NodeConfig::from_args(&args(&["--node-key", "x=k", "6060", "127.0.0.1:6061"])).is_err()
+        );
+    }
+
+    #[test]
+    fn test_from_args_invalid_id() {
+        assert!(NodeConfig::from_args(&args(&["--id", "x", "6060", "127.0.0.1:6061"])).is_err());
+        assert!(NodeConfig::from_args(&args(&["6060", "127.0.0.1:6061", "--id"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_missing_neighbors() {
+        assert!(NodeConfig::from_args(&args(&["6060"])).is_err());
+    }
+
+    #[test]
+    fn test_from_args_invalid_neighbor() {
+        assert!(NodeConfig::from_args(&args(&["6060", "not an address"])).is_err());
+    }
+}
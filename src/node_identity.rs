@@ -0,0 +1,81 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::net::{IpAddr, SocketAddr};
+
+/// Identidad de un nodo: su ID numerico configurado y una prioridad opcional.
+/// Los nodos se ordenan primero por prioridad y luego por ID; el mayor gana las elecciones.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+pub struct NodeIdentity {
+    pub priority: u64,
+    pub id: u64,
+}
+
+impl NodeIdentity {
+    pub fn new(id: u64, priority: u64) -> NodeIdentity {
+        NodeIdentity { priority, id }
+    }
+}
+
+impl fmt::Display for NodeIdentity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(p{})", self.id, self.priority)
+    }
+}
+
+/// ID que se usa cuando no se configura uno, derivado de la direccion anunciada del nodo.
+/// Para IPv4 combina la IP y el puerto, asi que es unico; para IPv6 se pliega la IP a 48 bits.
+/// Entre nodos de una misma IP el orden de los IDs es el de los puertos.
+pub fn default_id(address: &SocketAddr) -> u64 {
+    let ip = match address.ip() {
+        IpAddr::V4(ip) => u32::from(ip) as u64,
+        IpAddr::V6(ip) => {
+            let bits = u128::from(ip);
+            let folded = (bits >> 64) as u64 ^ bits as u64;
+            (folded >> 48 ^ folded >> 32 ^ folded >> 16 ^ folded) & 0xffff_ffff_ffff
+        }
+    };
+    ip << 16 | address.port() as u64
+}
+
+/// Representacion de un nodo en los mensajes: `<id>,<prioridad>,<direccion>`.
+pub fn format_node(identity: &NodeIdentity, address: &SocketAddr) -> String {
+    format!("{},{},{}", identity.id, identity.priority, address)
+}
+
+/// Interpreta un `<id>,<prioridad>,<direccion>`.
+pub fn parse_node(node: &str) -> Option<(NodeIdentity, SocketAddr)> {
+    match node.split(',').collect::<Vec<&str>>()[..] {
+        [id, priority, address] => Some((
+            NodeIdentity::new(id.parse().ok()?, priority.parse().ok()?),
+            address.parse().ok()?,
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_higher_id_wins_with_same_priority() {
+        assert!(NodeIdentity::new(2, 0) > NodeIdentity::new(1, 0));
+    }
+
+    #[test]
+    fn test_priority_wins_over_id() {
+        assert!(NodeIdentity::new(1, 5) > NodeIdentity::new(9, 0));
+    }
+
+    #[test]
+    fn test_default_id_uses_ip_and_port() {
+        let first = default_id(&"127.0.0.1:6060".parse().unwrap());
+        assert_eq!(first, 0x7f00_0001_17ac);
+        assert!(default_id(&"127.0.0.1:6061".parse().unwrap()) > first);
+        assert_ne!(default_id(&"127.0.0.2:6060".parse().unwrap()), first);
+        assert_ne!(
+            default_id(&"[::1]:6060".parse().unwrap()),
+            default_id(&"[::2]:6060".parse().unwrap())
+        );
+    }
+}
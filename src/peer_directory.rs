@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use crate::node_identity::NodeIdentity;
+
+/// Directorio de los nodos conocidos: para cada ID, su identidad completa y la direccion
+/// desde la que se lo escucho por ultima vez.
+#[derive(Default)]
+pub struct PeerDirectory {
+    peers: HashMap<u64, (NodeIdentity, SocketAddr)>,
+}
+
+impl PeerDirectory {
+    pub fn record(&mut self, identity: NodeIdentity, address: SocketAddr) {
+        self.peers.insert(identity.id, (identity, address));
+    }
+
+    pub fn address_of(&self, id: u64) -> Option<SocketAddr> {
+        self.peers.get(&id).map(|(_, address)| *address)
+    }
+
+    pub fn identity_of(&self, id: u64) -> Option<NodeIdentity> {
+        self.peers.get(&id).map(|(identity, _)| *identity)
+    }
+
+    pub fn identity_at(&self, address: &SocketAddr) -> Option<NodeIdentity> {
+        self.peers
+            .values()
+            .find(|(_, peer_address)| peer_address == address)
+            .map(|(identity, _)| *identity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_lookup() {
+        let mut directory = PeerDirectory::default();
+        let address = "127.0.0.1:6060".parse().unwrap();
+        directory.record(NodeIdentity::new(7, 1), address);
+
+        assert_eq!(directory.address_of(7), Some(address));
+        assert_eq!(directory.address_of(8), None);
+        assert_eq!(directory.identity_of(7), Some(NodeIdentity::new(7, 1)));
+        assert_eq!(
+            directory.identity_at(&address),
+            Some(NodeIdentity::new(7, 1))
+        );
+    }
+
+    #[test]
+    fn test_record_updates_address() {
+        let mut directory = PeerDirectory::default();
+        directory.record(NodeIdentity::new(7, 0), "127.0.0.1:6060".parse().unwrap());
+        directory.record(NodeIdentity::new(7, 0), "10.0.0.7:6060".parse().unwrap());
+
+        assert_eq!(
+            directory.address_of(7),
+            Some("10.0.0.7:6060".parse().unwrap())
+        );
+    }
+}
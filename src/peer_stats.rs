@@ -0,0 +1,85 @@
+use std::time::{Duration, Instant};
+
+/// Estadisticas de heartbeats de un vecino: cuando se lo escucho por ultima vez y los RTT medidos.
+#[derive(Debug, Clone, Default)]
+pub struct PeerStats {
+    pub last_heard: Option<Instant>,
+    pub pending_ping: Option<(u64, Instant)>,
+    pub pings_sent: u64,
+    pub pongs_received: u64,
+    pub last_rtt: Option<Duration>,
+    pub min_rtt: Option<Duration>,
+    pub max_rtt: Option<Duration>,
+    total_rtt: Duration,
+}
+
+impl PeerStats {
+    /// RTT promedio de todos los Pong recibidos.
+    pub fn avg_rtt(&self) -> Option<Duration> {
+        if self.pongs_received == 0 {
+            return None;
+        }
+        Some(self.total_rtt / self.pongs_received as u32)
+    }
+
+    pub fn record_heard(&mut self, now: Instant) {
+        self.last_heard = Some(now);
+    }
+
+    pub fn record_ping(&mut self, seq: u64, now: Instant) {
+        self.pings_sent += 1;
+        self.pending_ping = Some((seq, now));
+    }
+
+    /// Registra un Pong. Solo se mide el RTT si corresponde al ultimo Ping enviado.
+    pub fn record_pong(&mut self, seq: u64, now: Instant) {
+        self.record_heard(now);
+        match self.pending_ping {
+            Some((pending_seq, sent_at)) if pending_seq == seq => {
+                let rtt = now.duration_since(sent_at);
+                self.pending_ping = None;
+                self.pongs_received += 1;
+                self.total_rtt += rtt;
+                self.last_rtt = Some(rtt);
+                self.min_rtt = Some(self.min_rtt.map_or(rtt, |min| min.min(rtt)));
+                self.max_rtt = Some(self.max_rtt.map_or(rtt, |max| max.max(rtt)));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtt_statistics() {
+        let start = Instant::now();
+        let mut stats = PeerStats::default();
+        stats.record_ping(1, start);
+        stats.record_pong(1, start + Duration::from_millis(10));
+        stats.record_ping(2, start + Duration::from_millis(100));
+        stats.record_pong(2, start + Duration::from_millis(130));
+
+        assert_eq!(stats.pings_sent, 2);
+        assert_eq!(stats.pongs_received, 2);
+        assert_eq!(stats.last_rtt, Some(Duration::from_millis(30)));
+        assert_eq!(stats.min_rtt, Some(Duration::from_millis(10)));
+        assert_eq!(stats.max_rtt, Some(Duration::from_millis(30)));
+        assert_eq!(stats.avg_rtt(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn test_stale_pong_does_not_count_as_rtt() {
+        let start = Instant::now();
+        let mut stats = PeerStats::default();
+        stats.record_ping(1, start);
+        stats.record_ping(2, start + Duration::from_millis(100));
+        stats.record_pong(1, start + Duration::from_millis(110));
+
+        assert_eq!(stats.pongs_received, 0);
+        assert_eq!(stats.avg_rtt(), None);
+        assert_eq!(stats.last_heard, Some(start + Duration::from_millis(110)));
+    }
+}
@@ -0,0 +1,24 @@
+use std::fmt;
+use std::fmt::Formatter;
+
+/// Estado de un vecino segun el detector de fallas.
+/// - **Alive**: se recibio algun mensaje suyo recientemente.
+/// - **Suspected**: hace un tiempo que no se sabe nada de el, pero todavia no se lo da por caido.
+/// - **Dead**: se lo considera fuera de servicio.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PeerStatus {
+    Alive,
+    Suspected,
+    Dead,
+}
+
+impl fmt::Display for PeerStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let status = match self {
+            PeerStatus::Alive => "alive",
+            PeerStatus::Suspected => "suspected",
+            PeerStatus::Dead => "dead",
+        };
+        write!(f, "{}", status)
+    }
+}
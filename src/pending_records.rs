@@ -0,0 +1,79 @@
+use std::collections::BTreeMap;
+
+use crate::blockchain::BlockchainRecord;
+
+/// Cantidad maxima de registros fuera de orden que se guardan a la espera de los que faltan.
+const MAX_PENDING_RECORDS: usize = 256;
+
+/// Registros recibidos del coordinador que todavia no se pueden agregar porque faltan los
+/// anteriores. Para cada posicion se guarda el hash del registro anterior y el registro.
+#[derive(Default)]
+pub struct PendingRecords {
+    records: BTreeMap<u64, (u64, BlockchainRecord)>,
+}
+
+impl PendingRecords {
+    /// Guarda el registro. Devuelve `false` si ya hay demasiados registros esperando.
+    pub fn insert(&mut self, index: u64, previous_hash: u64, record: BlockchainRecord) -> bool {
+        if self.records.len() >= MAX_PENDING_RECORDS && !self.records.contains_key(&index) {
+            return false;
+        }
+        self.records.insert(index, (previous_hash, record));
+        true
+    }
+
+    /// Saca el registro que va en la posicion `height`, descartando los de posiciones anteriores
+    /// (que ya se agregaron por otro medio).
+    pub fn take_next(&mut self, height: u64) -> Option<(u64, BlockchainRecord)> {
+        self.records = self.records.split_off(&height);
+        self.records.remove(&height)
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(name: &str) -> BlockchainRecord {
+        BlockchainRecord {
+            student_name: String::from(name),
+            grade: 7.0,
+            hash: 0,
+        }
+    }
+
+    #[test]
+    fn test_take_next_returns_records_in_order() {
+        let mut pending = PendingRecords::default();
+        pending.insert(3, 30, record("c"));
+        pending.insert(2, 20, record("b"));
+        assert_eq!(pending.take_next(1), None);
+        assert_eq!(pending.take_next(2), Some((20, record("b"))));
+        assert_eq!(pending.take_next(3), Some((30, record("c"))));
+        assert_eq!(pending.len(), 0);
+    }
+
+    #[test]
+    fn test_take_next_drops_stale_records() {
+        let mut pending = PendingRecords::default();
+        pending.insert(2, 20, record("b"));
+        pending.insert(5, 50, record("e"));
+        assert_eq!(pending.take_next(4), None);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending.take_next(5), Some((50, record("e"))));
+    }
+
+    #[test]
+    fn test_insert_is_bounded() {
+        let mut pending = PendingRecords::default();
+        for index in 0..MAX_PENDING_RECORDS as u64 {
+            assert!(pending.insert(index, 0, record("a")));
+        }
+        assert!(!pending.insert(MAX_PENDING_RECORDS as u64, 0, record("a")));
+        assert!(pending.insert(0, 0, record("b")));
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Estado de la reconciliacion (anti-entropia) de la cadena propia con la de un vecino.
+///
+/// Cuando se detecta que el hash de la cadena propia a cierta altura no coincide con el de un
+/// vecino, se busca el punto de divergencia por busqueda binaria: `agree` es la mayor altura
+/// conocida en la que ambas cadenas coinciden (a altura 0 siempre coinciden) y `disagree` la menor
+/// en la que difieren. Al encontrarlo se consulta a todos los vecinos el hash a la altura
+/// `disagree` y se decide por mayoria que version conservar.
+pub struct Reconciliation {
+    peer: Option<SocketAddr>,
+    agree: u64,
+    disagree: u64,
+    votes: Option<HashMap<SocketAddr, u64>>,
+    touched_at: Instant,
+    pub exchanges: u64,
+    pub divergences: u64,
+    pub repairs: u64,
+    pub discarded_records: u64,
+}
+
+impl Reconciliation {
+    pub fn new(now: Instant) -> Reconciliation {
+        Reconciliation {
+            peer: None,
+            agree: 0,
+            disagree: 0,
+            votes: None,
+            touched_at: now,
+            exchanges: 0,
+            divergences: 0,
+            repairs: 0,
+            discarded_records: 0,
+        }
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.peer.is_none()
+    }
+
+    pub fn is_voting(&self) -> bool {
+        self.votes.is_some()
+    }
+
+    /// Empieza a buscar el punto de divergencia con `peer`, sabiendo que a la altura
+    /// `disagree` las cadenas difieren.
+    pub fn start(&mut self, peer: SocketAddr, disagree: u64, now: Instant) {
+        self.peer = Some(peer);
+        self.agree = 0;
+        self.disagree = disagree;
+        self.votes = None;
+        self.touched_at = now;
+        self.divergences += 1;
+    }
+
+    /// Registra si el hash de `peer` a la altura `height` coincide con el propio.
+    /// Se ignoran (devolviendo `false`) las respuestas de otros vecinos o fuera del intervalo
+    /// que se esta buscando.
+    pub fn record_probe(
+        &mut self,
+        peer: SocketAddr,
+        height: u64,
+        agrees: bool,
+        now: Instant,
+    ) -> bool {
+        if self.peer != Some(peer) || self.is_voting() {
+            return false;
+        }
+        if height <= self.agree || height >= self.disagree {
+            return false;
+        }
+        if agrees {
+            self.agree = height;
+        } else {
+            self.disagree = height;
+        }
+        self.touched_at = now;
+        true
+    }
+
+    pub fn peer(&self) -> Option<SocketAddr> {
+        self.peer
+    }
+
+    /// Proxima altura a consultar, o `None` si ya se encontro el punto de divergencia.
+    pub fn next_probe(&self) -> Option<u64> {
+        if self.disagree - self.agree > 1 {
+            Some(self.agree + (self.disagree - self.agree) / 2)
+        } else {
+            None
+        }
+    }
+
+    /// Altura hasta la que ambas cadenas coinciden.
+    pub fn common_height(&self) -> u64 {
+        self.agree
+    }
+
+    /// Primera altura en la que las cadenas difieren.
+    pub fn divergent_height(&self) -> u64 {
+        self.disagree
+    }
+
+    pub fn start_voting(&mut self, now: Instant) {
+        self.votes = Some(HashMap::new());
+        self.touched_at = now;
+    }
+
+    pub fn record_vote(&mut self, peer: SocketAddr, height: u64, hash: u64, now: Instant) {
+        if height != self.disagree {
+            return;
+        }
+        if let Some(votes) = self.votes.as_mut() {
+            votes.insert(peer, hash);
+            self.touched_at = now;
+        }
+    }
+
+    pub fn vote_count(&self) -> usize {
+        self.votes.as_ref().map_or(0, |votes| votes.len())
+    }
+
+    /// Hash con mas votos a la altura de divergencia, contando el propio (si se tiene un registro
+    /// a esa altura). En caso de empate se prefiere la version propia.
+    pub fn majority(&self, own_hash: Option<u64>) -> Option<u64> {
+        let mut tally: HashMap<u64, usize> = HashMap::new();
+        if let Some(own_hash) = own_hash {
+            *tally.entry(own_hash).or_insert(0) += 1;
+        }
+        for hash in self.votes.iter().flat_map(|votes| votes.values()) {
+            *tally.entry(*hash).or_insert(0) += 1;
+        }
+        let best = *tally.values().max()?;
+        if let Some(own_hash) = own_hash {
+            if tally.get(&own_hash) == Some(&best) {
+                return Some(own_hash);
+            }
+        }
+        tally
+            .into_iter()
+            .filter(|(_, count)| *count == best)
+            .map(|(hash, _)| hash)
+            .min()
+    }
+
+    /// Vecinos que votaron por `hash`.
+    pub fn voters_for(&self, hash: u64) -> Vec<SocketAddr> {
+        let mut voters = self
+            .votes
+            .iter()
+            .flat_map(|votes| votes.iter())
+            .filter(|(_, vote)| **vote == hash)
+            .map(|(peer, _)| *peer)
+            .collect::<Vec<SocketAddr>>();
+        voters.sort();
+        voters
+    }
+
+    pub fn is_timed_out(&self, now: Instant, timeout: Duration) -> bool {
+        !self.is_idle() && now.saturating_duration_since(self.touched_at) >= timeout
+    }
+
+    /// Termina la reconciliacion en curso, conservando las metricas.
+    pub fn reset(&mut self) {
+        self.peer = None;
+        self.votes = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn test_binary_search_finds_divergence_point() {
+        let now = Instant::now();
+        let mut reconciliation = Reconciliation::new(now);
+        reconciliation.start(peer(1), 16, now);
+        // Las cadenas coinciden hasta la altura 5 y difieren desde la 6.
+        while let Some(height) = reconciliation.next_probe() {
+            reconciliation.record_probe(peer(1), height, height <= 5, now);
+        }
+        assert_eq!(reconciliation.common_height(), 5);
+        assert_eq!(reconciliation.divergent_height(), 6);
+        assert_eq!(reconciliation.divergences, 1);
+    }
+
+    #[test]
+    fn test_probes_from_other_peers_are_ignored() {
+        let now = Instant::now();
+        let mut reconciliation = Reconciliation::new(now);
+        reconciliation.start(peer(1), 8, now);
+        assert!(!reconciliation.record_probe(peer(2), 4, true, now));
+        assert_eq!(reconciliation.common_height(), 0);
+        assert!(reconciliation.record_probe(peer(1), 4, true, now));
+        assert_eq!(reconciliation.common_height(), 4);
+    }
+
+    #[test]
+    fn test_majority_vote() {
+        let now = Instant::now();
+        let mut reconciliation = Reconciliation::new(now);
+        reconciliation.start(peer(1), 3, now);
+        reconciliation.start_voting(now);
+        reconciliation.record_vote(peer(1), 3, 20, now);
+        reconciliation.record_vote(peer(2), 3, 20, now);
+        reconciliation.record_vote(peer(3), 2, 10, now);
+        assert_eq!(reconciliation.vote_count(), 2);
+        assert_eq!(reconciliation.majority(Some(10)), Some(20));
+        assert_eq!(reconciliation.voters_for(20), vec![peer(1), peer(2)]);
+    }
+
+    #[test]
+    fn test_majority_tie_keeps_own_version() {
+        let now = Instant::now();
+        let mut reconciliation = Reconciliation::new(now);
+        reconciliation.start(peer(1), 3, now);
+        reconciliation.start_voting(now);
+        reconciliation.record_vote(peer(1), 3, 20, now);
+        assert_eq!(reconciliation.majority(Some(10)), Some(10));
+        assert_eq!(reconciliation.majority(None), Some(20));
+    }
+
+    #[test]
+    fn test_timeout() {
+        let now = Instant::now();
+        let mut reconciliation = Reconciliation::new(now);
+        let timeout = Duration::from_secs(2);
+        assert!(!reconciliation.is_timed_out(now + timeout, timeout));
+        reconciliation.start(peer(1), 3, now);
+        assert!(reconciliation.is_timed_out(now + timeout, timeout));
+        reconciliation.reset();
+        assert!(reconciliation.is_idle());
+    }
+}
@@ -0,0 +1,72 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::blockchain_node::BlockchainNode;
+use crate::capture_record::CaptureRecord;
+use crate::fault_direction::FaultDirection;
+use crate::logger::log;
+use crate::memory_network::MemoryNetwork;
+use crate::message_capture::MessageCapture;
+use crate::node_config::NodeConfig;
+use crate::virtual_clock::VirtualClock;
+
+/// Reproduce offline los mensajes recibidos en un archivo de captura sobre un nodo armado con
+/// la misma configuracion que el que lo grabo. El nodo usa una red en memoria (lo que envia no
+/// llega a nadie) y un reloj virtual que avanza segun los timestamps de la captura.
+/// Al terminar muestra la cadena y el estado de los vecinos del nodo.
+pub fn replay(mut config: NodeConfig, path: &str) -> Result<(), String> {
+    // La reproduccion no tiene que pisar la cadena que guardo el nodo real.
+    config.data_dir = None;
+    let records = MessageCapture::load(path)?;
+    let network = MemoryNetwork::new();
+    let clock = Arc::new(VirtualClock::new());
+    let transport = Arc::new(MemoryNetwork::bind(&network, config.listen_address));
+    let node = Arc::new(Mutex::new(BlockchainNode::with_transport(
+        config,
+        transport,
+        clock.clone(),
+    )));
+    let replayed = replay_records(&node, &clock, &records);
+    let captured_sent = records
+        .iter()
+        .filter(|record| record.direction == FaultDirection::Send)
+        .count();
+    println!(
+        "Replayed {} received messages. The node sent {} messages ({} in the capture)",
+        replayed,
+        network.in_flight(),
+        captured_sent
+    );
+    let node = node.lock().unwrap();
+    node.print();
+    node.print_peers();
+    Ok(())
+}
+
+/// Le pasa al nodo, en orden, los mensajes recibidos de la captura. Devuelve cuantos fueron.
+pub fn replay_records(
+    node: &Arc<Mutex<BlockchainNode>>,
+    clock: &VirtualClock,
+    records: &[CaptureRecord],
+) -> usize {
+    let mut previous = None;
+    let mut replayed = 0;
+    for record in records
+        .iter()
+        .filter(|record| record.direction == FaultDirection::Receive)
+    {
+        if let Some(previous) = previous {
+            clock.advance(Duration::from_millis(
+                record.timestamp.saturating_sub(previous),
+            ));
+        }
+        previous = Some(record.timestamp);
+        log(format!("Replaying {}", record.as_string()));
+        // Antes del siguiente se espera a que el handler termine o quede esperando otro mensaje
+        // (por ejemplo el RELEASE de un lock), sin dejar pasar el tiempo virtual.
+        BlockchainNode::replay_message(node.clone(), record);
+        clock.wait_until_idle();
+        replayed += 1;
+    }
+    replayed
+}
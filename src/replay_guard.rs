@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use crate::message_nonce::MessageNonce;
+
+/// Cantidad de mensajes hacia atras que se aceptan fuera de orden.
+const WINDOW_SIZE: u64 = 64;
+
+/// Proteccion contra mensajes repetidos. Para cada emisor recuerda su sesion mas reciente,
+/// el mayor contador recibido y cuales de los ultimos `WINDOW_SIZE` contadores ya llegaron.
+/// Los mensajes de sesiones anteriores se rechazan.
+#[derive(Default)]
+pub struct ReplayGuard {
+    senders: HashMap<u64, (u64, u64, u64)>,
+}
+
+impl ReplayGuard {
+    /// Devuelve `true` y registra el nonce si es la primera vez que se lo ve.
+    pub fn check_and_record(&mut self, sender_id: u64, nonce: MessageNonce) -> bool {
+        let (session, highest, seen) =
+            self.senders
+                .entry(sender_id)
+                .or_insert((nonce.session, nonce.counter, 0));
+        if nonce.session < *session {
+            return false;
+        }
+        if nonce.session > *session {
+            *session = nonce.session;
+            *highest = nonce.counter;
+            *seen = 1;
+            return true;
+        }
+        if nonce.counter > *highest {
+            let shift = nonce.counter - *highest;
+            *seen = if shift >= WINDOW_SIZE {
+                0
+            } else {
+                *seen << shift
+            };
+            *seen |= 1;
+            *highest = nonce.counter;
+            return true;
+        }
+        let age = *highest - nonce.counter;
+        if age >= WINDOW_SIZE || *seen & (1 << age) != 0 {
+            return false;
+        }
+        *seen |= 1 << age;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nonce(session: u64, counter: u64) -> MessageNonce {
+        MessageNonce { session, counter }
+    }
+
+    #[test]
+    fn test_rejects_repeated_messages() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.check_and_record(1, nonce(10, 1)));
+        assert!(guard.check_and_record(1, nonce(10, 2)));
+        assert!(!guard.check_and_record(1, nonce(10, 2)));
+        assert!(!guard.check_and_record(1, nonce(10, 1)));
+    }
+
+    #[test]
+    fn test_accepts_reordered_messages_inside_the_window() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.check_and_record(1, nonce(10, 5)));
+        assert!(guard.check_and_record(1, nonce(10, 3)));
+        assert!(!guard.check_and_record(1, nonce(10, 3)));
+        assert!(guard.check_and_record(1, nonce(10, 4)));
+        assert!(guard.check_and_record(1, nonce(10, 200)));
+        assert!(!guard.check_and_record(1, nonce(10, 100)));
+    }
+
+    #[test]
+    fn test_new_session_replaces_old_one() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.check_and_record(1, nonce(10, 5)));
+        assert!(guard.check_and_record(1, nonce(20, 1)));
+        assert!(!guard.check_and_record(1, nonce(10, 6)));
+    }
+
+    #[test]
+    fn test_senders_are_independent() {
+        let mut guard = ReplayGuard::default();
+        assert!(guard.check_and_record(1, nonce(10, 1)));
+        assert!(guard.check_and_record(2, nonce(10, 1)));
+    }
+}
@@ -0,0 +1,152 @@
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Nombres de los parametros en el archivo de configuracion (`[retry]`) y en `--retry`.
+pub const RETRY_SETTINGS: [&str; 6] = [
+    "max_attempts",
+    "initial_backoff_ms",
+    "max_backoff_ms",
+    "deadline_ms",
+    "join_attempts",
+    "election_rounds",
+];
+
+/// Politica de reintentos de una operacion que depende del coordinador (como `add_grade`).
+/// Entre intentos se espera un tiempo que se duplica en cada uno, hasta `max_backoff`, con una
+/// parte al azar para que los nodos que fallaron a la vez no reintenten todos juntos.
+/// Se deja de intentar al llegar a `max_attempts` o si el proximo intento empezaria despues de
+/// `deadline`, contado desde el primero.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub deadline: Duration,
+    /// Cantidad de veces que se pide entrar al cluster a un mismo contacto.
+    pub join_attempts: u32,
+    /// Veces que quien inicia una eleccion en anillo la vuelve a empezar si no le llega el
+    /// resultado.
+    pub election_rounds: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            deadline: Duration::from_secs(60),
+            join_attempts: 3,
+            election_rounds: 3,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Cambia el parametro `name` (uno de `RETRY_SETTINGS`), que no puede ser 0.
+    pub fn set(&mut self, name: &str, value: u64) -> Result<(), String> {
+        if value == 0 {
+            return Err(format!("Retry setting {} must be greater than 0", name));
+        }
+        match name {
+            "max_attempts" => {
+                self.max_attempts = u32::try_from(value)
+                    .map_err(|_error| format!("Too many retry attempts: {}", value))?
+            }
+            "initial_backoff_ms" => self.initial_backoff = Duration::from_millis(value),
+            "max_backoff_ms" => self.max_backoff = Duration::from_millis(value),
+            "deadline_ms" => self.deadline = Duration::from_millis(value),
+            "join_attempts" => {
+                self.join_attempts = u32::try_from(value)
+                    .map_err(|_error| format!("Too many join attempts: {}", value))?
+            }
+            "election_rounds" => {
+                self.election_rounds = u32::try_from(value)
+                    .map_err(|_error| format!("Too many election rounds: {}", value))?
+            }
+            _ => {
+                return Err(format!(
+                    "Unknown retry setting {:?}. Available: {}",
+                    name,
+                    RETRY_SETTINGS.join(", ")
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Espera antes del intento `attempt + 1`, despues de que fallara el intento `attempt`
+    /// (empezando en 1). Es un valor entre la mitad y el total del backoff exponencial, elegido
+    /// a partir de `random`.
+    pub fn backoff(&self, attempt: u32, random: u64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let half = backoff.as_millis() as u64 / 2;
+        Duration::from_millis(backoff.as_millis() as u64 - half + random % (half + 1))
+    }
+
+    /// Si se puede hacer otro intento despues de `attempt` intentos fallidos, sabiendo que
+    /// desde el primero paso `elapsed` y que antes del proximo se esperaria `backoff`.
+    pub fn allows_retry(&self, attempt: u32, elapsed: Duration, backoff: Duration) -> bool {
+        attempt < self.max_attempts && elapsed + backoff < self.deadline
+    }
+
+    /// Tiempo que queda antes de `deadline`, si desde el primer intento paso `elapsed`.
+    pub fn remaining(&self, elapsed: Duration) -> Duration {
+        self.deadline.saturating_sub(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_exponentially_up_to_max() {
+        let policy = RetryPolicy::default();
+        let expected = [500, 1000, 2000, 4000, 8000, 8000];
+        for (attempt, millis) in (1..).zip(expected) {
+            let full = Duration::from_millis(millis);
+            assert_eq!(policy.backoff(attempt, 0), full / 2);
+            assert_eq!(policy.backoff(attempt, millis / 2), full);
+            let jittered = policy.backoff(attempt, 12345);
+            assert!(full / 2 <= jittered && jittered <= full);
+        }
+        assert_eq!(policy.backoff(100, 0), Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_allows_retry() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            deadline: Duration::from_secs(10),
+            ..RetryPolicy::default()
+        };
+        let second = Duration::from_secs(1);
+        assert!(policy.allows_retry(1, second, second));
+        assert!(policy.allows_retry(2, second, second));
+        assert!(!policy.allows_retry(3, second, second));
+        assert!(!policy.allows_retry(1, 9 * second, second));
+        assert_eq!(policy.remaining(second), 9 * second);
+        assert_eq!(policy.remaining(11 * second), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_set() {
+        let mut policy = RetryPolicy::default();
+        policy.set("max_attempts", 2).unwrap();
+        policy.set("deadline_ms", 1500).unwrap();
+        assert_eq!(policy.max_attempts, 2);
+        assert_eq!(policy.deadline, Duration::from_millis(1500));
+        policy.set("join_attempts", 5).unwrap();
+        assert_eq!(policy.join_attempts, 5);
+        policy.set("election_rounds", 1).unwrap();
+        assert_eq!(policy.election_rounds, 1);
+        assert!(policy.set("max_attempts", 0).is_err());
+        assert!(policy.set("max_attempts", u64::MAX).is_err());
+        assert!(policy.set("jitter", 1).is_err());
+    }
+}
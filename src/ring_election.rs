@@ -0,0 +1,195 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use crate::blockchain_node::BlockchainNode;
+use crate::election_algorithm::ElectionAlgorithm;
+use crate::logger::log;
+use crate::node_identity::NodeIdentity;
+use crate::peer_status::PeerStatus;
+use crate::ring_message::RingMessage;
+
+/// Algoritmo en anillo: los nodos forman un anillo logico ordenado por ID, y cada uno le pasa
+/// los mensajes de eleccion a su sucesor, salteando a los que el detector de fallas da por
+/// caidos. Mientras no se conozca el ID de algun vecino vivo (se lo conoce por cualquier mensaje
+/// recibido de el, como los heartbeats) el anillo se ordena por direccion, que se conoce de
+/// todos, para no dejar a nadie afuera.
+pub struct RingElection;
+
+impl RingElection {
+    /// Siguiente nodo vivo del anillo despues del propio, si hay alguno.
+    fn successor(node: &BlockchainNode) -> Option<SocketAddr> {
+        let alive = node
+            .neighbor_addresses()
+            .iter()
+            .filter(|neighbor| {
+                let is_dead = node.peer_status(neighbor) == PeerStatus::Dead;
+                if is_dead {
+                    log(format!(
+                        "\t\tSkipping dead ring successor at {:?}",
+                        neighbor
+                    ));
+                }
+                !is_dead
+            })
+            .copied()
+            .collect::<Vec<SocketAddr>>();
+        let by_id = alive
+            .iter()
+            .map(|neighbor| {
+                node.identity_at(neighbor)
+                    .map(|identity| (identity.id, *neighbor))
+            })
+            .collect::<Option<Vec<(u64, SocketAddr)>>>();
+        match by_id {
+            Some(members) => RingElection::next_in_ring(node.identity().id, members),
+            None => {
+                log(
+                    "\t\tSome ring members' IDs are unknown. Ordering the ring by address"
+                        .to_string(),
+                );
+                let members = alive
+                    .iter()
+                    .map(|neighbor| (*neighbor, *neighbor))
+                    .collect();
+                RingElection::next_in_ring(node.address(), members)
+            }
+        }
+    }
+
+    /// Direccion del miembro que sigue a `own` en el anillo ordenado por la clave de cada uno.
+    fn next_in_ring<K: Ord>(own: K, mut members: Vec<(K, SocketAddr)>) -> Option<SocketAddr> {
+        members.sort();
+        members
+            .iter()
+            .find(|(key, _)| *key > own)
+            .or_else(|| members.first())
+            .map(|(_, neighbor)| *neighbor)
+    }
+
+    /// Le pasa el mensaje al sucesor. Devuelve `false` si no hay ningun otro nodo vivo.
+    fn forward(node: &BlockchainNode, message: &RingMessage) -> bool {
+        match RingElection::successor(node) {
+            Some(successor) => {
+                log(format!("\t\tSending {:?} to {:?}", message, successor));
+                node.send(message.as_string().as_bytes(), &successor);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// La eleccion dio la vuelta al anillo: gana el mayor de los participantes y se empieza a
+    /// pasar el resultado, con el term mas alto que conoce el nodo.
+    fn close_election(node: &mut BlockchainNode, participants: Vec<(NodeIdentity, SocketAddr)>) {
+        let own = (node.identity(), node.address());
+        let winner = participants.into_iter().max().unwrap_or(own);
+        log(format!(
+            "La eleccion dio la vuelta al anillo. Gana {} en {:?}",
+            winner.0, winner.1
+        ));
+        let term = node.term();
+        node.set_coordinator(winner.0, winner.1, term);
+        RingElection::forward(node, &RingMessage::Coordinator(term, winner, vec![own]));
+    }
+}
+
+impl ElectionAlgorithm for RingElection {
+    fn begin_election(&self, arc_mutex_self: Arc<Mutex<BlockchainNode>>) {
+        let rounds = arc_mutex_self
+            .lock()
+            .unwrap()
+            .retry_policy()
+            .election_rounds;
+        for round in 1..=rounds {
+            let (in_election, clock, timeout) = {
+                let mut _self = arc_mutex_self.lock().unwrap();
+                if round == 1 && _self.start_election().is_none() {
+                    return;
+                }
+                let own = (_self.identity(), _self.address());
+                let election = RingMessage::Election(_self.term(), vec![own]);
+                if !RingElection::forward(&_self, &election) {
+                    log("No hay otros nodos vivos en el anillo. Soy el coordinador.".to_string());
+                    _self.make_leader();
+                    _self.end_election();
+                    return;
+                }
+                // La eleccion y su resultado dan una vuelta al anillo cada uno.
+                let hops = 2 * (_self.neighbor_addresses().len() as u32 + 1);
+                (
+                    _self.election_in_progress(),
+                    _self.clock(),
+                    _self.timeouts().ok_election * hops,
+                )
+            };
+            if clock.wait_until(&in_election, false, timeout) {
+                return;
+            }
+            log(format!(
+                "No llego el resultado de la eleccion en anillo (ronda {} de {})",
+                round, rounds
+            ));
+        }
+        log("Abandonando la eleccion en anillo sin resultado".to_string());
+        arc_mutex_self.lock().unwrap().end_election();
+    }
+
+    fn process_message(
+        &self,
+        arc_mutex_self: Arc<Mutex<BlockchainNode>>,
+        message: &str,
+        _sender: &SocketAddr,
+        _sender_identity: NodeIdentity,
+    ) -> bool {
+        let message = match RingMessage::from_string(message) {
+            Some(message) => message,
+            None => return false,
+        };
+        let mut _self = arc_mutex_self.lock().unwrap();
+        let own = (_self.identity(), _self.address());
+        match message {
+            RingMessage::Election(term, mut participants) => {
+                _self.observe_term(term);
+                if participants
+                    .iter()
+                    .any(|(identity, _)| identity.id == own.0.id)
+                {
+                    RingElection::close_election(&mut _self, participants);
+                } else {
+                    participants.push(own);
+                    if RingElection::successor(&_self).is_some() {
+                        let election = RingMessage::Election(_self.term(), participants);
+                        RingElection::forward(&_self, &election);
+                    } else {
+                        RingElection::close_election(&mut _self, participants);
+                    }
+                }
+            }
+            RingMessage::Coordinator(term, coordinator, mut notified) => {
+                if notified.iter().any(|(identity, _)| identity.id == own.0.id) {
+                    log(format!(
+                        "El coordinador {} ya dio la vuelta al anillo",
+                        coordinator.0
+                    ));
+                    return true;
+                }
+                drop(_self);
+                // Un anuncio de un term anterior no se sigue pasando.
+                if BlockchainNode::receive_coordinator(
+                    arc_mutex_self.clone(),
+                    coordinator.0,
+                    coordinator.1,
+                    term,
+                ) {
+                    notified.push(own);
+                    let _self = arc_mutex_self.lock().unwrap();
+                    RingElection::forward(
+                        &_self,
+                        &RingMessage::Coordinator(term, coordinator, notified),
+                    );
+                }
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,127 @@
+use std::net::SocketAddr;
+
+use crate::node_identity::{self, NodeIdentity};
+
+/// Mensajes de la eleccion en anillo. Cada nodo se los pasa a su sucesor en el anillo.
+/// - **Election**: Lleva el term de la eleccion y a los nodos por los que paso, en orden (el
+///   primero es quien inicio la eleccion). Cada nodo se agrega al final; cuando le llega a uno
+///   que ya esta en la lista, dio la vuelta al anillo y gana el de mayor prioridad (y a igual
+///   prioridad, mayor ID).
+/// - **Coordinator**: Lleva el term, al ganador y a los nodos que ya se enteraron. Da la vuelta
+///   al anillo hasta llegarle a uno que ya esta en la lista.
+///
+/// Cada `<nodo>` es `<id>,<prioridad>,<direccion>`.
+///
+/// |     Tipo      |  Representacion                                           |
+/// |---------------|-----------------------------------------------------------|
+/// |   Election    |   RING_ELECTION;<term>;<nodo>;<nodo>;...                  |
+/// |   Coordinator |   RING_COORDINATOR;<term>;<ganador>;<nodo>;<nodo>;...     |
+#[derive(PartialEq, Debug)]
+pub enum RingMessage {
+    Election(u64, Vec<(NodeIdentity, SocketAddr)>),
+    Coordinator(
+        u64,
+        (NodeIdentity, SocketAddr),
+        Vec<(NodeIdentity, SocketAddr)>,
+    ),
+}
+
+impl RingMessage {
+    pub fn as_string(&self) -> String {
+        let (kind, term, nodes) = match self {
+            RingMessage::Election(term, participants) => {
+                ("RING_ELECTION", term, participants.iter().collect())
+            }
+            RingMessage::Coordinator(term, coordinator, notified) => (
+                "RING_COORDINATOR",
+                term,
+                std::iter::once(coordinator)
+                    .chain(notified)
+                    .collect::<Vec<&(NodeIdentity, SocketAddr)>>(),
+            ),
+        };
+        vec![String::from(kind), term.to_string()]
+            .into_iter()
+            .chain(
+                nodes
+                    .iter()
+                    .map(|(identity, address)| node_identity::format_node(identity, address)),
+            )
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+
+    pub fn from_string(string: &str) -> Option<RingMessage> {
+        let tokens = string.split(';').collect::<Vec<&str>>();
+        let term = tokens.get(1)?.parse::<u64>().ok()?;
+        let nodes = tokens
+            .get(2..)?
+            .iter()
+            .map(|node| node_identity::parse_node(node))
+            .collect::<Option<Vec<(NodeIdentity, SocketAddr)>>>()?;
+        match (tokens[0], &nodes[..]) {
+            ("RING_ELECTION", [_, ..]) => Some(RingMessage::Election(term, nodes)),
+            ("RING_COORDINATOR", [coordinator, notified @ ..]) => Some(RingMessage::Coordinator(
+                term,
+                *coordinator,
+                notified.to_vec(),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u64, priority: u64) -> (NodeIdentity, SocketAddr) {
+        (
+            NodeIdentity::new(id, priority),
+            SocketAddr::from(([127, 0, 0, 1], id as u16)),
+        )
+    }
+
+    #[test]
+    fn test_as_string() {
+        assert_eq!(
+            RingMessage::Election(3, vec![node(6060, 0), node(6061, 2)]).as_string(),
+            "RING_ELECTION;3;6060,0,127.0.0.1:6060;6061,2,127.0.0.1:6061"
+        );
+        assert_eq!(
+            RingMessage::Coordinator(3, node(6061, 2), vec![node(6060, 0)]).as_string(),
+            "RING_COORDINATOR;3;6061,2,127.0.0.1:6061;6060,0,127.0.0.1:6060"
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for message in [
+            RingMessage::Election(1, vec![node(6060, 0)]),
+            RingMessage::Election(2, vec![node(6060, 0), node(6061, 2), node(6062, 1)]),
+            RingMessage::Coordinator(2, node(6061, 2), vec![node(6060, 0), node(6062, 1)]),
+            RingMessage::Coordinator(7, node(6061, 2), Vec::new()),
+        ] {
+            assert_eq!(
+                RingMessage::from_string(&message.as_string()),
+                Some(message)
+            );
+        }
+    }
+
+    #[test]
+    fn test_none_from_string() {
+        for string in [
+            "RING_ELECTION",
+            "RING_COORDINATOR",
+            "RING_ELECTION;1",
+            "RING_ELECTION;1;6060,0",
+            "RING_ELECTION;6060,0,127.0.0.1:6060",
+            "RING_COORDINATOR;1;x,0,127.0.0.1:6060",
+            "ELECTION;6060,0,127.0.0.1:6060",
+            "E",
+        ] {
+            assert_eq!(RingMessage::from_string(string), None, "{}", string);
+        }
+    }
+}
@@ -1,8 +1,13 @@
-use std::net::UdpSocket;
+use std::net::{SocketAddr, UdpSocket};
 
+use crate::ip_parser;
 use crate::BUFFER_SIZE;
 
-pub fn send(socket: UdpSocket, buffer: &[u8], address: &str) {
+pub fn send(socket: &UdpSocket, buffer: &[u8], address: &SocketAddr) {
+    let address = match socket.local_addr() {
+        Ok(local_address) => ip_parser::for_socket(address, &local_address),
+        Err(_error) => *address,
+    };
     for chunk in buffer.chunks(BUFFER_SIZE) {
         socket.send_to(chunk, address).unwrap();
     }
@@ -0,0 +1,35 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// Reloj del sistema.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    fn wait_until(
+        &self,
+        flag: &Arc<(Mutex<bool>, Condvar)>,
+        value: bool,
+        timeout: Duration,
+    ) -> bool {
+        let (current, _) = flag
+            .1
+            .wait_timeout_while(flag.0.lock().unwrap(), timeout, |current| *current != value)
+            .unwrap();
+        *current == value
+    }
+
+    fn spawn(&self, task: Box<dyn FnOnce() + Send>) {
+        thread::spawn(task);
+    }
+}
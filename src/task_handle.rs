@@ -0,0 +1,25 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// Tarea lanzada con `VirtualClock::spawn_task`. Se marca como terminada antes de dejar de
+/// contar como activa, asi que despues de `wait_until_idle` `is_finished` ya refleja si termino
+/// (`JoinHandle::is_finished` recien cambia cuando el thread termina de cerrarse).
+pub struct TaskHandle<T> {
+    thread: JoinHandle<T>,
+    finished: Arc<AtomicBool>,
+}
+
+impl<T> TaskHandle<T> {
+    pub fn new(thread: JoinHandle<T>, finished: Arc<AtomicBool>) -> TaskHandle<T> {
+        TaskHandle { thread, finished }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+
+    pub fn join(self) -> thread::Result<T> {
+        self.thread.join()
+    }
+}
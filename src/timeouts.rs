@@ -0,0 +1,138 @@
+use std::time::Duration;
+
+/// Nombres de los timeouts en el archivo de configuracion (`[timeouts]`) y en `--timeout`,
+/// en milisegundos.
+pub const TIMEOUT_NAMES: [&str; 16] = [
+    "ok_acquire_ms",
+    "ok_release_ms",
+    "ok_election_ms",
+    "synchronization_done_ms",
+    "leader_silence_ms",
+    "heartbeat_interval_ms",
+    "suspect_after_ms",
+    "dead_after_ms",
+    "join_ms",
+    "lock_release_ms",
+    "heads_window_ms",
+    "range_retry_ms",
+    "anti_entropy_interval_ms",
+    "reconciliation_ms",
+    "peer_exchange_interval_ms",
+    "peer_discovery_window_ms",
+];
+
+/// Tiempos maximos de espera del nodo.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Timeouts {
+    /// Espera del OK_ACQUIRE del coordinador despues de pedirle el lock.
+    pub ok_acquire: Duration,
+    /// Espera del coordinador al RELEASE de quien tiene el lock.
+    pub ok_release: Duration,
+    /// Espera de un OK despues de enviar ELECTION a los nodos con mayor ID.
+    pub ok_election: Duration,
+    /// Espera a que termine la sincronizacion de la cadena al iniciar el nodo.
+    pub synchronization_done: Duration,
+    /// Tiempo sin heartbeats del coordinador despues del cual se inicia una eleccion.
+    pub leader_silence: Duration,
+    /// Cada cuanto se envia un Ping a los vecinos (y se revisan los demas timers periodicos).
+    pub heartbeat_interval: Duration,
+    /// Tiempo sin noticias de un vecino despues del cual se lo sospecha caido.
+    pub suspect_after: Duration,
+    /// Tiempo sin noticias de un vecino despues del cual se lo da por caido.
+    pub dead_after: Duration,
+    /// Espera de la respuesta a cada pedido para entrar al cluster.
+    pub join: Duration,
+    /// Espera del coordinador que sale del cluster a que se libere el lock antes de traspasarlo.
+    pub lock_release: Duration,
+    /// Tiempo que se juntan las cabezas de los vecinos antes de sincronizar la cadena.
+    pub heads_window: Duration,
+    /// Espera de un rango de bloques pedido antes de volver a pedirlo.
+    pub range_retry: Duration,
+    /// Cada cuanto se compara la cabeza de la cadena con la de un vecino al azar.
+    pub anti_entropy_interval: Duration,
+    /// Espera de las respuestas durante una reconciliacion con un vecino.
+    pub reconciliation: Duration,
+    /// Cada cuanto se intercambia la lista de vecinos con un vecino al azar.
+    pub peer_exchange_interval: Duration,
+    /// Tiempo que se espera al iniciar el nodo para conocer a los demas a partir de las semillas.
+    pub peer_discovery_window: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            ok_acquire: Duration::from_secs(5),
+            ok_release: Duration::from_secs(10),
+            ok_election: Duration::from_secs(1),
+            synchronization_done: Duration::from_secs(30),
+            leader_silence: Duration::from_secs(2),
+            heartbeat_interval: Duration::from_millis(500),
+            suspect_after: Duration::from_millis(1500),
+            dead_after: Duration::from_millis(3000),
+            join: Duration::from_secs(1),
+            lock_release: Duration::from_secs(5),
+            heads_window: Duration::from_secs(1),
+            range_retry: Duration::from_secs(1),
+            anti_entropy_interval: Duration::from_secs(5),
+            reconciliation: Duration::from_secs(2),
+            peer_exchange_interval: Duration::from_secs(5),
+            peer_discovery_window: Duration::from_secs(1),
+        }
+    }
+}
+
+impl Timeouts {
+    /// Cambia el timeout `name` (uno de `TIMEOUT_NAMES`), que no puede ser 0.
+    pub fn set(&mut self, name: &str, millis: u64) -> Result<(), String> {
+        if millis == 0 {
+            return Err(format!("Timeout {} must be greater than 0", name));
+        }
+        let timeout = match name {
+            "ok_acquire_ms" => &mut self.ok_acquire,
+            "ok_release_ms" => &mut self.ok_release,
+            "ok_election_ms" => &mut self.ok_election,
+            "synchronization_done_ms" => &mut self.synchronization_done,
+            "leader_silence_ms" => &mut self.leader_silence,
+            "heartbeat_interval_ms" => &mut self.heartbeat_interval,
+            "suspect_after_ms" => &mut self.suspect_after,
+            "dead_after_ms" => &mut self.dead_after,
+            "join_ms" => &mut self.join,
+            "lock_release_ms" => &mut self.lock_release,
+            "heads_window_ms" => &mut self.heads_window,
+            "range_retry_ms" => &mut self.range_retry,
+            "anti_entropy_interval_ms" => &mut self.anti_entropy_interval,
+            "reconciliation_ms" => &mut self.reconciliation,
+            "peer_exchange_interval_ms" => &mut self.peer_exchange_interval,
+            "peer_discovery_window_ms" => &mut self.peer_discovery_window,
+            _ => {
+                return Err(format!(
+                    "Unknown timeout {:?}. Available: {}",
+                    name,
+                    TIMEOUT_NAMES.join(", ")
+                ))
+            }
+        };
+        *timeout = Duration::from_millis(millis);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut timeouts = Timeouts::default();
+        for name in TIMEOUT_NAMES {
+            timeouts.set(name, 250).unwrap();
+        }
+        assert_eq!(timeouts.ok_acquire, Duration::from_millis(250));
+        assert_eq!(timeouts.synchronization_done, Duration::from_millis(250));
+        assert_eq!(timeouts.leader_silence, Duration::from_millis(250));
+        assert_eq!(timeouts.dead_after, Duration::from_millis(250));
+        assert_eq!(timeouts.peer_discovery_window, Duration::from_millis(250));
+        assert!(timeouts.set("ok_acquire_ms", 0).is_err());
+        assert!(timeouts.set("heartbeat_ms", 100).is_err());
+    }
+}
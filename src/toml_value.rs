@@ -0,0 +1,147 @@
+/// Valor de una clave del archivo de configuracion. Solo se soportan los tipos de TOML que usa
+/// la configuracion del nodo.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TomlValue {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+    Array(Vec<TomlValue>),
+}
+
+impl TomlValue {
+    /// Interpreta un valor completo (sin comentarios). Los arrays pueden ocupar varias lineas,
+    /// ya unidas por quien llama.
+    pub fn parse(text: &str) -> Result<TomlValue, String> {
+        let (value, rest) = parse_value(text.trim())?;
+        if !rest.trim().is_empty() {
+            return Err(format!("Unexpected text after value: {:?}", rest.trim()));
+        }
+        Ok(value)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            TomlValue::String(_) => "a string",
+            TomlValue::Integer(_) => "an integer",
+            TomlValue::Boolean(_) => "a boolean",
+            TomlValue::Array(_) => "an array",
+        }
+    }
+}
+
+/// Interpreta el valor al principio de `text` y devuelve lo que sigue.
+fn parse_value(text: &str) -> Result<(TomlValue, &str), String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        return parse_basic_string(rest);
+    }
+    if let Some(rest) = text.strip_prefix('\'') {
+        return match rest.split_once('\'') {
+            Some((string, rest)) => Ok((TomlValue::String(string.to_string()), rest)),
+            None => Err(String::from("Unterminated string")),
+        };
+    }
+    if let Some(rest) = text.strip_prefix('[') {
+        return parse_array(rest);
+    }
+    let end = text
+        .find(|character: char| character == ',' || character == ']' || character.is_whitespace())
+        .unwrap_or(text.len());
+    let (token, rest) = text.split_at(end);
+    let value = match token {
+        "true" => TomlValue::Boolean(true),
+        "false" => TomlValue::Boolean(false),
+        "" => return Err(String::from("Missing value")),
+        _ => match token.replace('_', "").parse::<i64>() {
+            Ok(integer) => TomlValue::Integer(integer),
+            Err(_error) => return Err(format!("Invalid value: {:?}", token)),
+        },
+    };
+    Ok((value, rest))
+}
+
+fn parse_basic_string(text: &str) -> Result<(TomlValue, &str), String> {
+    let mut string = String::new();
+    let mut chars = text.char_indices();
+    while let Some((index, character)) = chars.next() {
+        match character {
+            '"' => return Ok((TomlValue::String(string), &text[index + 1..])),
+            '\\' => match chars.next() {
+                Some((_, '"')) => string.push('"'),
+                Some((_, '\\')) => string.push('\\'),
+                Some((_, 'n')) => string.push('\n'),
+                Some((_, 't')) => string.push('\t'),
+                Some((_, escape)) => return Err(format!("Invalid escape: \\{}", escape)),
+                None => break,
+            },
+            _ => string.push(character),
+        }
+    }
+    Err(String::from("Unterminated string"))
+}
+
+fn parse_array(text: &str) -> Result<(TomlValue, &str), String> {
+    let mut values = Vec::new();
+    let mut rest = text.trim_start();
+    loop {
+        if let Some(after) = rest.strip_prefix(']') {
+            return Ok((TomlValue::Array(values), after));
+        }
+        if rest.is_empty() {
+            return Err(String::from("Unterminated array"));
+        }
+        let (value, after) = parse_value(rest)?;
+        values.push(value);
+        rest = after.trim_start();
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after.trim_start();
+        } else if !rest.starts_with(']') {
+            return Err(String::from("Expected ',' or ']' in array"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scalars() {
+        assert_eq!(
+            TomlValue::parse("\"a \\\"b\\\"\""),
+            Ok(TomlValue::String(String::from("a \"b\"")))
+        );
+        assert_eq!(
+            TomlValue::parse("'C:\\data'"),
+            Ok(TomlValue::String(String::from("C:\\data")))
+        );
+        assert_eq!(TomlValue::parse("6_060"), Ok(TomlValue::Integer(6060)));
+        assert_eq!(TomlValue::parse("-1"), Ok(TomlValue::Integer(-1)));
+        assert_eq!(TomlValue::parse("true"), Ok(TomlValue::Boolean(true)));
+    }
+
+    #[test]
+    fn test_parse_arrays() {
+        assert_eq!(
+            TomlValue::parse("[ \"a\", 'b', ]"),
+            Ok(TomlValue::Array(vec![
+                TomlValue::String(String::from("a")),
+                TomlValue::String(String::from("b")),
+            ]))
+        );
+        assert_eq!(TomlValue::parse("[]"), Ok(TomlValue::Array(Vec::new())));
+        assert_eq!(
+            TomlValue::parse("[[1], [2, 3]]"),
+            Ok(TomlValue::Array(vec![
+                TomlValue::Array(vec![TomlValue::Integer(1)]),
+                TomlValue::Array(vec![TomlValue::Integer(2), TomlValue::Integer(3)]),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in ["\"abc", "[1, 2", "[1 2]", "yes", "", "1 2", "\"\\q\""] {
+            assert!(TomlValue::parse(text).is_err(), "{:?}", text);
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::io;
+use std::net::SocketAddr;
+
+/// Fragmento recibido junto a la direccion de su emisor.
+pub type Datagram = (Vec<u8>, SocketAddr);
+
+/// Medio por el que los nodos intercambian bytes. En produccion es un socket UDP
+/// (`UdpTransport`); en los tests, una red en memoria con entrega controlada (`MemoryTransport`).
+///
+/// Cada mensaje se envia seguido de un `\n`, que el receptor usa para reconstruirlo a partir de
+/// los fragmentos que le lleguen. Como se envia desde varios threads (heartbeats, envios
+/// demorados por la inyeccion de fallas), los fragmentos de un mensaje no se pueden mezclar con
+/// los de otro.
+pub trait Transport: Send + Sync {
+    fn send_to(&self, buffer: &[u8], address: &SocketAddr);
+
+    /// Bloquea hasta recibir el proximo fragmento, devolviendolo junto a la direccion del emisor.
+    fn recv_from(&self) -> io::Result<Datagram>;
+}
@@ -0,0 +1,105 @@
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Mutex;
+
+use crate::sender::send;
+use crate::transport::{Datagram, Transport};
+use crate::BUFFER_SIZE;
+
+/// Transporte sobre un socket UDP: los mensajes se envian en fragmentos de `BUFFER_SIZE` bytes.
+/// Como el receptor reconstruye los mensajes juntando los fragmentos de cada emisor, los envios
+/// se hacen de a uno: si dos threads enviaran a la vez al mismo destino se mezclarian sus
+/// fragmentos.
+pub struct UdpTransport {
+    socket: UdpSocket,
+    sending: Mutex<()>,
+}
+
+impl UdpTransport {
+    /// El socket queda habilitado para enviar broadcasts, que usa el descubrimiento de vecinos.
+    pub fn bind(address: SocketAddr) -> io::Result<UdpTransport> {
+        let socket = UdpSocket::bind(address)?;
+        if address.is_ipv4() {
+            socket.set_broadcast(true)?;
+        }
+        Ok(UdpTransport {
+            socket,
+            sending: Mutex::new(()),
+        })
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send_to(&self, buffer: &[u8], address: &SocketAddr) {
+        let _sending = self.sending.lock().unwrap();
+        send(&self.socket, buffer, address);
+    }
+
+    fn recv_from(&self) -> io::Result<Datagram> {
+        let mut buf = [0; BUFFER_SIZE];
+        let (size, from) = self.socket.recv_from(&mut buf)?;
+        Ok((Vec::from(&buf[0..size]), from))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::mpsc::channel;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+    use std::time::Duration;
+
+    const SENDERS: usize = 4;
+    const MESSAGES: usize = 3;
+
+    /// Varios threads envian mensajes al mismo destino a la vez, como los heartbeats y los
+    /// envios demorados por la inyeccion de fallas. Cada mensaje tiene que llegar entero.
+    #[test]
+    fn test_concurrent_sends_to_the_same_peer_dont_interleave() {
+        let any_port = SocketAddr::from(([127, 0, 0, 1], 0));
+        let receiver = UdpTransport::bind(any_port).unwrap();
+        let receiver_address = receiver.socket.local_addr().unwrap();
+        let sender = Arc::new(UdpTransport::bind(any_port).unwrap());
+
+        let (messages, received) = channel();
+        thread::spawn(move || {
+            let mut message = Vec::new();
+            while let Ok((chunk, _from)) = receiver.recv_from() {
+                message.extend(chunk);
+                if message.ends_with(b"\n") {
+                    message.pop();
+                    let _ = messages.send(String::from_utf8_lossy(&message).to_string());
+                    message.clear();
+                }
+            }
+        });
+        let expected = (0..SENDERS)
+            .flat_map(|thread| {
+                (0..MESSAGES).map(move |index| format!("thread{}:msg{:02}", thread, index))
+            })
+            .collect::<HashSet<String>>();
+        let start = Arc::new(Barrier::new(SENDERS));
+        for thread in 0..SENDERS {
+            let sender = sender.clone();
+            let start = start.clone();
+            thread::spawn(move || {
+                start.wait();
+                for index in 0..MESSAGES {
+                    let message = format!("thread{}:msg{:02}", thread, index);
+                    sender.send_to(message.as_bytes(), &receiver_address);
+                }
+            });
+        }
+
+        for _ in 0..SENDERS * MESSAGES {
+            let message = received.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert!(
+                expected.contains(&message),
+                "Corrupted message {:?}",
+                message
+            );
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle, ThreadId};
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+#[cfg(test)]
+use crate::task_handle::TaskHandle;
+
+type Flag = Arc<(Mutex<bool>, Condvar)>;
+
+/// Un thread dormido en el reloj, hasta `deadline` o hasta que `flag` valga lo esperado.
+struct Waiter {
+    id: u64,
+    deadline: Duration,
+    flag: Option<(Flag, bool)>,
+    /// Si es una tarea lanzada con `spawn`, que deja de contar como activa mientras espera.
+    tracked: bool,
+    /// Si `advance` ya lo desperto por vencer su plazo (y lo volvio a contar como activo).
+    due: bool,
+}
+
+/// Estado del reloj: el tiempo virtual y las tareas que todavia pueden avanzar sin que pase.
+#[derive(Default)]
+struct Schedule {
+    elapsed: Duration,
+    /// Tareas lanzadas con `spawn` que no terminaron ni estan dormidas en el reloj.
+    running: usize,
+    tasks: HashSet<ThreadId>,
+    waiters: Vec<Waiter>,
+    next_waiter: u64,
+}
+
+/// Reloj virtual para los tests: el tiempo solo pasa al llamar a `advance`, asi que los
+/// timeouts de los nodos vencen exactamente cuando el test lo decide.
+///
+/// Ademas lleva la cuenta de las tareas lanzadas con `spawn`: una tarea deja de estar activa
+/// mientras duerme en el reloj y vuelve a estarlo cuando la despiertan, para que
+/// `wait_until_idle` sepa sin esperar tiempo real cuando ya no queda nada por hacer.
+pub struct VirtualClock {
+    start: Instant,
+    schedule: Arc<(Mutex<Schedule>, Condvar)>,
+}
+
+/// Marca el fin de una tarea, aunque termine con un panic.
+struct TaskGuard {
+    schedule: Arc<(Mutex<Schedule>, Condvar)>,
+    finished: Arc<AtomicBool>,
+}
+
+impl Drop for TaskGuard {
+    fn drop(&mut self) {
+        let mut schedule = self
+            .schedule
+            .0
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        self.finished.store(true, Ordering::SeqCst);
+        schedule.tasks.remove(&thread::current().id());
+        schedule.running -= 1;
+        self.schedule.1.notify_all();
+    }
+}
+
+impl VirtualClock {
+    pub fn new() -> VirtualClock {
+        VirtualClock {
+            start: Instant::now(),
+            schedule: Arc::new((Mutex::new(Schedule::default()), Condvar::new())),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Schedule> {
+        self.schedule.0.lock().unwrap()
+    }
+
+    /// Adelanta el reloj, despertando a los threads dormidos cuyo plazo haya vencido.
+    pub fn advance(&self, duration: Duration) {
+        let mut flags = Vec::new();
+        {
+            let mut schedule = self.lock();
+            schedule.elapsed += duration;
+            let elapsed = schedule.elapsed;
+            let mut woken = 0;
+            for waiter in schedule.waiters.iter_mut() {
+                if !waiter.due && waiter.deadline <= elapsed {
+                    waiter.due = true;
+                    if waiter.tracked {
+                        woken += 1;
+                    }
+                    if let Some((flag, _)) = &waiter.flag {
+                        flags.push(flag.clone());
+                    }
+                }
+            }
+            schedule.running += woken;
+            self.schedule.1.notify_all();
+        }
+        // Se toma el lock de cada flag para no perder el aviso si su thread todavia no se durmio.
+        for flag in flags {
+            let _current = flag.0.lock().unwrap();
+            flag.1.notify_all();
+        }
+    }
+
+    /// Tiempo virtual transcurrido desde la creacion del reloj.
+    pub fn elapsed(&self) -> Duration {
+        self.lock().elapsed
+    }
+
+    /// Corre `task` en un thread propio, contandola como activa hasta que termine.
+    #[cfg(test)]
+    pub fn spawn_task<T: Send + 'static>(
+        &self,
+        task: impl FnOnce() -> T + Send + 'static,
+    ) -> TaskHandle<T> {
+        let (thread, finished) = self.start_task(task);
+        TaskHandle::new(thread, finished)
+    }
+
+    /// Lanza la tarea. Devuelve su thread y la marca que se prende al terminar.
+    fn start_task<T: Send + 'static>(
+        &self,
+        task: impl FnOnce() -> T + Send + 'static,
+    ) -> (JoinHandle<T>, Arc<AtomicBool>) {
+        self.lock().running += 1;
+        let schedule = self.schedule.clone();
+        let finished = Arc::new(AtomicBool::new(false));
+        let guard_finished = finished.clone();
+        let thread = thread::spawn(move || {
+            schedule
+                .0
+                .lock()
+                .unwrap()
+                .tasks
+                .insert(thread::current().id());
+            let _guard = TaskGuard {
+                schedule,
+                finished: guard_finished,
+            };
+            task()
+        });
+        (thread, finished)
+    }
+
+    /// Espera a que todas las tareas terminen o queden dormidas sin nada que las despierte
+    /// mas que el avance del reloj.
+    pub fn wait_until_idle(&self) {
+        let mut schedule = self.lock();
+        loop {
+            if schedule.running == 0 {
+                let flags = schedule
+                    .waiters
+                    .iter()
+                    .filter(|waiter| !waiter.due)
+                    .filter_map(|waiter| {
+                        waiter
+                            .flag
+                            .clone()
+                            .map(|(flag, value)| (waiter.id, flag, value))
+                    })
+                    .collect::<Vec<(u64, Flag, bool)>>();
+                drop(schedule);
+                let ready = flags
+                    .iter()
+                    .filter(|(_, flag, value)| *flag.0.lock().unwrap() == *value)
+                    .map(|(id, _, _)| *id)
+                    .collect::<Vec<u64>>();
+                schedule = self.lock();
+                if ready.is_empty() {
+                    return;
+                }
+                // Los que ya tienen su flag todavia no se despertaron: avisan al hacerlo.
+                if !schedule
+                    .waiters
+                    .iter()
+                    .any(|waiter| ready.contains(&waiter.id))
+                {
+                    continue;
+                }
+            }
+            schedule = self.schedule.1.wait(schedule).unwrap();
+        }
+    }
+
+    /// Anota un thread que se duerme en el reloj. Si es una tarea deja de contar como activa.
+    fn add_waiter(
+        &self,
+        schedule: &mut Schedule,
+        deadline: Duration,
+        flag: Option<(Flag, bool)>,
+    ) -> u64 {
+        let id = schedule.next_waiter;
+        schedule.next_waiter += 1;
+        let tracked = schedule.tasks.contains(&thread::current().id());
+        if tracked {
+            schedule.running -= 1;
+            self.schedule.1.notify_all();
+        }
+        schedule.waiters.push(Waiter {
+            id,
+            deadline,
+            flag,
+            tracked,
+            due: false,
+        });
+        id
+    }
+
+    /// Saca al thread de los dormidos. Si lo desperto su flag (y no `advance`) vuelve a contar
+    /// como activo. Devuelve si lo desperto `advance`.
+    fn remove_waiter(&self, schedule: &mut Schedule, id: u64) -> bool {
+        let position = schedule
+            .waiters
+            .iter()
+            .position(|waiter| waiter.id == id)
+            .unwrap();
+        let waiter = schedule.waiters.remove(position);
+        if !waiter.due && waiter.tracked {
+            schedule.running += 1;
+        }
+        self.schedule.1.notify_all();
+        waiter.due
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        let mut schedule = self.lock();
+        if duration.is_zero() {
+            return;
+        }
+        let deadline = schedule.elapsed + duration;
+        let id = self.add_waiter(&mut schedule, deadline, None);
+        let mut schedule = self
+            .schedule
+            .1
+            .wait_while(schedule, |schedule| {
+                schedule
+                    .waiters
+                    .iter()
+                    .any(|waiter| waiter.id == id && !waiter.due)
+            })
+            .unwrap();
+        self.remove_waiter(&mut schedule, id);
+    }
+
+    fn wait_until(&self, flag: &Flag, value: bool, timeout: Duration) -> bool {
+        let mut current = flag.0.lock().unwrap();
+        let id = {
+            let mut schedule = self.lock();
+            if *current == value || timeout.is_zero() {
+                return *current == value;
+            }
+            let deadline = schedule.elapsed + timeout;
+            self.add_waiter(&mut schedule, deadline, Some((flag.clone(), value)))
+        };
+        loop {
+            current = flag.1.wait(current).unwrap();
+            let mut schedule = self.lock();
+            let due = schedule
+                .waiters
+                .iter()
+                .any(|waiter| waiter.id == id && waiter.due);
+            if *current == value || due {
+                self.remove_waiter(&mut schedule, id);
+                return *current == value;
+            }
+            // Despertado sin que cambie nada: se avisa por si `wait_until_idle` lo esperaba.
+            self.schedule.1.notify_all();
+        }
+    }
+
+    fn spawn(&self, task: Box<dyn FnOnce() + Send>) {
+        self.start_task(task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Avanza el reloj de a un segundo, las veces indicadas, esperando cada vez a que las tareas
+    /// se vuelvan a dormir.
+    fn advance_seconds(clock: &VirtualClock, seconds: u64) {
+        for _ in 0..seconds {
+            clock.wait_until_idle();
+            clock.advance(Duration::from_secs(1));
+        }
+        clock.wait_until_idle();
+    }
+
+    #[test]
+    fn test_sleep_ends_when_clock_advances() {
+        let clock = Arc::new(VirtualClock::new());
+        let sleeper_clock = clock.clone();
+        let sleeper = clock.spawn_task(move || {
+            let start = sleeper_clock.now();
+            sleeper_clock.sleep(Duration::from_secs(10));
+            sleeper_clock.now() - start
+        });
+        advance_seconds(&clock, 9);
+        assert!(!sleeper.is_finished());
+        advance_seconds(&clock, 1);
+        assert_eq!(sleeper.join().unwrap(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_wait_until_times_out_in_virtual_time() {
+        let clock = Arc::new(VirtualClock::new());
+        let flag = Arc::new((Mutex::new(false), Condvar::new()));
+        let (waiter_clock, waiter_flag) = (clock.clone(), flag.clone());
+        let waiter = clock.spawn_task(move || {
+            waiter_clock.wait_until(&waiter_flag, true, Duration::from_secs(3))
+        });
+        advance_seconds(&clock, 3);
+        assert!(!waiter.join().unwrap());
+        assert_eq!(clock.elapsed(), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_wait_until_sees_flag() {
+        let clock = VirtualClock::new();
+        let flag = Arc::new((Mutex::new(true), Condvar::new()));
+        assert!(clock.wait_until(&flag, true, Duration::ZERO));
+        assert_eq!(clock.now(), clock.start);
+    }
+
+    #[test]
+    fn test_idle_waits_for_tasks_woken_by_their_flag() {
+        let clock = Arc::new(VirtualClock::new());
+        let flag = Arc::new((Mutex::new(false), Condvar::new()));
+        let done = Arc::new(Mutex::new(false));
+        let (waiter_clock, waiter_flag, waiter_done) = (clock.clone(), flag.clone(), done.clone());
+        clock.spawn_task(move || {
+            if waiter_clock.wait_until(&waiter_flag, true, Duration::from_secs(3)) {
+                *waiter_done.lock().unwrap() = true;
+            }
+        });
+        clock.wait_until_idle();
+        let setter_flag = flag.clone();
+        clock.spawn_task(move || {
+            *setter_flag.0.lock().unwrap() = true;
+            setter_flag.1.notify_all();
+        });
+        clock.wait_until_idle();
+        assert!(*done.lock().unwrap());
+        assert_eq!(clock.elapsed(), Duration::ZERO);
+    }
+}
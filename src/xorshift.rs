@@ -0,0 +1,70 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Generador de numeros pseudoaleatorios xorshift64. No sirve para criptografia, solo para
+/// elegir vecinos al azar y decisiones similares.
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    /// El estado nunca puede ser 0, asi que una semilla 0 se reemplaza por una constante.
+    pub fn new(seed: u64) -> Xorshift {
+        Xorshift {
+            state: if seed == 0 {
+                0x9e37_79b9_7f4a_7c15
+            } else {
+                seed
+            },
+        }
+    }
+
+    /// Semilla a partir de la hora actual, mezclada con `salt` para que dos nodos que arrancan
+    /// a la vez no generen la misma secuencia.
+    pub fn from_time(salt: u64) -> Xorshift {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Xorshift::new(nanos ^ salt.rotate_left(32))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Numero en `[0, bound)`. `bound` tiene que ser mayor a 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Xorshift::new(42);
+        let mut b = Xorshift::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_zero_seed_is_usable() {
+        let mut rng = Xorshift::new(0);
+        assert_ne!(rng.next_u64(), 0);
+    }
+
+    #[test]
+    fn test_below_stays_in_range() {
+        let mut rng = Xorshift::new(7);
+        for _ in 0..100 {
+            assert!(rng.below(3) < 3);
+        }
+    }
+}